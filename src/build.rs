@@ -1,77 +1,95 @@
-extern crate cc;
-
-fn main() {
-    // Embed the admin manifest on Windows to require administrator privileges
-/*     #[cfg(target_os = "windows")]
-    embed_resource::compile_for("admin.manifest", &["24"], Some("1")); */
-
-    let mut shared_config = cc::Build::new();
-
-    #[cfg(target_env = "msvc")]
-    shared_config
-        .flag("/O2")
-        .flag("/Oi")
-        .flag("/Ot")
-        .flag("/Oy")
-        .flag("/GT")
-        .flag("/GL");
-
-    #[cfg(not(target_env = "msvc"))]
-    shared_config.flag("-std=c99").flag("-mtune=native");
-
-    let mut config = shared_config.clone();
-
-    config
-        .file("src/c/sph_shabal.c")
-        .file("src/c/common.c")
-        .compile("shabal");
-
-    let mut config = shared_config.clone();
-
-    #[cfg(not(target_env = "msvc"))]
-    config.flag("-msse2");
-
-    config
-        .file("src/c/mshabal_128_sse2.c")
-        .file("src/c/noncegen_128_sse2.c")
-        .compile("shabal_sse2");
-
-    let mut config = shared_config.clone();
-
-    #[cfg(target_env = "msvc")]
-    config.flag("/arch:AVX2");
-
-    #[cfg(not(target_env = "msvc"))]
-    config.flag("-mavx2");
-
-    config
-        .file("src/c/mshabal_256_avx2.c")
-        .file("src/c/noncegen_256_avx2.c")
-        .compile("shabal_avx2");
-
-    let mut config = shared_config.clone();
-
-    #[cfg(target_env = "msvc")]
-    config.flag("/arch:AVX");
-
-    #[cfg(not(target_env = "msvc"))]
-    config.flag("-mavx");
-
-    config
-        .file("src/c/mshabal_128_avx.c")
-        .file("src/c/noncegen_128_avx.c")
-        .compile("shabal_avx");
-
-    let mut config = shared_config.clone();
-
-    #[cfg(target_env = "msvc")]
-    config.flag("/arch:AVX512");
-
-    #[cfg(not(target_env = "msvc"))]
-    config.flag("-mavx512f");
-
-    config
-        .file("src/c/mshabal_512_avx512f.c")
-        .file("src/c/noncegen_512_avx512f.c")
-        .compile("shabal_avx512");
-}
\ No newline at end of file
+extern crate cc;
+
+use std::env;
+
+fn main() {
+    // Embed the admin manifest on Windows to require administrator privileges
+/*     #[cfg(target_os = "windows")]
+    embed_resource::compile_for("admin.manifest", &["24"], Some("1")); */
+
+    // the c simd backends are x86 only, other targets and pure-rust builds hash
+    // with the multi-lane rust implementation and need no c compiler
+    println!("cargo:rustc-check-cfg=cfg(c_simd)");
+    // cc prints rerun-if-env-changed, which turns off rerunning on any change
+    println!("cargo:rerun-if-changed=src/c");
+    let arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    let x86 = arch == "x86" || arch == "x86_64";
+    if !x86 || env::var_os("CARGO_FEATURE_PURE_RUST").is_some() {
+        return;
+    }
+    println!("cargo:rustc-cfg=c_simd");
+
+    // decided by the target, not the host building it
+    let msvc = env::var("CARGO_CFG_TARGET_ENV").is_ok_and(|x| x == "msvc");
+
+    let mut shared_config = cc::Build::new();
+
+    if msvc {
+        shared_config
+            .flag("/O2")
+            .flag("/Oi")
+            .flag("/Ot")
+            .flag("/Oy")
+            .flag("/GT")
+            .flag("/GL");
+    } else {
+        shared_config.flag("-std=c99").flag("-mtune=native");
+    }
+
+    let mut config = shared_config.clone();
+
+    config
+        .file("src/c/sph_shabal.c")
+        .file("src/c/common.c")
+        .compile("shabal");
+
+    let mut config = shared_config.clone();
+
+    if !msvc {
+        config.flag("-msse2");
+    }
+
+    config
+        .file("src/c/mshabal_128_sse2.c")
+        .file("src/c/noncegen_128_sse2.c")
+        .compile("shabal_sse2");
+
+    let mut config = shared_config.clone();
+
+    if msvc {
+        config.flag("/arch:AVX2");
+    } else {
+        config.flag("-mavx2");
+    }
+
+    config
+        .file("src/c/mshabal_256_avx2.c")
+        .file("src/c/noncegen_256_avx2.c")
+        .compile("shabal_avx2");
+
+    let mut config = shared_config.clone();
+
+    if msvc {
+        config.flag("/arch:AVX");
+    } else {
+        config.flag("-mavx");
+    }
+
+    config
+        .file("src/c/mshabal_128_avx.c")
+        .file("src/c/noncegen_128_avx.c")
+        .compile("shabal_avx");
+
+    let mut config = shared_config.clone();
+
+    if msvc {
+        config.flag("/arch:AVX512");
+    } else {
+        config.flag("-mavx512f");
+    }
+
+    config
+        .file("src/c/mshabal_512_avx512f.c")
+        .file("src/c/noncegen_512_avx512f.c")
+        .compile("shabal_avx512");
+}
@@ -0,0 +1,350 @@
+use crate::kernel_tuning::KernelSetting;
+use crate::plotter::{Plotter, PlotterTask};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// 4 GiB of hashes per trial, twice the largest memory candidate so every buffer
+// size actually gets used
+pub const DEFAULT_TRIAL_NONCES: u64 = 16384;
+const MEM_CANDIDATES: [u64; 3] = [512 << 20, 1 << 30, 2 << 30];
+// share of each gpu's compute units to try, in percent
+const GPU_SHARES: [u64; 3] = [100, 75, 50];
+
+// the winning settings of an autotune run, saved per machine
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TunedConfig {
+    // 0 hashes on the gpus only
+    pub cpu_threads: u32,
+    pub mem: String,
+    // platform:device:cores, None plots on the cpu only
+    pub gpus: Option<Vec<String>>,
+    pub zcb: bool,
+    pub async_io: bool,
+    pub nonces_per_minute: f64,
+}
+
+impl TunedConfig {
+    // one file per host, config dirs may be shared between machines
+    pub fn path() -> Option<PathBuf> {
+        let host = sys_info::hostname().unwrap_or_else(|_| "localhost".to_string());
+        eframe::storage_dir("Signum Plotter GUI")
+            .map(|dir| dir.join(format!("autotune-{}.json", host)))
+    }
+
+    pub fn load() -> Option<TunedConfig> {
+        let data = fs::read_to_string(TunedConfig::path()?).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    pub fn save(&self) -> io::Result<PathBuf> {
+        let path = TunedConfig::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let data = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(&path, data)?;
+        Ok(path)
+    }
+}
+
+// candidate values per setting, the first ones are where the search starts
+#[derive(Clone, Debug)]
+pub struct SearchSpace {
+    pub cpu_threads: Vec<u32>,
+    pub mem: Vec<u64>,
+    // "platform:device" and compute units of every gpu to plot with
+    pub gpus: Option<Vec<(String, usize)>>,
+    pub gpu_shares: Vec<u64>,
+    pub zcb: Vec<bool>,
+    pub async_io: Vec<bool>,
+}
+
+impl SearchSpace {
+    // a handful of candidates sized to the machine, memory candidates leave a
+    // quarter of the available memory alone
+    pub fn for_machine(cores: u32, avail_mem: u64, gpus: Option<Vec<(String, usize)>>) -> SearchSpace {
+        let gpu = gpus.is_some();
+        let mut cpu_threads = vec![cores, cores * 3 / 4, cores / 2];
+        // gpu only
+        if gpu {
+            cpu_threads.push(0);
+        }
+        cpu_threads.retain(|&x| x > 0 || gpu);
+        cpu_threads.dedup();
+
+        let mut mem: Vec<u64> = MEM_CANDIDATES
+            .iter()
+            .copied()
+            .filter(|&x| x <= avail_mem / 4 * 3)
+            .collect();
+        if mem.is_empty() {
+            mem.push(MEM_CANDIDATES[0]);
+        }
+
+        SearchSpace {
+            cpu_threads,
+            mem,
+            gpus,
+            gpu_shares: if gpu { GPU_SHARES.to_vec() } else { vec![100] },
+            zcb: if gpu { vec![false, true] } else { vec![false] },
+            async_io: vec![true, false],
+        }
+    }
+
+    fn dimensions(&self) -> [usize; 5] {
+        [
+            self.cpu_threads.len(),
+            self.mem.len(),
+            self.gpu_shares.len(),
+            self.zcb.len(),
+            self.async_io.len(),
+        ]
+    }
+
+    fn config(&self, point: [usize; 5]) -> TunedConfig {
+        let share = self.gpu_shares[point[2]];
+        TunedConfig {
+            cpu_threads: self.cpu_threads[point[0]],
+            mem: format!("{}MiB", self.mem[point[1]] >> 20),
+            gpus: self.gpus.as_ref().map(|gpus| {
+                gpus.iter()
+                    .map(|(spec, units)| {
+                        format!("{}:{}", spec, (*units as u64 * share / 100).max(1))
+                    })
+                    .collect()
+            }),
+            zcb: self.zcb[point[3]],
+            async_io: self.async_io[point[4]],
+            nonces_per_minute: 0.0,
+        }
+    }
+}
+
+// Coordinate descent: each setting in turn is set to its best candidate while the
+// others keep the best values found so far. Far fewer trials than the full grid,
+// and the settings hardly interact. Every trial is reported once measured.
+pub fn search(
+    space: &SearchSpace,
+    mut measure: impl FnMut(&TunedConfig) -> Option<f64>,
+    mut report: impl FnMut(&TunedConfig),
+) -> Option<TunedConfig> {
+    let dimensions = space.dimensions();
+    let mut results: HashMap<[usize; 5], f64> = HashMap::new();
+    let mut best = [0; 5];
+    for (dimension, &candidates) in dimensions.iter().enumerate() {
+        for candidate in 0..candidates {
+            let mut point = best;
+            point[dimension] = candidate;
+            results.entry(point).or_insert_with(|| {
+                let mut config = space.config(point);
+                // failed trials count as the slowest possible
+                config.nonces_per_minute = measure(&config).unwrap_or(0.0);
+                report(&config);
+                config.nonces_per_minute
+            });
+            if results[&point] > results[&best] {
+                best = point;
+            }
+        }
+    }
+
+    let mut config = space.config(best);
+    config.nonces_per_minute = results[&best];
+    if config.nonces_per_minute > 0.0 {
+        Some(config)
+    } else {
+        None
+    }
+}
+
+// hashes trial_nonces in benchmark mode, nothing is written to output_path
+pub fn run_trial(config: &TunedConfig, output_path: &str, trial_nonces: u64) -> Option<f64> {
+    let task = PlotterTask {
+        numeric_id: 0,
+        start_nonce: 0,
+        nonces: trial_nonces,
+        output_path: output_path.to_string(),
+        mem: config.mem.clone(),
+        cpu_threads: config.cpu_threads,
+        gpu_only: config.cpu_threads == 0,
+        gpus: config.gpus.clone(),
+        direct_io: false,
+        async_io: config.async_io,
+        quiet: true,
+        benchmark: true,
+        zcb: config.zcb,
+        host_shuffle: false,
+        gpu_check: None,
+        kernel: Vec::new(),
+        transpose_path: None,
+        write_backend: Default::default(),
+        buffers: 0,
+        huge_pages: Default::default(),
+        lock_memory: false,
+        pinning: Default::default(),
+        adaptive_memory: false,
+        report_path: None,
+        simd: None,
+    };
+    measure_task(task)
+}
+
+// nonces per minute of a benchmark task, None if it failed
+pub fn measure_task(task: PlotterTask) -> Option<f64> {
+    match Plotter::new().run(vec![task]) {
+        Ok(x) if x.nonces > 0 => Some(x.nonces_per_minute()),
+        Ok(_) => None,
+        Err(e) => {
+            println!("Trial failed: {}", e);
+            None
+        }
+    }
+}
+
+// one run of a kernel comparison, variant None is the task's own settings
+#[derive(Clone, Debug, PartialEq)]
+pub struct KernelTrial {
+    pub variant: Option<KernelSetting>,
+    pub nonces_per_minute: f64,
+}
+
+// Measures the task as given, then once per variant added to its kernel
+// settings, e.g. other build options or hashes per run. Every trial is
+// reported once measured, failed ones at 0.
+pub fn compare_kernels(
+    task: &PlotterTask,
+    variants: &[KernelSetting],
+    mut measure: impl FnMut(PlotterTask) -> Option<f64>,
+    mut report: impl FnMut(&KernelTrial),
+) -> Vec<KernelTrial> {
+    let mut trials = Vec::new();
+    for variant in std::iter::once(None).chain(variants.iter().map(Some)) {
+        let mut trial_task = task.clone();
+        trial_task.kernel.extend(variant.cloned());
+        let trial = KernelTrial {
+            variant: variant.cloned(),
+            nonces_per_minute: measure(trial_task).unwrap_or(0.0),
+        };
+        report(&trial);
+        trials.push(trial);
+    }
+    trials
+}
+
+#[cfg(test)]
+mod autotune_tests {
+    use super::*;
+
+    #[test]
+    fn search_finds_the_best_settings() {
+        let space = SearchSpace::for_machine(
+            16,
+            64 << 30,
+            Some(vec![("0:0".to_string(), 40)]),
+        );
+        assert_eq!(space.cpu_threads, vec![16, 12, 8, 0]);
+        assert_eq!(space.mem.len(), 3);
+
+        // a gpu box that plots fastest with 8 cpu threads, 30 gpu cores and zcb
+        let mut trials = 0;
+        let best = search(
+            &space,
+            |config| {
+                let mut rate = 10000.0;
+                rate -= (config.cpu_threads as f64 - 8.0).abs() * 100.0;
+                if config.gpus.as_ref().unwrap()[0] == "0:0:30" {
+                    rate += 500.0;
+                }
+                if config.zcb {
+                    rate += 200.0;
+                }
+                if !config.async_io {
+                    rate -= 1000.0;
+                }
+                Some(rate)
+            },
+            |_| trials += 1,
+        )
+        .unwrap();
+        assert_eq!(best.cpu_threads, 8);
+        assert_eq!(best.gpus, Some(vec!["0:0:30".to_string()]));
+        assert!(best.zcb);
+        assert!(best.async_io);
+        assert_eq!(best.nonces_per_minute, 10700.0);
+        // one pass over every setting, the starting point isn't measured twice
+        assert_eq!(trials, 4 + 3 + 3 + 2 + 2 - 4);
+
+        // nothing worked
+        assert_eq!(search(&space, |_| None, |_| {}), None);
+    }
+
+    #[test]
+    fn kernel_variants_run_on_top_of_the_task() {
+        let mut task = PlotterTask {
+            numeric_id: 0,
+            start_nonce: 0,
+            nonces: 1024,
+            output_path: String::new(),
+            mem: "0B".to_string(),
+            cpu_threads: 0,
+            gpu_only: true,
+            gpus: Some(vec!["0".to_string()]),
+            direct_io: false,
+            async_io: true,
+            quiet: true,
+            benchmark: true,
+            zcb: false,
+            host_shuffle: false,
+            gpu_check: None,
+            kernel: Vec::new(),
+            transpose_path: None,
+            write_backend: Default::default(),
+            buffers: 0,
+            huge_pages: Default::default(),
+            lock_memory: false,
+            pinning: Default::default(),
+            adaptive_memory: false,
+            report_path: None,
+            simd: None,
+        };
+        task.kernel.push("options=-cl-mad-enable".parse().unwrap());
+        let variants: Vec<KernelSetting> = vec!["hashes=64".parse().unwrap(), "hashes=8".parse().unwrap()];
+
+        // faster with more hashes per run, 8 makes the kernel fail
+        let mut reported = 0;
+        let trials = compare_kernels(
+            &task,
+            &variants,
+            |trial| {
+                assert_eq!(trial.kernel[0], task.kernel[0]);
+                match trial.kernel.get(1).and_then(|x| x.hashes_per_run) {
+                    None => Some(1000.0),
+                    Some(64) => Some(1200.0),
+                    Some(_) => None,
+                }
+            },
+            |_| reported += 1,
+        );
+        assert_eq!(reported, 3);
+        assert_eq!(trials[0].variant, None);
+        assert_eq!(trials[0].nonces_per_minute, 1000.0);
+        assert_eq!(trials[1].variant, Some(variants[0].clone()));
+        assert_eq!(trials[1].nonces_per_minute, 1200.0);
+        assert_eq!(trials[2].nonces_per_minute, 0.0);
+    }
+
+    #[test]
+    fn cpu_only_space() {
+        let space = SearchSpace::for_machine(1, 1 << 30, None);
+        assert_eq!(space.cpu_threads, vec![1]);
+        assert_eq!(space.mem, vec![512 << 20]);
+        assert_eq!(space.config([0; 5]).gpus, None);
+        assert_eq!(space.config([0; 5]).mem, "512MiB");
+        assert_eq!(space.dimensions(), [1, 1, 1, 1, 2]);
+    }
+}
@@ -1,63 +1,489 @@
-use std::alloc::{alloc, dealloc, Layout};
-use std::sync::{Arc, Mutex};
-// use std::ptr;
-
-pub struct PageAlignedByteBuffer {
-    data: Option<Arc<Mutex<Vec<u8>>>>,
-    pointer: *mut u8,
-    layout: Layout,
-}
-
-impl PageAlignedByteBuffer {
-    pub fn new(buffer_size: usize) -> Self {
-        let alignment = page_size::get();
-        let layout = Layout::from_size_align(buffer_size, alignment)
-            .expect("Invalid layout for page-aligned buffer");
-
-        unsafe {
-            let pointer = alloc(layout);
-
-            if pointer.is_null() {
-                std::alloc::handle_alloc_error(layout);
-            }
-
-            let data = Vec::from_raw_parts(pointer, buffer_size, buffer_size);
-
-            PageAlignedByteBuffer {
-                data: Some(Arc::new(Mutex::new(data))),
-                pointer,
-                layout,
-            }
-        }
-    }
-
-    pub fn get_buffer(&self) -> Arc<Mutex<Vec<u8>>> {
-        self.data.as_ref().unwrap().clone()
-    }
-}
-
-impl Drop for PageAlignedByteBuffer {
-    fn drop(&mut self) {
-        // Prevent the Vec from dropping (and attempting to free) the memory
-        std::mem::forget(self.data.take().unwrap());
-
-        unsafe {
-            dealloc(self.pointer, self.layout);
-        }
-    }
-}
-
-unsafe impl Send for PageAlignedByteBuffer {}
-
-#[cfg(test)]
-mod buffer_tests {
-    use super::PageAlignedByteBuffer;
-
-    #[test]
-    fn buffer_creation_destruction_test() {
-        {
-            let _test = PageAlignedByteBuffer::new(1024 * 1024);
-        }
-        assert!(true);
-    }
-}
\ No newline at end of file
+use crate::plotter::{NONCE_SIZE, NUM_SCOOPS, SCOOP_SIZE};
+use std::alloc::{alloc, dealloc, Layout};
+use std::fmt;
+use std::ops::Range;
+use std::slice::{from_raw_parts, from_raw_parts_mut};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+// page backing of plotting buffers, big buffers suffer from tlb misses with normal pages
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum HugePages {
+    #[default]
+    Normal,
+    // transparent huge pages requested with madvise
+    Transparent,
+    // hugetlbfs pages, need to be reserved by the admin (vm.nr_hugepages)
+    Explicit2M,
+    Explicit1G,
+}
+
+impl HugePages {
+    // next strategy to try if this one can't be had
+    fn fallback(self) -> HugePages {
+        match self {
+            HugePages::Explicit1G => HugePages::Explicit2M,
+            HugePages::Explicit2M => HugePages::Transparent,
+            _ => HugePages::Normal,
+        }
+    }
+}
+
+impl FromStr for HugePages {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<HugePages, String> {
+        match s.trim().to_lowercase().as_str() {
+            "normal" | "none" | "" => Ok(HugePages::Normal),
+            "transparent" | "thp" => Ok(HugePages::Transparent),
+            "2mib" | "2m" => Ok(HugePages::Explicit2M),
+            "1gib" | "1g" => Ok(HugePages::Explicit1G),
+            _ => Err(format!("unknown page size: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for HugePages {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HugePages::Normal => write!(f, "normal"),
+            HugePages::Transparent => write!(f, "transparent"),
+            HugePages::Explicit2M => write!(f, "2MiB"),
+            HugePages::Explicit1G => write!(f, "1GiB"),
+        }
+    }
+}
+
+// how a buffer is (or should be) allocated
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Allocation {
+    pub pages: HugePages,
+    // mlock the buffer so it can't be swapped out
+    pub locked: bool,
+}
+
+impl fmt::Display for Allocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} pages{}", self.pages, if self.locked { ", locked" } else { "" })
+    }
+}
+
+enum Backing {
+    Heap(Layout),
+    #[cfg(target_os = "linux")]
+    Mapped(usize),
+}
+
+// The allocation itself, shared with the views lent out of it so it outlives
+// all of them.
+struct Memory {
+    pointer: *mut u8,
+    len: usize,
+    backing: Backing,
+    locked: bool,
+    // nonce ranges lent out and not yet returned
+    lent: Mutex<Vec<Range<u64>>>,
+}
+
+// only reached through the buffer or disjoint views
+unsafe impl Send for Memory {}
+unsafe impl Sync for Memory {}
+
+impl Memory {
+    // forgets a range or part of it when a view is dropped or split
+    fn give_back(&self, nonces: &Range<u64>) {
+        let mut lent = self.lent.lock().unwrap();
+        let i = lent
+            .iter()
+            .position(|x| *x == *nonces)
+            .expect("view not lent out");
+        lent.swap_remove(i);
+    }
+}
+
+impl Drop for Memory {
+    fn drop(&mut self) {
+        if self.locked {
+            unlock(self.pointer, self.len);
+        }
+        unsafe {
+            match self.backing {
+                Backing::Heap(layout) => dealloc(self.pointer, layout),
+                #[cfg(target_os = "linux")]
+                Backing::Mapped(len) => {
+                    libc::munmap(self.pointer as *mut libc::c_void, len);
+                }
+            }
+        }
+    }
+}
+
+// A plot buffer in the PoC2 layout: every scoop row holds that scoop of all
+// the buffer's nonces. Hashers get disjoint nonce ranges of it as NonceViews,
+// the buffer itself can only be read or written once all of them are back.
+pub struct PageAlignedByteBuffer {
+    memory: Arc<Memory>,
+    allocation: Allocation,
+}
+
+impl PageAlignedByteBuffer {
+    pub fn new(buffer_size: usize) -> Self {
+        Self::with_allocation(buffer_size, Allocation::default())
+    }
+
+    // Tries the requested allocation and falls back towards normal, unlocked
+    // pages. allocation() tells what the buffer actually got.
+    pub fn with_allocation(buffer_size: usize, requested: Allocation) -> Self {
+        let (pointer, backing, pages) = alloc_pages(buffer_size, requested.pages);
+        let locked = requested.locked && lock(pointer, buffer_size);
+
+        PageAlignedByteBuffer {
+            memory: Arc::new(Memory {
+                pointer,
+                len: buffer_size,
+                backing,
+                locked,
+                lent: Mutex::new(Vec::new()),
+            }),
+            allocation: Allocation { pages, locked },
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.memory.len
+    }
+
+    // nonces the buffer holds, the stride of its scoop rows
+    pub fn nonces(&self) -> u64 {
+        self.memory.len as u64 / NONCE_SIZE
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        self.assert_returned();
+        unsafe { from_raw_parts(self.memory.pointer, self.memory.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.assert_returned();
+        unsafe { from_raw_parts_mut(self.memory.pointer, self.memory.len) }
+    }
+
+    // Lends a range of nonces out to a hasher. Ranges outside the buffer or
+    // overlapping one that is still lent out are a scheduling bug and panic.
+    pub fn lend(&mut self, nonces: Range<u64>) -> NonceView {
+        assert!(
+            nonces.start <= nonces.end && nonces.end <= self.nonces(),
+            "nonces {:?} outside a buffer of {}",
+            nonces,
+            self.nonces()
+        );
+        let mut lent = self.memory.lent.lock().unwrap();
+        let overlap = lent
+            .iter()
+            .find(|x| x.start < nonces.end && nonces.start < x.end)
+            .cloned();
+        if overlap.is_none() {
+            lent.push(nonces.clone());
+        }
+        // not while holding the lock, the views still out give their nonces back
+        drop(lent);
+        if let Some(other) = overlap {
+            panic!("nonces {:?} overlap {:?}, still lent out", nonces, other);
+        }
+        NonceView {
+            memory: self.memory.clone(),
+            buffer_nonces: self.nonces(),
+            nonces,
+        }
+    }
+
+    pub fn allocation(&self) -> Allocation {
+        self.allocation
+    }
+
+    fn assert_returned(&self) {
+        let lent = self.memory.lent.lock().unwrap().clone();
+        assert!(lent.is_empty(), "buffer still being hashed: {:?}", lent);
+    }
+}
+
+// A range of nonces lent out of a buffer. It writes the nonces' part of every
+// scoop row and nothing else, views of the same buffer never overlap. A hasher
+// drops its view before it reports the nonces as hashed.
+pub struct NonceView {
+    memory: Arc<Memory>,
+    buffer_nonces: u64,
+    nonces: Range<u64>,
+}
+
+impl NonceView {
+    // the nonces within the buffer
+    #[cfg_attr(not(c_simd), allow(dead_code))]
+    pub fn range(&self) -> Range<u64> {
+        self.nonces.clone()
+    }
+
+    pub fn nonces(&self) -> u64 {
+        self.nonces.end - self.nonces.start
+    }
+
+    #[cfg_attr(not(c_simd), allow(dead_code))]
+    pub fn buffer_nonces(&self) -> u64 {
+        self.buffer_nonces
+    }
+
+    // the view's nonces of one scoop row, SCOOP_SIZE bytes each
+    pub fn scoop_mut(&mut self, scoop: usize) -> &mut [u8] {
+        assert!((scoop as u64) < NUM_SCOOPS, "scoop {} out of range", scoop);
+        let offset = (scoop as u64 * self.buffer_nonces + self.nonces.start) * SCOOP_SIZE;
+        unsafe {
+            from_raw_parts_mut(
+                self.memory.pointer.add(offset as usize),
+                (self.nonces() * SCOOP_SIZE) as usize,
+            )
+        }
+    }
+
+    // Base of the whole buffer for the c and opencl backends, which compute
+    // the scoop rows themselves. Only the view's nonces may be written.
+    #[cfg_attr(not(c_simd), allow(dead_code))]
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.memory.pointer
+    }
+
+    // splits off the nonces from at (counted from the view's first) on
+    pub fn split_off(&mut self, at: u64) -> NonceView {
+        assert!(at <= self.nonces(), "split at {} of {} nonces", at, self.nonces());
+        let mid = self.nonces.start + at;
+        let tail = mid..self.nonces.end;
+        {
+            let mut lent = self.memory.lent.lock().unwrap();
+            let i = lent
+                .iter()
+                .position(|x| *x == self.nonces)
+                .expect("view not lent out");
+            lent[i].end = mid;
+            lent.push(tail.clone());
+        }
+        self.nonces.end = mid;
+        NonceView {
+            memory: self.memory.clone(),
+            buffer_nonces: self.buffer_nonces,
+            nonces: tail,
+        }
+    }
+
+    // the view in pieces of size nonces, the last one may be smaller
+    #[cfg(any(feature = "opencl", test))]
+    pub fn chunks(mut self, size: u64) -> Vec<NonceView> {
+        let mut chunks = Vec::new();
+        while self.nonces() > size {
+            let rest = self.split_off(size);
+            chunks.push(self);
+            self = rest;
+        }
+        chunks.push(self);
+        chunks
+    }
+}
+
+impl Drop for NonceView {
+    fn drop(&mut self) {
+        self.memory.give_back(&self.nonces);
+    }
+}
+
+fn alloc_pages(buffer_size: usize, mut pages: HugePages) -> (*mut u8, Backing, HugePages) {
+    loop {
+        match pages {
+            HugePages::Explicit2M | HugePages::Explicit1G => {
+                #[cfg(target_os = "linux")]
+                {
+                    if let Some((pointer, len)) = map_huge_pages(buffer_size, pages) {
+                        return (pointer, Backing::Mapped(len), pages);
+                    }
+                }
+            }
+            HugePages::Transparent => {
+                // thp only backs 2 MiB aligned ranges
+                let (pointer, layout) = alloc_heap(buffer_size, HUGE_PAGE_SIZE);
+                if advise_huge_pages(pointer, buffer_size) {
+                    return (pointer, Backing::Heap(layout), pages);
+                }
+                return (pointer, Backing::Heap(layout), HugePages::Normal);
+            }
+            HugePages::Normal => {
+                let (pointer, layout) = alloc_heap(buffer_size, page_size::get());
+                return (pointer, Backing::Heap(layout), pages);
+            }
+        }
+        pages = pages.fallback();
+    }
+}
+
+fn alloc_heap(buffer_size: usize, alignment: usize) -> (*mut u8, Layout) {
+    let layout = Layout::from_size_align(buffer_size, alignment)
+        .expect("Invalid layout for page-aligned buffer");
+
+    unsafe {
+        let pointer = alloc(layout);
+
+        if pointer.is_null() {
+            std::alloc::handle_alloc_error(layout);
+        }
+        (pointer, layout)
+    }
+}
+
+// returns the mapping and its length, rounded up to whole huge pages
+#[cfg(target_os = "linux")]
+fn map_huge_pages(buffer_size: usize, pages: HugePages) -> Option<(*mut u8, usize)> {
+    let (page_size, flag) = match pages {
+        HugePages::Explicit1G => (1024 * 1024 * 1024, libc::MAP_HUGE_1GB),
+        _ => (HUGE_PAGE_SIZE, libc::MAP_HUGE_2MB),
+    };
+    let len = buffer_size.div_ceil(page_size) * page_size;
+    let pointer = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_HUGETLB | flag,
+            -1,
+            0,
+        )
+    };
+    if pointer == libc::MAP_FAILED {
+        None
+    } else {
+        Some((pointer as *mut u8, len))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn advise_huge_pages(pointer: *mut u8, len: usize) -> bool {
+    unsafe { libc::madvise(pointer as *mut libc::c_void, len, libc::MADV_HUGEPAGE) == 0 }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise_huge_pages(_pointer: *mut u8, _len: usize) -> bool {
+    false
+}
+
+// fails without privileges or with a low RLIMIT_MEMLOCK
+#[cfg(unix)]
+fn lock(pointer: *mut u8, len: usize) -> bool {
+    unsafe { libc::mlock(pointer as *const libc::c_void, len) == 0 }
+}
+
+#[cfg(unix)]
+fn unlock(pointer: *mut u8, len: usize) {
+    unsafe {
+        libc::munlock(pointer as *const libc::c_void, len);
+    }
+}
+
+#[cfg(not(unix))]
+fn lock(_pointer: *mut u8, _len: usize) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+fn unlock(_pointer: *mut u8, _len: usize) {}
+
+#[cfg(test)]
+mod buffer_tests {
+    use super::*;
+
+    #[test]
+    fn buffer_creation_destruction_test() {
+        {
+            let _test = PageAlignedByteBuffer::new(1024 * 1024);
+        }
+        assert!(true);
+    }
+
+    #[test]
+    fn allocations_fall_back_and_stay_usable() {
+        for pages in [
+            HugePages::Normal,
+            HugePages::Transparent,
+            HugePages::Explicit2M,
+            HugePages::Explicit1G,
+        ] {
+            let requested = Allocation { pages, locked: true };
+            let mut buffer = PageAlignedByteBuffer::with_allocation(3 * 1024 * 1024, requested);
+            let mut chain = vec![pages];
+            while *chain.last().unwrap() != HugePages::Normal {
+                chain.push(chain.last().unwrap().fallback());
+            }
+            assert!(chain.contains(&buffer.allocation().pages));
+            let data = buffer.as_mut_slice();
+            assert_eq!(data.as_ptr() as usize % page_size::get(), 0);
+            data[0] = 1;
+            data[3 * 1024 * 1024 - 1] = 2;
+        }
+    }
+
+    #[test]
+    fn views_write_their_nonces_of_every_scoop() {
+        let mut buffer = PageAlignedByteBuffer::new(4 * NONCE_SIZE as usize);
+        buffer.as_mut_slice().fill(0);
+        let mut view = buffer.lend(1..3);
+        let mut tail = view.split_off(1);
+        assert_eq!((view.range(), tail.range()), (1..2, 2..3));
+        for scoop in [0, 4095] {
+            view.scoop_mut(scoop).fill(1);
+            tail.scoop_mut(scoop).fill(2);
+        }
+        drop((view, tail));
+
+        let data = buffer.as_slice();
+        let nonce = |scoop: u64, nonce: u64| {
+            let offset = ((scoop * 4 + nonce) * SCOOP_SIZE) as usize;
+            &data[offset..offset + SCOOP_SIZE as usize]
+        };
+        for scoop in [0, 4095] {
+            assert!(nonce(scoop, 0).iter().all(|&x| x == 0));
+            assert!(nonce(scoop, 1).iter().all(|&x| x == 1));
+            assert!(nonce(scoop, 2).iter().all(|&x| x == 2));
+            assert!(nonce(scoop, 3).iter().all(|&x| x == 0));
+        }
+        assert!(nonce(1, 1).iter().all(|&x| x == 0));
+    }
+
+    #[test]
+    fn returned_ranges_can_be_lent_again() {
+        let mut buffer = PageAlignedByteBuffer::new(8 * NONCE_SIZE as usize);
+        let chunks = buffer.lend(0..8).chunks(3);
+        let ranges: Vec<Range<u64>> = chunks.iter().map(|x| x.range()).collect();
+        assert_eq!(ranges, vec![0..3, 3..6, 6..8]);
+        drop(chunks);
+        let _all = buffer.lend(0..8);
+    }
+
+    #[test]
+    #[should_panic(expected = "overlap")]
+    fn overlapping_views_panic() {
+        let mut buffer = PageAlignedByteBuffer::new(8 * NONCE_SIZE as usize);
+        let _a = buffer.lend(0..4);
+        let _b = buffer.lend(3..5);
+    }
+
+    #[test]
+    #[should_panic(expected = "outside")]
+    fn views_past_the_end_panic() {
+        let mut buffer = PageAlignedByteBuffer::new(8 * NONCE_SIZE as usize);
+        let _view = buffer.lend(6..9);
+    }
+
+    #[test]
+    #[should_panic(expected = "still being hashed")]
+    fn buffer_is_locked_while_lent() {
+        let mut buffer = PageAlignedByteBuffer::new(8 * NONCE_SIZE as usize);
+        let _view = buffer.lend(0..1);
+        buffer.as_slice();
+    }
+}
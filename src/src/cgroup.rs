@@ -0,0 +1,340 @@
+use crate::numa::parse_cpu_list;
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// v1 reports "no limit" as a huge page aligned number
+const UNLIMITED: u64 = 1 << 62;
+
+// Limits of the cgroup the plotter runs in (docker, systemd slices, ...), on top
+// of what the host has. None where the cgroup doesn't restrict anything.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct CgroupLimits {
+    // 0 = no cgroup found
+    pub version: u8,
+    pub memory_max: Option<u64>,
+    // usage without reclaimable page cache
+    pub memory_used: Option<u64>,
+    // cpu time quota in cpus, e.g. 2.5
+    pub cpu_quota: Option<f64>,
+    pub cpuset: Option<Vec<usize>>,
+}
+
+impl CgroupLimits {
+    pub fn detect() -> CgroupLimits {
+        match fs::read_to_string("/proc/self/cgroup") {
+            Ok(x) => {
+                let online = sys_info::cpu_num().map(|x| x as usize).unwrap_or(0);
+                CgroupLimits::read(&x, Path::new("/sys/fs/cgroup"), online)
+            }
+            Err(_) => CgroupLimits::default(),
+        }
+    }
+
+    // online: the host's online cpus (0 if unknown), a cpuset with all of them
+    // doesn't limit anything
+    fn read(proc_cgroup: &str, root: &Path, online: usize) -> CgroupLimits {
+        let mut limits = if root.join("cgroup.controllers").exists() {
+            let path = proc_cgroup
+                .lines()
+                .find_map(|x| x.strip_prefix("0::"))
+                .unwrap_or("/");
+            read_v2(&cgroup_dirs(root, path))
+        } else {
+            read_v1(proc_cgroup, root)
+        };
+        if online > 0 && limits.cpuset.as_ref().is_some_and(|x| x.len() >= online) {
+            limits.cpuset = None;
+        }
+        limits
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.memory_max.is_some() || self.cpu_quota.is_some() || self.cpuset.is_some()
+    }
+
+    pub fn memory_available(&self) -> Option<u64> {
+        self.memory_max
+            .map(|max| max.saturating_sub(self.memory_used.unwrap_or(0)))
+    }
+
+    // cpus the process can keep busy
+    pub fn cpus(&self) -> Option<usize> {
+        let quota = self.cpu_quota.map(|x| (x.ceil() as usize).max(1));
+        let cpuset = self.cpuset.as_ref().map(|x| x.len());
+        match (quota, cpuset) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    // clamps the host's memory info (KiB) to the cgroup's memory limit
+    pub fn apply(&self, mut memory: sys_info::MemInfo) -> sys_info::MemInfo {
+        if let (Some(max), Some(available)) = (self.memory_max, self.memory_available()) {
+            memory.total = memory.total.min(max / 1024);
+            memory.free = memory.free.min(available / 1024);
+            memory.avail = memory.avail.min(available / 1024);
+        }
+        memory
+    }
+}
+
+impl fmt::Display for CgroupLimits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cgroup v{}:", self.version)?;
+        match self.memory_max {
+            Some(max) => write!(
+                f,
+                " memory={:.2} GiB ({:.2} GiB used)",
+                max as f64 / 1024.0 / 1024.0 / 1024.0,
+                self.memory_used.unwrap_or(0) as f64 / 1024.0 / 1024.0 / 1024.0
+            )?,
+            None => write!(f, " memory=unlimited")?,
+        }
+        match self.cpu_quota {
+            Some(quota) => write!(f, ", cpu quota={:.2}", quota)?,
+            None => write!(f, ", cpu quota=unlimited")?,
+        }
+        if let Some(cpuset) = &self.cpuset {
+            write!(f, ", cpuset={} cpus", cpuset.len())?;
+        }
+        Ok(())
+    }
+}
+
+// the cgroup's directory and its parents up to the mount, innermost first. Inside a
+// cgroup namespace the listed path doesn't exist below the mount, the mount is the cgroup.
+fn cgroup_dirs(mount: &Path, path: &str) -> Vec<PathBuf> {
+    let mut dir = mount.join(path.trim_start_matches('/'));
+    if !dir.exists() {
+        dir = mount.to_path_buf();
+    }
+    let mut dirs = vec![dir.clone()];
+    while dir != mount {
+        match dir.parent() {
+            Some(x) => dir = x.to_path_buf(),
+            None => break,
+        }
+        dirs.push(dir.clone());
+    }
+    dirs
+}
+
+fn read_value(dir: &Path, file: &str) -> Option<String> {
+    fs::read_to_string(dir.join(file))
+        .ok()
+        .map(|x| x.trim().to_string())
+}
+
+fn read_number(dir: &Path, file: &str) -> Option<u64> {
+    read_value(dir, file).and_then(|x| x.parse().ok())
+}
+
+fn stat_value(dir: &Path, file: &str, key: &str) -> Option<u64> {
+    read_value(dir, file)?.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        if parts.next() == Some(key) {
+            parts.next().and_then(|x| x.parse().ok())
+        } else {
+            None
+        }
+    })
+}
+
+// smallest limit along the hierarchy wins
+fn min_limit(limits: impl Iterator<Item = u64>) -> Option<u64> {
+    limits.filter(|&x| x < UNLIMITED).min()
+}
+
+fn min_quota(quotas: impl Iterator<Item = f64>) -> Option<f64> {
+    quotas.fold(None, |min: Option<f64>, x| Some(min.map_or(x, |min| min.min(x))))
+}
+
+// "max 100000" or "<quota> <period>"
+fn parse_cpu_max(value: &str) -> Option<f64> {
+    let mut parts = value.split_whitespace();
+    let quota: f64 = parts.next()?.parse().ok()?;
+    let period: f64 = parts.next().unwrap_or("100000").parse().ok()?;
+    if period > 0.0 {
+        Some(quota / period)
+    } else {
+        None
+    }
+}
+
+fn read_v2(dirs: &[PathBuf]) -> CgroupLimits {
+    let leaf = &dirs[0];
+    let memory_max = min_limit(
+        dirs.iter()
+            .filter_map(|dir| read_value(dir, "memory.max"))
+            .filter_map(|x| x.parse().ok()),
+    );
+    let memory_used = read_number(leaf, "memory.current").map(|current| {
+        current.saturating_sub(stat_value(leaf, "memory.stat", "inactive_file").unwrap_or(0))
+    });
+    let cpu_quota = min_quota(
+        dirs.iter()
+            .filter_map(|dir| read_value(dir, "cpu.max"))
+            .filter_map(|x| parse_cpu_max(&x)),
+    );
+    // the root cgroup lists every cpu, it isn't a limit
+    let cgroups = if dirs.len() > 1 { &dirs[..dirs.len() - 1] } else { dirs };
+    let cpuset = cgroups
+        .iter()
+        .find_map(|dir| read_value(dir, "cpuset.cpus.effective"))
+        .and_then(|x| parse_cpu_list(&x).ok())
+        .filter(|x| !x.is_empty());
+    CgroupLimits {
+        version: 2,
+        memory_max,
+        memory_used: memory_max.and(memory_used),
+        cpu_quota,
+        cpuset,
+    }
+}
+
+fn read_v1(proc_cgroup: &str, root: &Path) -> CgroupLimits {
+    // lines look like "4:memory:/docker/<id>" or "2:cpu,cpuacct:/user.slice"
+    let dirs = |controller: &str| -> Option<Vec<PathBuf>> {
+        proc_cgroup.lines().find_map(|line| {
+            let mut parts = line.splitn(3, ':');
+            let (_, controllers, path) = (parts.next()?, parts.next()?, parts.next()?);
+            if !controllers.split(',').any(|x| x == controller) {
+                return None;
+            }
+            [root.join(controllers), root.join(controller)]
+                .iter()
+                .find(|x| x.exists())
+                .map(|mount| cgroup_dirs(mount, path))
+        })
+    };
+
+    let mut limits = CgroupLimits::default();
+    if let Some(dirs) = dirs("memory") {
+        limits.version = 1;
+        limits.memory_max = min_limit(
+            dirs.iter()
+                .filter_map(|dir| read_number(dir, "memory.limit_in_bytes")),
+        );
+        if limits.memory_max.is_some() {
+            limits.memory_used = read_number(&dirs[0], "memory.usage_in_bytes").map(|usage| {
+                usage.saturating_sub(
+                    stat_value(&dirs[0], "memory.stat", "total_inactive_file").unwrap_or(0),
+                )
+            });
+        }
+    }
+    if let Some(dirs) = dirs("cpu") {
+        limits.version = 1;
+        limits.cpu_quota = min_quota(dirs.iter().filter_map(|dir| {
+            let quota = read_value(dir, "cpu.cfs_quota_us")?.parse::<i64>().ok()?;
+            let period = read_number(dir, "cpu.cfs_period_us")?;
+            if quota > 0 && period > 0 {
+                Some(quota as f64 / period as f64)
+            } else {
+                None
+            }
+        }));
+    }
+    if let Some(dirs) = dirs("cpuset") {
+        limits.version = 1;
+        limits.cpuset = read_value(&dirs[0], "cpuset.effective_cpus")
+            .or_else(|| read_value(&dirs[0], "cpuset.cpus"))
+            .and_then(|x| parse_cpu_list(&x).ok())
+            .filter(|x| !x.is_empty());
+    }
+    limits
+}
+
+#[cfg(test)]
+mod cgroup_tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    fn fixture(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = temp_dir(&format!("cgroup-{}", name));
+        for (file, content) in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn reads_v2_limits_along_the_hierarchy() {
+        let root = fixture(
+            "v2",
+            &[
+                ("cgroup.controllers", "cpu memory cpuset"),
+                ("system.slice/memory.max", "4294967296\n"),
+                ("system.slice/cpu.max", "max 100000\n"),
+                ("system.slice/plot.service/memory.max", "max\n"),
+                ("system.slice/plot.service/memory.current", "1073741824\n"),
+                ("system.slice/plot.service/memory.stat", "anon 1\ninactive_file 536870912\n"),
+                ("system.slice/plot.service/cpu.max", "250000 100000\n"),
+                ("system.slice/plot.service/cpuset.cpus.effective", "0-3\n"),
+            ],
+        );
+        let limits = CgroupLimits::read("0::/system.slice/plot.service\n", &root, 8);
+        assert_eq!(limits.version, 2);
+        assert_eq!(limits.memory_max, Some(4 << 30));
+        assert_eq!(limits.memory_available(), Some((4 << 30) - (512 << 20)));
+        assert_eq!(limits.cpu_quota, Some(2.5));
+        assert_eq!(limits.cpus(), Some(3));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn v2_cpusets_of_every_cpu_are_no_limit() {
+        // only the root cgroup has a cpuset
+        let root = fixture(
+            "v2-root-cpuset",
+            &[
+                ("cgroup.controllers", "cpu memory cpuset"),
+                ("cpuset.cpus.effective", "0-7\n"),
+                ("user.slice/memory.max", "max\n"),
+                ("user.slice/cpu.max", "max 100000\n"),
+            ],
+        );
+        let limits = CgroupLimits::read("0::/user.slice\n", &root, 8);
+        assert_eq!(limits.cpuset, None);
+        assert!(!limits.is_limited());
+        fs::remove_dir_all(&root).unwrap();
+
+        // a cgroup namespace mounts the container's cgroup, narrower sets limit it
+        let root = fixture(
+            "v2-ns-cpuset",
+            &[("cgroup.controllers", "cpu memory cpuset"), ("cpuset.cpus.effective", "0-7\n")],
+        );
+        assert_eq!(CgroupLimits::read("0::/\n", &root, 8).cpuset, None);
+        assert_eq!(CgroupLimits::read("0::/\n", &root, 16).cpuset, Some((0..8).collect()));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn reads_v1_limits_inside_a_namespace() {
+        let root = fixture(
+            "v1",
+            &[
+                ("memory/memory.limit_in_bytes", "2147483648\n"),
+                ("memory/memory.usage_in_bytes", "0\n"),
+                ("cpu,cpuacct/cpu.cfs_quota_us", "-1\n"),
+                ("cpu,cpuacct/cpu.cfs_period_us", "100000\n"),
+            ],
+        );
+        let limits = CgroupLimits::read("4:memory:/docker/abc\n2:cpu,cpuacct:/docker/abc\n", &root, 8);
+        assert_eq!(limits.version, 1);
+        assert_eq!(limits.memory_max, Some(2 << 30));
+        assert_eq!(limits.cpu_quota, None);
+        assert_eq!(limits.cpus(), None);
+        fs::remove_dir_all(&root).unwrap();
+
+        // unlimited v1 memory
+        let root = fixture("v1-unlimited", &[("memory/memory.limit_in_bytes", "9223372036854771712\n")]);
+        let limits = CgroupLimits::read("4:memory:/\n", &root, 8);
+        assert!(!limits.is_limited());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,250 +1,483 @@
-use crate::poc_hashing::noncegen_rust;
-use libc::{c_void, size_t};
-use std::slice::from_raw_parts_mut;
-use std::sync::mpsc::Sender;
-
-const NUM_SCOOPS: usize = 4096;
-const SCOOP_SIZE: usize = 64;
-const NONCE_SIZE: usize = NUM_SCOOPS * SCOOP_SIZE;
-
-extern "C" {
-    pub fn init_shabal_sse2() -> ();
-    pub fn init_shabal_avx() -> ();
-    pub fn init_shabal_avx2() -> ();
-    pub fn init_shabal_avx512f() -> ();
-    pub fn noncegen_sse2(
-        cache: *mut c_void,
-        cache_size: size_t,
-        chunk_offset: size_t,
-        numeric_ID: u64,
-        local_startnonce: u64,
-        local_nonces: u64,
-    );
-    pub fn noncegen_avx(
-        cache: *mut c_void,
-        cache_size: size_t,
-        chunk_offset: size_t,
-        numeric_ID: u64,
-        local_startnonce: u64,
-        local_nonces: u64,
-    );
-    pub fn noncegen_avx2(
-        cache: *mut c_void,
-        cache_size: size_t,
-        chunk_offset: size_t,
-        numeric_ID: u64,
-        local_startnonce: u64,
-        local_nonces: u64,
-    );
-    pub fn noncegen_avx512(
-        cache: *mut c_void,
-        cache_size: size_t,
-        chunk_offset: size_t,
-        numeric_ID: u64,
-        local_startnonce: u64,
-        local_nonces: u64,
-    );
-}
-pub struct SafePointer {
-    pub ptr: *mut u8,
-}
-unsafe impl Send for SafePointer {}
-unsafe impl Sync for SafePointer {}
-
-pub struct CpuTask {
-    pub cache: SafePointer,
-    pub cache_size: usize,
-    pub chunk_offset: usize,
-    pub numeric_id: u64,
-    pub local_startnonce: u64,
-    pub local_nonces: u64,
-}
-
-#[derive(Debug, Clone)]
-pub enum SimdExtension {
-    AVX512f,
-    AVX2,
-    AVX,
-    SSE2,
-    None,
-}
-
-pub fn init_simd() -> SimdExtension {
-    if is_x86_feature_detected!("avx512f") {
-        unsafe {
-            init_shabal_avx512f();
-        }
-        SimdExtension::AVX512f
-    } else if is_x86_feature_detected!("avx2") {
-        unsafe {
-            init_shabal_avx2();
-        }
-        SimdExtension::AVX2
-    } else if is_x86_feature_detected!("avx") {
-        unsafe {
-            init_shabal_avx();
-        }
-        SimdExtension::AVX
-    } else if is_x86_feature_detected!("sse2") {
-        unsafe {
-            init_shabal_sse2();
-        }
-        SimdExtension::SSE2
-    } else {
-        SimdExtension::None
-    }
-}
-
-pub fn hash_cpu(
-    tx: Sender<(u8, u8, u64)>,
-    hasher_task: CpuTask,
-    simd_ext: SimdExtension,
-) -> impl FnOnce() {
-    move || {
-        unsafe {
-            match simd_ext {
-                SimdExtension::AVX512f => noncegen_avx512(
-                    hasher_task.cache.ptr as *mut c_void,
-                    hasher_task.cache_size,
-                    hasher_task.chunk_offset,
-                    hasher_task.numeric_id,
-                    hasher_task.local_startnonce,
-                    hasher_task.local_nonces,
-                ),
-                SimdExtension::AVX2 => noncegen_avx2(
-                    hasher_task.cache.ptr as *mut c_void,
-                    hasher_task.cache_size,
-                    hasher_task.chunk_offset,
-                    hasher_task.numeric_id,
-                    hasher_task.local_startnonce,
-                    hasher_task.local_nonces,
-                ),
-                SimdExtension::AVX => noncegen_avx(
-                    hasher_task.cache.ptr as *mut c_void,
-                    hasher_task.cache_size,
-                    hasher_task.chunk_offset,
-                    hasher_task.numeric_id,
-                    hasher_task.local_startnonce,
-                    hasher_task.local_nonces,
-                ),
-                SimdExtension::SSE2 => noncegen_sse2(
-                    hasher_task.cache.ptr as *mut c_void,
-                    hasher_task.cache_size,
-                    hasher_task.chunk_offset,
-                    hasher_task.numeric_id,
-                    hasher_task.local_startnonce,
-                    hasher_task.local_nonces,
-                ),
-                _ => {
-                    let data = from_raw_parts_mut(
-                        hasher_task.cache.ptr,
-                        hasher_task.cache_size * NONCE_SIZE,
-                    );
-                    noncegen_rust(
-                        data,
-                        hasher_task.chunk_offset,
-                        hasher_task.numeric_id,
-                        hasher_task.local_startnonce,
-                        hasher_task.local_nonces,
-                    )
-                }
-            }
-        }
-        // report hashing done
-        tx.send((0u8, 1u8, 0))
-            .expect("CPU task can't communicate with scheduler thread.");
-        // report data in hostmem
-        tx.send((0u8, 0u8, hasher_task.local_nonces))
-            .expect("CPU task can't communicate with scheduler thread.");
-    }
-}
-
-#[cfg(test)]
-mod test {
-    extern crate crypto;
-    use self::crypto::digest::Digest;
-    use self::crypto::sha2::Sha256;
-    use super::*;
-    use crate::plotter;
-
-    #[test]
-    fn test_noncegen() {
-        let numeric_id = 7900104405094198526;
-        let start_nonce = 1337;
-        let exp_result_hash = "eebdf7dce694cbea9539f71efc362d4b72f8792def335d7157dadb09bb6d9e5f";
-
-        let check_result = |buf: &Vec<u8>| {
-            let mut hasher = Sha256::new();
-            hasher.input(buf);
-            assert_eq!(hasher.result_str(), exp_result_hash);
-        };
-
-        if is_x86_feature_detected!("avx512f") {
-            let mut buf = vec![0; 32 * plotter::NONCE_SIZE as usize];
-            unsafe {
-                init_shabal_avx512f();
-                noncegen_avx512(
-                    buf.as_mut_ptr() as *mut c_void,
-                    32,
-                    0,
-                    numeric_id,
-                    start_nonce,
-                    32,
-                );
-            }
-            check_result(&buf);
-        }
-
-        if is_x86_feature_detected!("avx2") {
-            let mut buf = vec![0; 32 * plotter::NONCE_SIZE as usize];
-            unsafe {
-                init_shabal_avx2();
-                noncegen_avx2(
-                    buf.as_mut_ptr() as *mut c_void,
-                    32,
-                    0,
-                    numeric_id,
-                    start_nonce,
-                    32,
-                );
-            }
-            check_result(&buf);
-        }
-
-        if is_x86_feature_detected!("avx") {
-            let mut buf = vec![0; 32 * plotter::NONCE_SIZE as usize];
-            unsafe {
-                init_shabal_avx();
-                noncegen_avx(
-                    buf.as_mut_ptr() as *mut c_void,
-                    32,
-                    0,
-                    numeric_id,
-                    start_nonce,
-                    32,
-                );
-            }
-            check_result(&buf);
-        }
-
-        if is_x86_feature_detected!("sse2") {
-            let mut buf = vec![0; 32 * plotter::NONCE_SIZE as usize];
-            unsafe {
-                init_shabal_sse2();
-                noncegen_sse2(
-                    buf.as_mut_ptr() as *mut c_void,
-                    32,
-                    0,
-                    numeric_id,
-                    start_nonce,
-                    32,
-                );
-            }
-            check_result(&buf);
-        }
-
-        let mut buf = vec![0; 32 * plotter::NONCE_SIZE as usize];
-        noncegen_rust(&mut buf, 0, numeric_id, start_nonce, 32);
-        check_result(&buf);
-    }
-}
+use crate::buffer::{NonceView, PageAlignedByteBuffer};
+use crate::lanes;
+use crate::scheduler::{DeviceMessage, DeviceStats, HashDevice, HashTask};
+#[cfg(c_simd)]
+use libc::{c_void, size_t};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::mpsc::Sender;
+#[cfg(feature = "opencl")]
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::time::Duration;
+
+const NUM_SCOOPS: usize = 4096;
+const SCOOP_SIZE: usize = 64;
+const NONCE_SIZE: usize = NUM_SCOOPS * SCOOP_SIZE;
+// sha256 of 32 nonces of account 7900104405094198526 starting at nonce 1337
+const SELF_TEST_NUMERIC_ID: u64 = 7900104405094198526;
+const SELF_TEST_START_NONCE: u64 = 1337;
+const SELF_TEST_NONCES: usize = 32;
+const SELF_TEST_DIGEST: &str = "eebdf7dce694cbea9539f71efc362d4b72f8792def335d7157dadb09bb6d9e5f";
+
+// built for x86 targets unless the pure-rust feature is set, see build.rs
+#[cfg(c_simd)]
+extern "C" {
+    pub fn init_shabal_sse2() -> ();
+    pub fn init_shabal_avx() -> ();
+    pub fn init_shabal_avx2() -> ();
+    pub fn init_shabal_avx512f() -> ();
+    pub fn noncegen_sse2(
+        cache: *mut c_void,
+        cache_size: size_t,
+        chunk_offset: size_t,
+        numeric_ID: u64,
+        local_startnonce: u64,
+        local_nonces: u64,
+    );
+    pub fn noncegen_avx(
+        cache: *mut c_void,
+        cache_size: size_t,
+        chunk_offset: size_t,
+        numeric_ID: u64,
+        local_startnonce: u64,
+        local_nonces: u64,
+    );
+    pub fn noncegen_avx2(
+        cache: *mut c_void,
+        cache_size: size_t,
+        chunk_offset: size_t,
+        numeric_ID: u64,
+        local_startnonce: u64,
+        local_nonces: u64,
+    );
+    pub fn noncegen_avx512(
+        cache: *mut c_void,
+        cache_size: size_t,
+        chunk_offset: size_t,
+        numeric_ID: u64,
+        local_startnonce: u64,
+        local_nonces: u64,
+    );
+}
+// nonces per cpu task
+pub const CPU_TASK_SIZE: u64 = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimdExtension {
+    AVX512f,
+    AVX2,
+    AVX,
+    SSE2,
+    // rust intrinsics only, see lanes::noncegen
+    NEON,
+    // portable multi-lane rust without intrinsics
+    None,
+}
+
+impl SimdExtension {
+    // fastest first
+    const ALL: [SimdExtension; 6] = [
+        SimdExtension::AVX512f,
+        SimdExtension::AVX2,
+        SimdExtension::AVX,
+        SimdExtension::SSE2,
+        SimdExtension::NEON,
+        SimdExtension::None,
+    ];
+
+    // hashed by the c backends where they are built, by rust intrinsics elsewhere
+    pub fn is_supported(&self) -> bool {
+        match self {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdExtension::AVX512f => is_x86_feature_detected!("avx512f"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdExtension::AVX2 => is_x86_feature_detected!("avx2"),
+            // only c has avx without avx2
+            #[cfg(c_simd)]
+            SimdExtension::AVX => is_x86_feature_detected!("avx"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdExtension::SSE2 => is_x86_feature_detected!("sse2"),
+            #[cfg(target_arch = "aarch64")]
+            SimdExtension::NEON => true,
+            SimdExtension::None => true,
+            _ => false,
+        }
+    }
+}
+
+// "avx512f", "avx2", "avx", "sse2", "neon" or "rust"
+impl FromStr for SimdExtension {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SimdExtension, String> {
+        match s.trim().to_lowercase().as_str() {
+            "avx512f" | "avx512" => Ok(SimdExtension::AVX512f),
+            "avx2" => Ok(SimdExtension::AVX2),
+            "avx" => Ok(SimdExtension::AVX),
+            "sse2" => Ok(SimdExtension::SSE2),
+            "neon" => Ok(SimdExtension::NEON),
+            "rust" | "none" => Ok(SimdExtension::None),
+            _ => Err(format!("unknown simd extension: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for SimdExtension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimdExtension::None => write!(f, "rust"),
+            x => write!(f, "{:?}", x),
+        }
+    }
+}
+
+// the best extension the cpu has
+pub fn init_simd() -> SimdExtension {
+    let simd_ext = SimdExtension::ALL
+        .iter()
+        .find(|x| x.is_supported())
+        .cloned()
+        .unwrap_or(SimdExtension::None);
+    init_shabal(&simd_ext);
+    simd_ext
+}
+
+// the requested extension, None picks the best one
+pub fn init_simd_with(requested: Option<SimdExtension>) -> Result<SimdExtension, String> {
+    match requested {
+        None => Ok(init_simd()),
+        Some(simd_ext) if simd_ext.is_supported() => {
+            init_shabal(&simd_ext);
+            Ok(simd_ext)
+        }
+        Some(simd_ext) => Err(format!("this cpu doesn't support {}", simd_ext)),
+    }
+}
+
+fn init_shabal(simd_ext: &SimdExtension) {
+    #[cfg(c_simd)]
+    unsafe {
+        match simd_ext {
+            SimdExtension::AVX512f => init_shabal_avx512f(),
+            SimdExtension::AVX2 => init_shabal_avx2(),
+            SimdExtension::AVX => init_shabal_avx(),
+            SimdExtension::SSE2 => init_shabal_sse2(),
+            SimdExtension::NEON | SimdExtension::None => (),
+        }
+    }
+    #[cfg(not(c_simd))]
+    let _ = simd_ext;
+}
+
+// hashes the view's nonces into their place in its buffer
+fn noncegen(simd_ext: &SimdExtension, view: &mut NonceView, numeric_id: u64, local_startnonce: u64) {
+    #[cfg(c_simd)]
+    let generate: Option<unsafe extern "C" fn(*mut c_void, size_t, size_t, u64, u64, u64)> =
+        match simd_ext {
+            SimdExtension::AVX512f => Some(noncegen_avx512),
+            SimdExtension::AVX2 => Some(noncegen_avx2),
+            SimdExtension::AVX => Some(noncegen_avx),
+            SimdExtension::SSE2 => Some(noncegen_sse2),
+            SimdExtension::NEON | SimdExtension::None => None,
+        };
+    #[cfg(c_simd)]
+    if let Some(generate) = generate {
+        // the c noncegen writes exactly the nonces it is given
+        unsafe {
+            generate(
+                view.as_mut_ptr() as *mut c_void,
+                view.buffer_nonces() as size_t,
+                view.range().start as size_t,
+                numeric_id,
+                local_startnonce,
+                view.nonces(),
+            );
+        }
+        return;
+    }
+
+    lanes::noncegen(simd_ext, view, numeric_id, local_startnonce);
+}
+
+// Hashes known nonces with an initialised extension and checks them against the
+// reference digest, a broken backend (or cpu) would silently write useless plots
+pub fn self_test(simd_ext: &SimdExtension) -> Result<(), String> {
+    let mut buffer = PageAlignedByteBuffer::new(SELF_TEST_NONCES * NONCE_SIZE);
+    let mut view = buffer.lend(0..SELF_TEST_NONCES as u64);
+    noncegen(simd_ext, &mut view, SELF_TEST_NUMERIC_ID, SELF_TEST_START_NONCE);
+    drop(view);
+    let digest: String = Sha256::digest(buffer.as_slice())
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect();
+    if digest == SELF_TEST_DIGEST {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} hashes don't match the reference, got digest {}",
+            simd_ext, digest
+        ))
+    }
+}
+
+// A numa node's thread pool, hashing its own stripe of every buffer with one
+// task per thread in flight.
+pub struct CpuPool {
+    pool: Arc<rayon::ThreadPool>,
+    name: String,
+    stripe: usize,
+    simd_ext: SimdExtension,
+    sender: Option<(usize, Sender<DeviceMessage>)>,
+}
+
+impl CpuPool {
+    pub fn new(pool: Arc<rayon::ThreadPool>, name: String, stripe: usize, simd_ext: SimdExtension) -> CpuPool {
+        CpuPool {
+            pool,
+            name,
+            stripe,
+            simd_ext,
+            sender: None,
+        }
+    }
+}
+
+impl HashDevice for CpuPool {
+    fn stats(&self) -> DeviceStats {
+        DeviceStats {
+            name: self.name.clone(),
+            cores: self.pool.current_num_threads(),
+            worksize: CPU_TASK_SIZE as usize,
+            nonces: 0,
+            busy: Duration::ZERO,
+        }
+    }
+
+    fn worksize(&self) -> u64 {
+        CPU_TASK_SIZE
+    }
+
+    fn slots(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    fn stripe(&self) -> Option<usize> {
+        Some(self.stripe)
+    }
+
+    fn start(&mut self, id: usize, tx: Sender<DeviceMessage>) {
+        self.sender = Some((id, tx));
+    }
+
+    fn hash(&mut self, task: HashTask) {
+        let (id, tx) = self.sender.clone().expect("CPU pool not started");
+        self.pool.spawn(hash_cpu(id, tx, task, self.simd_ext.clone()));
+    }
+}
+
+fn hash_cpu(
+    id: usize,
+    tx: Sender<DeviceMessage>,
+    hasher_task: HashTask,
+    simd_ext: SimdExtension,
+) -> impl FnOnce() {
+    move || {
+        let HashTask {
+            mut view,
+            numeric_id,
+            local_startnonce,
+        } = hasher_task;
+        noncegen(&simd_ext, &mut view, numeric_id, local_startnonce);
+        let nonces = view.nonces();
+        drop(view);
+        // report hashing done
+        tx.send(DeviceMessage::Ready(id))
+            .expect("CPU task can't communicate with scheduler thread.");
+        // report data in hostmem
+        tx.send(DeviceMessage::Hashed(id, nonces))
+            .expect("CPU task can't communicate with scheduler thread.");
+    }
+}
+
+// Hashes the work of a quarantined gpu with the plotter's backend on its numa
+// pools, or on rayon's global pool when there are none (gpus only).
+#[cfg(feature = "opencl")]
+#[derive(Clone)]
+pub struct Rehasher {
+    pools: Vec<Arc<rayon::ThreadPool>>,
+    simd_ext: SimdExtension,
+}
+
+#[cfg(feature = "opencl")]
+impl Rehasher {
+    pub fn new(pools: Vec<Arc<rayon::ThreadPool>>, simd_ext: SimdExtension) -> Rehasher {
+        Rehasher { pools, simd_ext }
+    }
+
+    // hashes a task's views in cpu sized chunks, spread over the pools, and
+    // returns once all of them are dropped
+    pub fn hash(&self, views: Vec<NonceView>, numeric_id: u64, local_startnonce: u64) {
+        let first = match views.first() {
+            Some(x) => x.range().start,
+            None => return,
+        };
+        let (tx, rx) = channel();
+        let chunks = views.into_iter().flat_map(|x| x.chunks(CPU_TASK_SIZE));
+        for (i, mut view) in chunks.enumerate() {
+            let simd_ext = self.simd_ext.clone();
+            let tx = tx.clone();
+            let start = local_startnonce + view.range().start - first;
+            let job = move || {
+                noncegen(&simd_ext, &mut view, numeric_id, start);
+                drop(view);
+                tx.send(()).expect("rehash can't report back");
+            };
+            if self.pools.is_empty() {
+                rayon::spawn(job);
+            } else {
+                self.pools[i % self.pools.len()].spawn(job);
+            }
+        }
+        drop(tx);
+        for _ in rx {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(c_simd)]
+    #[test]
+    fn test_noncegen() {
+        use crypto::digest::Digest;
+        use crypto::sha2::Sha256;
+        use crate::plotter;
+        use crate::poc_hashing::noncegen_rust;
+
+        let numeric_id = 7900104405094198526;
+        let start_nonce = 1337;
+        let exp_result_hash = "eebdf7dce694cbea9539f71efc362d4b72f8792def335d7157dadb09bb6d9e5f";
+
+        let check_result = |buf: &Vec<u8>| {
+            let mut hasher = Sha256::new();
+            hasher.input(buf);
+            assert_eq!(hasher.result_str(), exp_result_hash);
+        };
+
+        if is_x86_feature_detected!("avx512f") {
+            let mut buf = vec![0; 32 * plotter::NONCE_SIZE as usize];
+            unsafe {
+                init_shabal_avx512f();
+                noncegen_avx512(
+                    buf.as_mut_ptr() as *mut c_void,
+                    32,
+                    0,
+                    numeric_id,
+                    start_nonce,
+                    32,
+                );
+            }
+            check_result(&buf);
+        }
+
+        if is_x86_feature_detected!("avx2") {
+            let mut buf = vec![0; 32 * plotter::NONCE_SIZE as usize];
+            unsafe {
+                init_shabal_avx2();
+                noncegen_avx2(
+                    buf.as_mut_ptr() as *mut c_void,
+                    32,
+                    0,
+                    numeric_id,
+                    start_nonce,
+                    32,
+                );
+            }
+            check_result(&buf);
+        }
+
+        if is_x86_feature_detected!("avx") {
+            let mut buf = vec![0; 32 * plotter::NONCE_SIZE as usize];
+            unsafe {
+                init_shabal_avx();
+                noncegen_avx(
+                    buf.as_mut_ptr() as *mut c_void,
+                    32,
+                    0,
+                    numeric_id,
+                    start_nonce,
+                    32,
+                );
+            }
+            check_result(&buf);
+        }
+
+        if is_x86_feature_detected!("sse2") {
+            let mut buf = vec![0; 32 * plotter::NONCE_SIZE as usize];
+            unsafe {
+                init_shabal_sse2();
+                noncegen_sse2(
+                    buf.as_mut_ptr() as *mut c_void,
+                    32,
+                    0,
+                    numeric_id,
+                    start_nonce,
+                    32,
+                );
+            }
+            check_result(&buf);
+        }
+
+        let mut buffer = PageAlignedByteBuffer::new(32 * plotter::NONCE_SIZE as usize);
+        noncegen_rust(&mut buffer.lend(0..32), numeric_id, start_nonce);
+        check_result(&buffer.as_slice().to_vec());
+    }
+
+    #[test]
+    fn forced_backends() {
+        assert_eq!("AVX2".parse(), Ok(SimdExtension::AVX2));
+        assert_eq!("rust".parse(), Ok(SimdExtension::None));
+        assert_eq!("neon".parse(), Ok(SimdExtension::NEON));
+        assert!("mmx".parse::<SimdExtension>().is_err());
+        assert_eq!(SimdExtension::None.to_string(), "rust");
+
+        // every x86_64 cpu has sse2, other targets hash with rust
+        let simd_ext = if SimdExtension::SSE2.is_supported() {
+            SimdExtension::SSE2
+        } else {
+            SimdExtension::None
+        };
+        let simd_ext = init_simd_with(Some(simd_ext)).unwrap();
+        assert_eq!(self_test(&simd_ext), Ok(()));
+    }
+
+    #[test]
+    #[cfg(feature = "opencl")]
+    fn rehash_on_pools_and_without() {
+        use crate::poc_hashing::noncegen_rust;
+
+        let nonces = 3 * CPU_TASK_SIZE as usize + 5;
+        let mut reference = PageAlignedByteBuffer::new((nonces + 7) * NONCE_SIZE);
+        reference.as_mut_slice().fill(0);
+        noncegen_rust(&mut reference.lend(7..nonces as u64 + 7), SELF_TEST_NUMERIC_ID, 1337);
+
+        let pools = vec![
+            Arc::new(rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap()),
+            Arc::new(rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap()),
+        ];
+        for rehasher in vec![
+            Rehasher::new(pools, SimdExtension::None),
+            Rehasher::new(Vec::new(), SimdExtension::None),
+        ] {
+            let mut buffer = PageAlignedByteBuffer::new((nonces + 7) * NONCE_SIZE);
+            buffer.as_mut_slice().fill(0);
+            let mut view = buffer.lend(7..nonces as u64 + 7);
+            let tail = view.split_off(CPU_TASK_SIZE + 3);
+            rehasher.hash(vec![view, tail], SELF_TEST_NUMERIC_ID, 1337);
+            assert!(buffer.as_slice() == reference.as_slice());
+        }
+    }
+}
@@ -0,0 +1,205 @@
+#[cfg(feature = "opencl")]
+use crate::buffer::{NonceView, PageAlignedByteBuffer};
+#[cfg(feature = "opencl")]
+use crate::plotter::NONCE_SIZE;
+#[cfg(feature = "opencl")]
+use crate::poc_hashing::noncegen_rust;
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(feature = "opencl")]
+const SCOOP_SIZE: usize = 64;
+#[cfg(feature = "opencl")]
+const NUM_SCOOPS: usize = 4096;
+
+// what a gpu that computed a wrong nonce gets
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OnMismatch {
+    // stop plotting before anything of it is written
+    Abort,
+    // re-hash its work on the cpu and give it no more
+    Quarantine,
+}
+
+// Re-hashes a share of every gpu task's nonces on the cpu and compares them
+// with what the gpu wrote to the buffer. Overclocked cards compute wrong
+// nonces without any error, which otherwise only shows as missed deadlines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GpuCheck {
+    // share of each task's nonces, at least one per task
+    pub fraction: f64,
+    pub on_mismatch: OnMismatch,
+}
+
+impl FromStr for GpuCheck {
+    type Err = String;
+
+    // fraction[:abort|quarantine], aborting by default
+    fn from_str(s: &str) -> Result<GpuCheck, String> {
+        let mut parts = s.trim().splitn(2, ':');
+        let fraction: f64 = parts
+            .next()
+            .and_then(|x| x.trim().parse().ok())
+            .filter(|x| *x > 0.0 && *x <= 1.0)
+            .ok_or_else(|| format!("invalid GPU check {}, expected a fraction in (0, 1]", s))?;
+        let on_mismatch = match parts.next().map(|x| x.trim().to_lowercase()).as_deref() {
+            None | Some("abort") => OnMismatch::Abort,
+            Some("quarantine") => OnMismatch::Quarantine,
+            Some(x) => return Err(format!("invalid GPU check action {}, expected abort or quarantine", x)),
+        };
+        Ok(GpuCheck {
+            fraction,
+            on_mismatch,
+        })
+    }
+}
+
+impl fmt::Display for GpuCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.on_mismatch {
+            OnMismatch::Abort => write!(f, "{}:abort", self.fraction),
+            OnMismatch::Quarantine => write!(f, "{}:quarantine", self.fraction),
+        }
+    }
+}
+
+// picks the nonces to re-hash, xorshift so every device gets its own sequence
+#[cfg(feature = "opencl")]
+pub struct Sampler {
+    fraction: f64,
+    state: u64,
+}
+
+#[cfg(feature = "opencl")]
+impl Sampler {
+    pub fn new(fraction: f64, seed: u64) -> Sampler {
+        Sampler {
+            fraction,
+            // xorshift never leaves 0
+            state: seed | 1,
+        }
+    }
+
+    // distinct indices of a task's nonces, ascending
+    pub fn sample(&mut self, nonces: u64) -> Vec<u64> {
+        if nonces == 0 {
+            return Vec::new();
+        }
+        let count = ((nonces as f64 * self.fraction).ceil() as u64).clamp(1, nonces);
+        let mut samples = Vec::with_capacity(count as usize);
+        while (samples.len() as u64) < count {
+            let x = self.next() % nonces;
+            if !samples.contains(&x) {
+                samples.push(x);
+            }
+        }
+        samples.sort_unstable();
+        samples
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
+
+// Compares the sampled nonces of a task, its views in order as the gpu
+// transfer left them, with the cpu's. Err is the index of the first wrong one.
+#[cfg(feature = "opencl")]
+pub fn verify(
+    views: &mut [NonceView],
+    numeric_id: u64,
+    local_startnonce: u64,
+    samples: &[u64],
+) -> Result<(), u64> {
+    let first = match views.first() {
+        Some(x) => x.range().start,
+        None => return Ok(()),
+    };
+    let mut reference = PageAlignedByteBuffer::new(NONCE_SIZE as usize);
+    for &sample in samples {
+        noncegen_rust(&mut reference.lend(0..1), numeric_id, local_startnonce + sample);
+        let nonce = first + sample;
+        let view = views
+            .iter_mut()
+            .find(|x| x.range().contains(&nonce))
+            .expect("sample outside of the task");
+        let offset = (nonce - view.range().start) as usize * SCOOP_SIZE;
+        let expected = reference.as_slice();
+        for scoop in 0..NUM_SCOOPS {
+            if view.scoop_mut(scoop)[offset..offset + SCOOP_SIZE]
+                != expected[scoop * SCOOP_SIZE..(scoop + 1) * SCOOP_SIZE]
+            {
+                return Err(sample);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod gpu_check_tests {
+    use super::*;
+
+    #[cfg(feature = "opencl")]
+    const NUMERIC_ID: u64 = 7900104405094198526;
+
+    #[test]
+    fn parse_checks() {
+        assert_eq!(
+            "0.01".parse(),
+            Ok(GpuCheck {
+                fraction: 0.01,
+                on_mismatch: OnMismatch::Abort
+            })
+        );
+        assert_eq!(
+            "0.5:Quarantine".parse(),
+            Ok(GpuCheck {
+                fraction: 0.5,
+                on_mismatch: OnMismatch::Quarantine
+            })
+        );
+        assert!("0".parse::<GpuCheck>().is_err());
+        assert!("2".parse::<GpuCheck>().is_err());
+        assert!("0.1:retry".parse::<GpuCheck>().is_err());
+        let check: GpuCheck = "0.25:quarantine".parse().unwrap();
+        assert_eq!(check.to_string().parse(), Ok(check));
+    }
+
+    #[test]
+    #[cfg(feature = "opencl")]
+    fn samples_are_distinct_and_in_range() {
+        let mut sampler = Sampler::new(0.25, 42);
+        assert_eq!(sampler.sample(2).len(), 1);
+        assert_eq!(sampler.sample(10).len(), 3);
+        let samples = sampler.sample(1000);
+        assert_eq!(samples.len(), 250);
+        assert!(samples.windows(2).all(|x| x[0] < x[1]));
+        assert!(samples.iter().all(|x| *x < 1000));
+        assert_eq!(Sampler::new(1.0, 7).sample(5), vec![0, 1, 2, 3, 4]);
+        assert!(sampler.sample(0).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "opencl")]
+    fn wrong_nonces_are_found() {
+        let mut plotted = PageAlignedByteBuffer::new(40 * NONCE_SIZE as usize);
+        plotted.as_mut_slice().fill(0);
+        noncegen_rust(&mut plotted.lend(3..37), NUMERIC_ID, 1337);
+
+        let all: Vec<u64> = (0..34).collect();
+        let mut views = plotted.lend(3..37).chunks(16);
+        assert_eq!(verify(&mut views, NUMERIC_ID, 1337, &all), Ok(()));
+        drop(views);
+
+        // one flipped bit in the last scoop of nonce 20, the 17th of the task
+        let offset = (4095 * 40 + 20) * SCOOP_SIZE + 63;
+        plotted.as_mut_slice()[offset] ^= 1;
+        let mut views = plotted.lend(3..37).chunks(16);
+        assert_eq!(verify(&mut views, NUMERIC_ID, 1337, &[0, 16, 17, 30]), Err(17));
+        assert_eq!(verify(&mut views, NUMERIC_ID, 1337, &[0, 30]), Ok(()));
+    }
+}
@@ -1,73 +1,260 @@
-use crate::cpu_hasher::SafePointer;
-use crate::ocl::{gpu_hash, gpu_hash_and_transfer_to_host, gpu_transfer_to_host, GpuContext};
-use crossbeam_channel::Receiver;
-use std::sync::mpsc::Sender;
-use std::sync::{Arc, Mutex};
-
-pub struct GpuTask {
-    pub cache: SafePointer,
-    pub cache_size: u64,
-    pub chunk_offset: u64,
-    pub numeric_id: u64,
-    pub local_startnonce: u64,
-    pub local_nonces: u64,
-}
-
-pub fn create_gpu_hasher_thread(
-    gpu_id: u8,
-    gpu_context: Arc<Mutex<GpuContext>>,
-    tx: Sender<(u8, u8, u64)>,
-    rx_hasher_task: Receiver<Option<GpuTask>>,
-) -> impl FnOnce() {
-    move || {
-        let mut first_run = true;
-        let mut buffer_id = 0u8;
-        let mut last_task = GpuTask {
-            cache: SafePointer { ptr: &mut 0u8 },
-            cache_size: 0,
-            chunk_offset: 0,
-            numeric_id: 0,
-            local_startnonce: 0,
-            local_nonces: 0,
-        };
-        for task in rx_hasher_task {
-            // check if new task or termination
-            match task {
-                // new task
-                Some(task) => {
-                    // first run - just hash
-                    if first_run {
-                        if task.local_nonces != 0 {
-                            first_run = false;
-                            gpu_hash(&gpu_context, &task);
-                            buffer_id = 1 - buffer_id;
-                            last_task = task;
-                            tx.send((gpu_id, 1u8, 0))
-                                .expect("GPU task can't communicate with scheduler thread.");
-                        }
-                    // last run - just transfer
-                    } else if task.local_nonces == 0 {
-                        gpu_transfer_to_host(&gpu_context, buffer_id, &last_task);
-                        first_run = true;
-                        buffer_id = 0;
-                        tx.send((gpu_id, 0u8, last_task.local_nonces))
-                            .expect("GPU task can't communicate with scheduler thread.");
-                    // normal run - hash and transfer async
-                    } else {
-                        gpu_hash_and_transfer_to_host(&gpu_context, buffer_id, &task, &last_task);
-                        buffer_id = 1 - buffer_id;
-                        tx.send((gpu_id, 0u8, last_task.local_nonces))
-                            .expect("GPU task can't communicate with scheduler thread.");
-                        last_task = task;
-                        tx.send((gpu_id, 1u8, 0))
-                            .expect("GPU task can't communicate with scheduler thread.");
-                    }
-                }
-                // termination
-                None => {
-                    break;
-                }
-            }
-        }
-    }
-}
+use crate::buffer::NonceView;
+use crate::cpu_hasher::Rehasher;
+use crate::gpu_check::{verify, GpuCheck, OnMismatch, Sampler};
+use crate::ocl::{gpu_hash, gpu_hash_and_transfer_to_host, gpu_transfer_to_host, GpuContext};
+use crate::scheduler::{DeviceMessage, DeviceStats, HashDevice, HashTask};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+enum GpuCommand {
+    Hash(HashTask),
+    // transfer the last task, the buffer has no more work
+    Flush,
+    Shutdown,
+}
+
+// An opencl context with its own hasher thread. It hashes one task while the
+// previous one is transferred to the host.
+pub struct GpuDevice {
+    context: Arc<Mutex<GpuContext>>,
+    check: Option<GpuCheck>,
+    rehasher: Rehasher,
+    channel: Option<Sender<GpuCommand>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl GpuDevice {
+    pub fn new(context: Arc<Mutex<GpuContext>>, check: Option<GpuCheck>, rehasher: Rehasher) -> GpuDevice {
+        GpuDevice {
+            context,
+            check,
+            rehasher,
+            channel: None,
+            thread: None,
+        }
+    }
+
+    fn send(&self, command: GpuCommand) {
+        self.channel
+            .as_ref()
+            .expect("GPU not started")
+            .send(command)
+            .unwrap();
+    }
+}
+
+impl HashDevice for GpuDevice {
+    fn stats(&self) -> DeviceStats {
+        let gpu = self.context.lock().unwrap();
+        DeviceStats {
+            name: gpu.name.clone(),
+            cores: gpu.cores,
+            worksize: gpu.worksize,
+            nonces: 0,
+            busy: Duration::ZERO,
+        }
+    }
+
+    fn worksize(&self) -> u64 {
+        self.context.lock().unwrap().worksize as u64
+    }
+
+    fn start(&mut self, id: usize, tx: mpsc::Sender<DeviceMessage>) {
+        let (tx_command, rx_command) = unbounded();
+        self.channel = Some(tx_command);
+        self.thread = Some(thread::spawn(create_gpu_hasher_thread(
+            id,
+            self.context.clone(),
+            self.check,
+            self.rehasher.clone(),
+            tx,
+            rx_command,
+        )));
+    }
+
+    fn hash(&mut self, task: HashTask) {
+        self.send(GpuCommand::Hash(task));
+    }
+
+    fn flush(&mut self) {
+        self.send(GpuCommand::Flush);
+    }
+
+    fn shutdown(&mut self) {
+        if self.channel.is_some() {
+            self.send(GpuCommand::Shutdown);
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap();
+        }
+    }
+}
+
+fn create_gpu_hasher_thread(
+    gpu_id: usize,
+    gpu_context: Arc<Mutex<GpuContext>>,
+    check: Option<GpuCheck>,
+    rehasher: Rehasher,
+    tx: mpsc::Sender<DeviceMessage>,
+    rx_command: Receiver<GpuCommand>,
+) -> impl FnOnce() {
+    move || {
+        let name = gpu_context.lock().unwrap().name.clone();
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |x| x.as_nanos() as u64)
+            ^ gpu_id as u64;
+        let mut checker = check.map(|x| Checker {
+            check: x,
+            sampler: Sampler::new(x.fraction, seed),
+        });
+        let mut buffer_id = 0u8;
+        // hashed on the gpu, not yet transferred
+        let mut last_task: Option<HashTask> = None;
+        // wrong results seen, the cpu hashes whatever the gpu still gets
+        let mut quarantined = false;
+        // wrong results seen, the buffer won't be written and nothing is hashed anymore
+        let mut failed = false;
+        for command in rx_command {
+            match command {
+                GpuCommand::Hash(task) if failed => {
+                    let nonces = task.view.nonces();
+                    drop(task);
+                    tx.send(DeviceMessage::Hashed(gpu_id, nonces))
+                        .expect("GPU task can't communicate with scheduler thread.");
+                }
+                GpuCommand::Hash(task) if quarantined => {
+                    let nonces = task.view.nonces();
+                    rehasher.hash(vec![task.view], task.numeric_id, task.local_startnonce);
+                    tx.send(DeviceMessage::Hashed(gpu_id, nonces))
+                        .expect("GPU task can't communicate with scheduler thread.");
+                    tx.send(DeviceMessage::Ready(gpu_id))
+                        .expect("GPU task can't communicate with scheduler thread.");
+                }
+                GpuCommand::Hash(task) => {
+                    match last_task.take() {
+                        // first run - just hash
+                        None => gpu_hash(&gpu_context, &task),
+                        // normal run - hash and transfer async
+                        Some(last) => {
+                            let nonces = last.view.nonces();
+                            let (numeric_id, start) = (last.numeric_id, last.local_startnonce);
+                            let views = gpu_hash_and_transfer_to_host(&gpu_context, buffer_id, &task, last);
+                            match checked(&mut checker, &name, views, numeric_id, start) {
+                                Ok(()) => (),
+                                // the task just hashed on the gpu is as suspect
+                                Err(Mismatch::Quarantine(views)) => {
+                                    tx.send(DeviceMessage::Retired(gpu_id))
+                                        .expect("GPU task can't communicate with scheduler thread.");
+                                    quarantined = true;
+                                    rehasher.hash(views, numeric_id, start);
+                                    tx.send(DeviceMessage::Hashed(gpu_id, nonces))
+                                        .expect("GPU task can't communicate with scheduler thread.");
+                                    let nonces = task.view.nonces();
+                                    rehasher.hash(vec![task.view], task.numeric_id, task.local_startnonce);
+                                    tx.send(DeviceMessage::Hashed(gpu_id, nonces))
+                                        .expect("GPU task can't communicate with scheduler thread.");
+                                    tx.send(DeviceMessage::Ready(gpu_id))
+                                        .expect("GPU task can't communicate with scheduler thread.");
+                                    continue;
+                                }
+                                Err(Mismatch::Abort(nonce)) => {
+                                    failed = true;
+                                    tx.send(DeviceMessage::Failed(gpu_id, nonce))
+                                        .expect("GPU task can't communicate with scheduler thread.");
+                                    tx.send(DeviceMessage::Hashed(gpu_id, nonces))
+                                        .expect("GPU task can't communicate with scheduler thread.");
+                                    let nonces = task.view.nonces();
+                                    drop(task);
+                                    tx.send(DeviceMessage::Hashed(gpu_id, nonces))
+                                        .expect("GPU task can't communicate with scheduler thread.");
+                                    continue;
+                                }
+                            }
+                            tx.send(DeviceMessage::Hashed(gpu_id, nonces))
+                                .expect("GPU task can't communicate with scheduler thread.");
+                        }
+                    }
+                    buffer_id = 1 - buffer_id;
+                    last_task = Some(task);
+                    tx.send(DeviceMessage::Ready(gpu_id))
+                        .expect("GPU task can't communicate with scheduler thread.");
+                }
+                // last run - just transfer
+                GpuCommand::Flush => {
+                    if let Some(last) = last_task.take() {
+                        let nonces = last.view.nonces();
+                        let (numeric_id, start) = (last.numeric_id, last.local_startnonce);
+                        let views = gpu_transfer_to_host(&gpu_context, buffer_id, last);
+                        buffer_id = 0;
+                        match checked(&mut checker, &name, views, numeric_id, start) {
+                            Ok(()) => (),
+                            Err(Mismatch::Quarantine(views)) => {
+                                tx.send(DeviceMessage::Retired(gpu_id))
+                                    .expect("GPU task can't communicate with scheduler thread.");
+                                quarantined = true;
+                                rehasher.hash(views, numeric_id, start);
+                            }
+                            Err(Mismatch::Abort(nonce)) => {
+                                failed = true;
+                                tx.send(DeviceMessage::Failed(gpu_id, nonce))
+                                    .expect("GPU task can't communicate with scheduler thread.");
+                            }
+                        }
+                        tx.send(DeviceMessage::Hashed(gpu_id, nonces))
+                            .expect("GPU task can't communicate with scheduler thread.");
+                    }
+                }
+                GpuCommand::Shutdown => break,
+            }
+        }
+    }
+}
+
+struct Checker {
+    check: GpuCheck,
+    sampler: Sampler,
+}
+
+// what checked found wrong with a task
+enum Mismatch {
+    // the wrong nonce, the views are dropped as the buffer won't be written
+    Abort(u64),
+    // the views to re-hash on the cpu
+    Quarantine(Vec<NonceView>),
+}
+
+// Samples a transferred task against the cpu. A mismatch stops the plotter
+// before the buffer reaches the writer, or hands the views back to be re-hashed
+// when the gpu is to be quarantined.
+fn checked(
+    checker: &mut Option<Checker>,
+    name: &str,
+    mut views: Vec<NonceView>,
+    numeric_id: u64,
+    local_startnonce: u64,
+) -> Result<(), Mismatch> {
+    let checker = match checker {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let nonces = views.iter().map(|x| x.nonces()).sum();
+    let samples = checker.sampler.sample(nonces);
+    let bad = match verify(&mut views, numeric_id, local_startnonce, &samples) {
+        Ok(()) => return Ok(()),
+        Err(x) => local_startnonce + x,
+    };
+    match checker.check.on_mismatch {
+        OnMismatch::Abort => Err(Mismatch::Abort(bad)),
+        OnMismatch::Quarantine => {
+            println!(
+                "Warning: {} computed a wrong nonce {} of account {}, the CPU takes over its work.",
+                name, bad, numeric_id
+            );
+            Err(Mismatch::Quarantine(views))
+        }
+    }
+}
@@ -0,0 +1,164 @@
+// share of global memory left to the driver, the kernel and other programs
+const RESERVED_SHARE: u64 = 16;
+
+// the memory limits of an OpenCL device that size its work
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeviceMemory {
+    pub global: u64,
+    // largest single buffer the device allocates
+    pub max_alloc: u64,
+    // integrated gpus and cpu runtimes share the host's ram
+    pub unified: bool,
+}
+
+// how the host gets at a gpu's output
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HostBuffer {
+    // the buffer the host reads is allocated in host memory and mapped, zero-copy
+    Mapped,
+    // the output is read into host memory, a pinned staging buffer for the host
+    // unpack, the plot buffers themselves for the device shuffle
+    Copied,
+    // the pinned staging buffer doubles as one of the two hash buffers, the
+    // device only holds the other one
+    Shared,
+}
+
+impl HostBuffer {
+    // buffers of cores * workgroup nonces in device memory
+    pub fn device_buffers(self, device_shuffle: bool) -> usize {
+        match self {
+            HostBuffer::Shared => 1,
+            _ if device_shuffle => 3,
+            _ => 2,
+        }
+    }
+
+    // buffers of cores * workgroup nonces in host memory
+    pub fn host_buffers(self, device_shuffle: bool) -> u64 {
+        match self {
+            // the shuffle's rows, or both hash buffers
+            HostBuffer::Mapped if device_shuffle => 1,
+            HostBuffer::Mapped => 2,
+            HostBuffer::Copied if device_shuffle => 0,
+            _ => 1,
+        }
+    }
+}
+
+impl DeviceMemory {
+    // Largest number of cores (work groups) up to requested whose buffers fit:
+    // each buffer holds cores * workgroup nonces and has to be a single
+    // allocation, all of them together fit in global memory minus a reserve.
+    // 0 if not even one core fits.
+    pub fn fit_cores(&self, requested: usize, workgroup: usize, buffers: usize, nonce_size: u64) -> usize {
+        let core_size = workgroup as u64 * nonce_size;
+        if core_size == 0 || buffers == 0 {
+            return requested;
+        }
+        let usable = self.global - self.global / RESERVED_SHARE;
+        let by_alloc = self.max_alloc / core_size;
+        let by_global = usable / (core_size * buffers as u64);
+        requested.min(by_alloc.min(by_global) as usize)
+    }
+
+    // The host buffer and the cores that fit with it. Unified memory, or
+    // zero_copy, maps. A discrete device copies its output, the host unpack
+    // hashes into the pinned staging buffer if that fits more cores.
+    pub fn host_buffer(
+        &self,
+        requested: usize,
+        workgroup: usize,
+        zero_copy: bool,
+        device_shuffle: bool,
+        nonce_size: u64,
+    ) -> (HostBuffer, usize) {
+        let fit = |host_buffer: HostBuffer| {
+            let buffers = host_buffer.device_buffers(device_shuffle);
+            (host_buffer, self.fit_cores(requested, workgroup, buffers, nonce_size))
+        };
+        if zero_copy || self.unified {
+            return fit(HostBuffer::Mapped);
+        }
+        let copied = fit(HostBuffer::Copied);
+        if device_shuffle || copied.1 == requested {
+            return copied;
+        }
+        let shared = fit(HostBuffer::Shared);
+        if shared.1 > copied.1 {
+            shared
+        } else {
+            copied
+        }
+    }
+}
+
+#[cfg(test)]
+mod gpu_memory_tests {
+    use super::*;
+
+    const NONCE: u64 = 256 * 1024;
+    const GIB: u64 = 1 << 30;
+
+    #[test]
+    fn cores_fit_the_device() {
+        // 8 GiB card, 2 GiB allocations: a core of 256 nonces is 64 MiB
+        let card = DeviceMemory {
+            global: 8 * GIB,
+            max_alloc: 2 * GIB,
+            unified: false,
+        };
+        assert_eq!(card.fit_cores(20, 256, 2, NONCE), 20);
+        // 32 cores would be exactly 2 GiB per buffer, 3 of them still fit
+        assert_eq!(card.fit_cores(40, 256, 3, NONCE), 32);
+        // 4 buffers of 30 cores take all of the 7.5 GiB not reserved
+        assert_eq!(card.fit_cores(30, 256, 4, NONCE), 30);
+        assert_eq!(card.fit_cores(40, 256, 4, NONCE), 30);
+    }
+
+    #[test]
+    fn too_small_devices_get_no_cores() {
+        let tiny = DeviceMemory {
+            global: 256 << 20,
+            max_alloc: 64 << 20,
+            unified: true,
+        };
+        assert_eq!(tiny.fit_cores(8, 256, 2, NONCE), 1);
+        assert_eq!(tiny.fit_cores(8, 512, 2, NONCE), 0);
+    }
+
+    #[test]
+    fn host_buffer_follows_memory() {
+        let card = DeviceMemory {
+            global: 8 * GIB,
+            max_alloc: 2 * GIB,
+            unified: false,
+        };
+        assert_eq!(card.host_buffer(20, 256, false, false, NONCE), (HostBuffer::Copied, 20));
+        assert_eq!(card.host_buffer(20, 256, false, true, NONCE), (HostBuffer::Copied, 20));
+        assert_eq!(card.host_buffer(20, 256, true, true, NONCE), (HostBuffer::Mapped, 20));
+        // 2 buffers of 32 cores don't fit in 3.75 GiB, the staging buffer takes one
+        let small = DeviceMemory {
+            global: 4 * GIB,
+            ..card
+        };
+        assert_eq!(small.host_buffer(40, 256, false, false, NONCE), (HostBuffer::Shared, 32));
+        assert_eq!(small.host_buffer(40, 256, false, true, NONCE), (HostBuffer::Copied, 20));
+        // the allocation limit fits no more cores with a shared buffer
+        let narrow = DeviceMemory {
+            max_alloc: GIB,
+            ..small
+        };
+        assert_eq!(narrow.host_buffer(40, 256, false, false, NONCE), (HostBuffer::Copied, 16));
+
+        let integrated = DeviceMemory {
+            unified: true,
+            ..card
+        };
+        assert_eq!(integrated.host_buffer(20, 256, false, false, NONCE), (HostBuffer::Mapped, 20));
+        assert_eq!(HostBuffer::Mapped.host_buffers(false), 2);
+        assert_eq!(HostBuffer::Mapped.host_buffers(true), 1);
+        assert_eq!(HostBuffer::Copied.host_buffers(true), 0);
+        assert_eq!(HostBuffer::Shared.device_buffers(false), 1);
+    }
+}
@@ -0,0 +1,292 @@
+use std::fmt;
+use std::str::FromStr;
+
+// One entry of a --gpu list or the GUI's selection
+#[derive(Clone, Debug, PartialEq)]
+pub enum GpuSelector {
+    // every GPU of every platform
+    All,
+    // OpenCL platform and device index, optionally with the cores to use
+    Device {
+        platform: usize,
+        device: usize,
+        cores: Option<usize>,
+    },
+    // position in the list of all devices of all platforms
+    Index(usize),
+    // devices whose name contains this, case insensitive, numbers are indices
+    Name(String),
+}
+
+impl FromStr for GpuSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<GpuSelector, String> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("empty GPU selection".to_string());
+        }
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(GpuSelector::All);
+        }
+        if let Ok(index) = s.parse() {
+            return Ok(GpuSelector::Index(index));
+        }
+        let parts: Vec<&str> = s.split(':').collect();
+        let numbers: Vec<usize> = parts.iter().filter_map(|x| x.trim().parse().ok()).collect();
+        if numbers.len() == parts.len() {
+            return match numbers[..] {
+                [platform, device] => Ok(GpuSelector::Device {
+                    platform,
+                    device,
+                    cores: None,
+                }),
+                // 0 cores means all of them, like before
+                [platform, device, cores] => Ok(GpuSelector::Device {
+                    platform,
+                    device,
+                    cores: Some(cores).filter(|&x| x > 0),
+                }),
+                _ => Err(format!(
+                    "invalid GPU {}, expected platform:device[:cores]",
+                    s
+                )),
+            };
+        }
+        Ok(GpuSelector::Name(s.to_lowercase()))
+    }
+}
+
+impl fmt::Display for GpuSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GpuSelector::All => write!(f, "all"),
+            GpuSelector::Device {
+                platform,
+                device,
+                cores: None,
+            } => write!(f, "{}:{}", platform, device),
+            GpuSelector::Device {
+                platform,
+                device,
+                cores: Some(cores),
+            } => write!(f, "{}:{}:{}", platform, device, cores),
+            GpuSelector::Index(index) => write!(f, "{}", index),
+            GpuSelector::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+// an OpenCL device as the selectors see it
+#[derive(Clone, Debug, PartialEq)]
+pub struct OclDevice {
+    pub platform: usize,
+    pub device: usize,
+    pub name: String,
+    pub vendor: String,
+    pub gpu: bool,
+    pub compute_units: usize,
+}
+
+// a device to hash with, None cores uses all compute units
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelectedGpu {
+    pub platform: usize,
+    pub device: usize,
+    pub name: String,
+    pub compute_units: usize,
+    pub cores: Option<usize>,
+}
+
+impl SelectedGpu {
+    // platform:device[:cores], what PlotterTask and autotune store
+    pub fn spec(&self) -> String {
+        match self.cores {
+            Some(cores) => format!("{}:{}:{}", self.platform, self.device, cores),
+            None => format!("{}:{}", self.platform, self.device),
+        }
+    }
+}
+
+// Resolves the selectors against the devices, in order and without
+// duplicates. An empty selection is all GPUs. Selectors that match nothing
+// are errors, no GPU is ever left out silently.
+pub fn resolve(selectors: &[GpuSelector], devices: &[OclDevice]) -> Result<Vec<SelectedGpu>, String> {
+    let all = [GpuSelector::All];
+    let selectors = if selectors.is_empty() { &all[..] } else { selectors };
+
+    let mut selected: Vec<SelectedGpu> = Vec::new();
+    for selector in selectors {
+        let (matches, cores): (Vec<&OclDevice>, Option<usize>) = match selector {
+            GpuSelector::All => (devices.iter().filter(|x| x.gpu).collect(), None),
+            GpuSelector::Device {
+                platform,
+                device,
+                cores,
+            } => {
+                if !devices.iter().any(|x| x.platform == *platform) {
+                    return Err(format!("OpenCL platform {} doesn't exist", platform));
+                }
+                let found = devices
+                    .iter()
+                    .find(|x| x.platform == *platform && x.device == *device)
+                    .ok_or_else(|| {
+                        format!("OpenCL device {} doesn't exist on platform {}", device, platform)
+                    })?;
+                (vec![found], *cores)
+            }
+            GpuSelector::Index(index) => {
+                let found = devices.get(*index).ok_or_else(|| {
+                    format!("OpenCL device {} doesn't exist, found {}", index, devices.len())
+                })?;
+                (vec![found], None)
+            }
+            GpuSelector::Name(name) => (
+                devices
+                    .iter()
+                    .filter(|x| x.name.to_lowercase().contains(name.as_str()))
+                    .collect(),
+                None,
+            ),
+        };
+        if matches.is_empty() {
+            return Err(match selector {
+                GpuSelector::All => "no OpenCL GPUs found".to_string(),
+                _ => format!("no OpenCL device matches \"{}\"", selector),
+            });
+        }
+        for device in matches {
+            if selected
+                .iter()
+                .any(|x| x.platform == device.platform && x.device == device.device)
+            {
+                continue;
+            }
+            selected.push(SelectedGpu {
+                platform: device.platform,
+                device: device.device,
+                name: device.name.clone(),
+                compute_units: device.compute_units,
+                cores,
+            });
+        }
+    }
+    Ok(selected)
+}
+
+// parses and resolves the strings of a --gpu list or PlotterTask::gpus
+pub fn select(gpus: &[String], devices: &[OclDevice]) -> Result<Vec<SelectedGpu>, String> {
+    let selectors = gpus
+        .iter()
+        .map(|x| x.parse())
+        .collect::<Result<Vec<GpuSelector>, String>>()?;
+    resolve(&selectors, devices)
+}
+
+// the OpenCL devices of this machine, none without the opencl feature
+#[cfg(feature = "opencl")]
+pub fn devices() -> Vec<OclDevice> {
+    crate::ocl::ocl_devices()
+}
+
+#[cfg(not(feature = "opencl"))]
+pub fn devices() -> Vec<OclDevice> {
+    Vec::new()
+}
+
+// PlotterTask::gpus resolved against the devices of this machine
+pub fn select_gpus(gpus: &[String]) -> Result<Vec<SelectedGpu>, String> {
+    select(gpus, &devices())
+}
+
+// "platform:device" of every selected gpu and its number of compute units
+pub fn gpu_compute_units(gpus: &[String]) -> Result<Vec<(String, usize)>, String> {
+    Ok(select_gpus(gpus)?
+        .iter()
+        .map(|x| (format!("{}:{}", x.platform, x.device), x.compute_units))
+        .collect())
+}
+
+#[cfg(test)]
+mod gpu_selector_tests {
+    use super::*;
+
+    fn device(platform: usize, device: usize, name: &str, gpu: bool) -> OclDevice {
+        OclDevice {
+            platform,
+            device,
+            name: name.to_string(),
+            vendor: String::new(),
+            gpu,
+            compute_units: 8,
+        }
+    }
+
+    fn machine() -> Vec<OclDevice> {
+        vec![
+            device(0, 0, "GeForce RTX 3080", true),
+            device(0, 1, "GeForce RTX 2060", true),
+            device(1, 0, "pthread-znver3", false),
+            device(2, 0, "gfx1030", true),
+        ]
+    }
+
+    fn specs(gpus: &[&str]) -> Result<Vec<String>, String> {
+        let gpus: Vec<String> = gpus.iter().map(|x| x.to_string()).collect();
+        select(&gpus, &machine()).map(|x| x.iter().map(|x| x.spec()).collect())
+    }
+
+    #[test]
+    fn parses_every_form() {
+        assert_eq!("ALL".parse(), Ok(GpuSelector::All));
+        assert_eq!("3".parse(), Ok(GpuSelector::Index(3)));
+        assert_eq!(
+            "1:2".parse(),
+            Ok(GpuSelector::Device {
+                platform: 1,
+                device: 2,
+                cores: None
+            })
+        );
+        assert_eq!(
+            "1:2:0".parse(),
+            Ok(GpuSelector::Device {
+                platform: 1,
+                device: 2,
+                cores: None
+            })
+        );
+        assert_eq!("RTX 3080".parse(), Ok(GpuSelector::Name("rtx 3080".to_string())));
+        assert!("1:2:3:4".parse::<GpuSelector>().is_err());
+        assert!(" ".parse::<GpuSelector>().is_err());
+        assert_eq!("0:1:20".parse::<GpuSelector>().unwrap().to_string(), "0:1:20");
+    }
+
+    #[test]
+    fn resolves_to_existing_devices() {
+        assert_eq!(specs(&["all"]), Ok(vec!["0:0".into(), "0:1".into(), "2:0".into()]));
+        assert_eq!(specs(&[]), specs(&["all"]));
+        assert_eq!(specs(&["3", "0:1:20"]), Ok(vec!["2:0".into(), "0:1:20".into()]));
+        assert_eq!(specs(&["rtx"]), Ok(vec!["0:0".into(), "0:1".into()]));
+        // cpu runtimes only when asked for
+        assert_eq!(specs(&["pthread"]), Ok(vec!["1:0".into()]));
+        // the first mention wins
+        assert_eq!(specs(&["rtx 2060", "all"]), Ok(vec!["0:1".into(), "0:0".into(), "2:0".into()]));
+    }
+
+    #[test]
+    fn unknown_devices_are_errors() {
+        assert_eq!(specs(&["4"]), Err("OpenCL device 4 doesn't exist, found 4".to_string()));
+        assert_eq!(specs(&["5:0"]), Err("OpenCL platform 5 doesn't exist".to_string()));
+        assert_eq!(
+            specs(&["0:2"]),
+            Err("OpenCL device 2 doesn't exist on platform 0".to_string())
+        );
+        assert_eq!(specs(&["vega"]), Err("no OpenCL device matches \"vega\"".to_string()));
+        let cpu_only = vec![device(0, 0, "pthread", false)];
+        assert_eq!(
+            select(&["all".to_string()], &cpu_only),
+            Err("no OpenCL GPUs found".to_string())
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// Compiled OpenCL programs on disk, one file per device, driver and kernel.
+// Building kernel.cl from source takes seconds per card, loading a binary is
+// close to free. Entries are never evicted, a new driver or kernel just gets
+// a new key.
+pub struct KernelCache {
+    dir: PathBuf,
+}
+
+impl KernelCache {
+    pub fn new(dir: PathBuf) -> KernelCache {
+        KernelCache { dir }
+    }
+
+    // next to the gui's settings, None if the os has no config directory
+    pub fn open() -> Option<KernelCache> {
+        eframe::storage_dir("Signum Plotter GUI").map(|dir| KernelCache::new(dir.join("kernels")))
+    }
+
+    pub fn load(&self, key: &str) -> Option<Vec<u8>> {
+        fs::read(self.path(key)).ok().filter(|x| !x.is_empty())
+    }
+
+    // written aside and renamed, plotters started at once never see half a binary
+    pub fn store(&self, key: &str, binary: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let part = self.dir.join(format!("{}.{}.part", key, std::process::id()));
+        fs::write(&part, binary)?;
+        fs::rename(&part, self.path(key))
+    }
+
+    // a binary that doesn't load (e.g. a driver reporting the old version) is rebuilt
+    pub fn remove(&self, key: &str) {
+        let _ = fs::remove_file(self.path(key));
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.bin", key))
+    }
+}
+
+// sha256 over everything the binary depends on
+pub fn cache_key(device_name: &str, driver_version: &str, source: &str, options: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in [device_name, driver_version, source, options].iter() {
+        hasher.update(part.as_bytes());
+        // keeps ("ab", "c") and ("a", "bc") apart
+        hasher.update([0u8]);
+    }
+    hasher.finalize().iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+#[cfg(test)]
+mod kernel_cache_tests {
+    use super::*;
+
+    #[test]
+    fn keys_depend_on_every_part() {
+        let key = cache_key("gfx1030", "3491.0", "kernel", "");
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, cache_key("gfx1030", "3491.0", "kernel", ""));
+        assert_ne!(key, cache_key("gfx1031", "3491.0", "kernel", ""));
+        assert_ne!(key, cache_key("gfx1030", "3492.0", "kernel", ""));
+        assert_ne!(key, cache_key("gfx1030", "3491.0", "kernel ", ""));
+        assert_ne!(key, cache_key("gfx1030", "3491.0", "kernel", "-O2"));
+        assert_ne!(cache_key("ab", "c", "", ""), cache_key("a", "bc", "", ""));
+    }
+
+    #[test]
+    fn binaries_round_trip() {
+        let dir = std::env::temp_dir().join(format!("signum-kernels-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let cache = KernelCache::new(dir.clone());
+        let key = cache_key("device", "driver", "source", "");
+        assert_eq!(cache.load(&key), None);
+        cache.store(&key, b"binary").unwrap();
+        assert_eq!(cache.load(&key), Some(b"binary".to_vec()));
+        cache.remove(&key);
+        assert_eq!(cache.load(&key), None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
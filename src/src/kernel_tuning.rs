@@ -0,0 +1,310 @@
+use crate::gpu_selector::GpuSelector;
+#[cfg(feature = "opencl")]
+use crate::gpu_selector::{self, OclDevice, SelectedGpu};
+use std::fmt;
+#[cfg(feature = "opencl")]
+use std::fs;
+use std::str::FromStr;
+
+// a run hashes a divisor of a nonce's 8192 hashes, the last one takes the final hash too
+const HASHES_PER_NONCE: usize = 8192;
+// the kernel interleaves nonces in vectors of 16, work groups are whole vectors
+const MSHABAL512_VECTOR_SIZE: usize = 16;
+#[cfg(feature = "opencl")]
+pub const DEFAULT_HASHES_PER_RUN: usize = 32;
+
+// One --kernel entry, "key=value;..." with the keys
+//   gpu       selector of the devices it applies to, all if omitted
+//   file      kernel source instead of the built-in kernel.cl
+//   options   OpenCL build options, e.g. -cl-fast-relaxed-math -DUNROLL=4
+//   hashes    hashes per kernel run, a power of two up to 8192
+//   workgroup nonces per work group, a multiple of 16
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KernelSetting {
+    pub gpu: Option<GpuSelector>,
+    pub file: Option<String>,
+    pub options: Option<String>,
+    pub hashes_per_run: Option<usize>,
+    pub workgroup_size: Option<usize>,
+}
+
+impl FromStr for KernelSetting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<KernelSetting, String> {
+        let mut setting = KernelSetting::default();
+        for pair in s.split(';').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            let mut parts = pair.splitn(2, '=');
+            let key = parts.next().unwrap().trim().to_lowercase();
+            let value = parts
+                .next()
+                .map(|x| x.trim())
+                .ok_or_else(|| format!("invalid kernel setting {}, expected key=value", pair))?;
+            match key.as_str() {
+                "gpu" => setting.gpu = Some(value.parse()?),
+                "file" => setting.file = Some(value.to_string()),
+                "options" => setting.options = Some(value.to_string()),
+                "hashes" => {
+                    setting.hashes_per_run = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|x: &usize| x.is_power_of_two() && *x <= HASHES_PER_NONCE)
+                            .ok_or_else(|| {
+                                format!("invalid hashes per run {}, expected a power of two up to 8192", value)
+                            })?,
+                    )
+                }
+                "workgroup" => {
+                    setting.workgroup_size = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|x: &usize| *x > 0 && x % MSHABAL512_VECTOR_SIZE == 0)
+                            .ok_or_else(|| {
+                                format!("invalid work group size {}, expected a multiple of 16", value)
+                            })?,
+                    )
+                }
+                _ => {
+                    return Err(format!(
+                        "unknown kernel setting {}, expected gpu, file, options, hashes or workgroup",
+                        key
+                    ))
+                }
+            }
+        }
+        if setting == KernelSetting::default() {
+            return Err("empty kernel setting".to_string());
+        }
+        Ok(setting)
+    }
+}
+
+impl fmt::Display for KernelSetting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut pairs = Vec::new();
+        if let Some(gpu) = &self.gpu {
+            pairs.push(format!("gpu={}", gpu));
+        }
+        if let Some(file) = &self.file {
+            pairs.push(format!("file={}", file));
+        }
+        if let Some(options) = &self.options {
+            pairs.push(format!("options={}", options));
+        }
+        if let Some(hashes) = self.hashes_per_run {
+            pairs.push(format!("hashes={}", hashes));
+        }
+        if let Some(workgroup) = self.workgroup_size {
+            pairs.push(format!("workgroup={}", workgroup));
+        }
+        write!(f, "{}", pairs.join(";"))
+    }
+}
+
+// "setting|setting|...", the gui's single line for what the cli takes as several --kernel
+pub fn parse_settings(s: &str) -> Result<Vec<KernelSetting>, String> {
+    s.split('|')
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| x.parse())
+        .collect()
+}
+
+// how the kernel is built and run on one device
+#[cfg(feature = "opencl")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct KernelTuning {
+    pub file: Option<String>,
+    pub options: String,
+    pub hashes_per_run: usize,
+    // None uses the largest work group the kernel allows
+    pub workgroup_size: Option<usize>,
+}
+
+#[cfg(feature = "opencl")]
+impl Default for KernelTuning {
+    fn default() -> KernelTuning {
+        KernelTuning {
+            file: None,
+            options: String::new(),
+            hashes_per_run: DEFAULT_HASHES_PER_RUN,
+            workgroup_size: None,
+        }
+    }
+}
+
+#[cfg(feature = "opencl")]
+impl KernelTuning {
+    // Applies the settings whose gpu selects this device, later ones override
+    // earlier ones. A selector matching no device at all is an error.
+    pub fn resolve(
+        settings: &[KernelSetting],
+        gpu: &SelectedGpu,
+        devices: &[OclDevice],
+    ) -> Result<KernelTuning, String> {
+        let mut tuning = KernelTuning::default();
+        for setting in settings {
+            if let Some(selector) = &setting.gpu {
+                let selected = gpu_selector::resolve(&[selector.clone()], devices)?;
+                if !selected
+                    .iter()
+                    .any(|x| x.platform == gpu.platform && x.device == gpu.device)
+                {
+                    continue;
+                }
+            }
+            if let Some(file) = &setting.file {
+                tuning.file = Some(file.clone());
+            }
+            if let Some(options) = &setting.options {
+                tuning.options = options.clone();
+            }
+            if let Some(hashes) = setting.hashes_per_run {
+                tuning.hashes_per_run = hashes;
+            }
+            if let Some(workgroup) = setting.workgroup_size {
+                tuning.workgroup_size = Some(workgroup);
+            }
+        }
+        Ok(tuning)
+    }
+
+    // the file's contents or the built-in kernel
+    pub fn source(&self, builtin: &str) -> Result<String, String> {
+        match &self.file {
+            Some(path) => fs::read_to_string(path)
+                .map_err(|e| format!("can't read the OpenCL kernel {}: {}", path, e)),
+            None => Ok(builtin.to_string()),
+        }
+    }
+
+    // the requested work group size within what the kernel allows on the device
+    pub fn workgroup_size(&self, kernel_max: usize) -> usize {
+        match self.workgroup_size {
+            Some(x) if x < kernel_max => x,
+            _ => kernel_max,
+        }
+    }
+
+    // the first and last hash of every kernel run, as calculate_nonces takes them
+    pub fn runs(&self) -> Vec<(i32, i32)> {
+        (0..HASHES_PER_NONCE)
+            .step_by(self.hashes_per_run)
+            .map(|start| {
+                let end = if start + self.hashes_per_run < HASHES_PER_NONCE {
+                    start + self.hashes_per_run - 1
+                } else {
+                    start + self.hashes_per_run
+                };
+                (start as i32, end as i32)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod kernel_tuning_tests {
+    use super::*;
+
+    #[cfg(feature = "opencl")]
+    fn device(platform: usize, device: usize, name: &str) -> OclDevice {
+        OclDevice {
+            platform,
+            device,
+            name: name.to_string(),
+            vendor: String::new(),
+            gpu: true,
+            compute_units: 8,
+        }
+    }
+
+    #[cfg(feature = "opencl")]
+    fn selected(device: &OclDevice) -> SelectedGpu {
+        SelectedGpu {
+            platform: device.platform,
+            device: device.device,
+            name: device.name.clone(),
+            compute_units: device.compute_units,
+            cores: None,
+        }
+    }
+
+    #[test]
+    fn parse_settings_round_trip() {
+        let setting: KernelSetting = "gpu=0:1; options=-cl-fast-relaxed-math -DUNROLL=4;hashes=64;workgroup=128"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            setting,
+            KernelSetting {
+                gpu: Some(GpuSelector::Device {
+                    platform: 0,
+                    device: 1,
+                    cores: None
+                }),
+                file: None,
+                options: Some("-cl-fast-relaxed-math -DUNROLL=4".to_string()),
+                hashes_per_run: Some(64),
+                workgroup_size: Some(128),
+            }
+        );
+        assert_eq!(setting.to_string().parse(), Ok(setting));
+        assert_eq!(
+            parse_settings("hashes=16 | gpu=rx;file=fast.cl").unwrap().len(),
+            2
+        );
+        assert_eq!(parse_settings(""), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        assert!("".parse::<KernelSetting>().is_err());
+        assert!("hashes".parse::<KernelSetting>().is_err());
+        assert!("hashes=0".parse::<KernelSetting>().is_err());
+        assert!("hashes=48".parse::<KernelSetting>().is_err());
+        assert!("hashes=16384".parse::<KernelSetting>().is_err());
+        assert!("workgroup=0".parse::<KernelSetting>().is_err());
+        assert!("workgroup=100".parse::<KernelSetting>().is_err());
+        assert!("unroll=4".parse::<KernelSetting>().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "opencl")]
+    fn settings_apply_per_device() {
+        let devices = vec![device(0, 0, "Radeon RX 6800"), device(1, 0, "GeForce RTX 3080")];
+        let settings = parse_settings("options=-cl-mad-enable;hashes=64|gpu=geforce;hashes=128;workgroup=512").unwrap();
+
+        let radeon = KernelTuning::resolve(&settings, &selected(&devices[0]), &devices).unwrap();
+        assert_eq!(radeon.options, "-cl-mad-enable");
+        assert_eq!(radeon.hashes_per_run, 64);
+        assert_eq!(radeon.workgroup_size, None);
+
+        let geforce = KernelTuning::resolve(&settings, &selected(&devices[1]), &devices).unwrap();
+        assert_eq!(geforce.options, "-cl-mad-enable");
+        assert_eq!(geforce.hashes_per_run, 128);
+        assert_eq!(geforce.workgroup_size(1024), 512);
+        assert_eq!(geforce.workgroup_size(256), 256);
+
+        let missing = parse_settings("gpu=intel;hashes=8").unwrap();
+        assert!(KernelTuning::resolve(&missing, &selected(&devices[0]), &devices).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "opencl")]
+    fn runs_cover_every_hash() {
+        for &hashes in [1, 32, 1024, 8192].iter() {
+            let tuning = KernelTuning {
+                hashes_per_run: hashes,
+                ..Default::default()
+            };
+            let runs = tuning.runs();
+            assert_eq!(runs.len(), HASHES_PER_NONCE / hashes);
+            // the kernel hashes from 8192 - start down to 8192 - end, both included
+            let covered: usize = runs.iter().map(|(start, end)| (end - start + 1) as usize).sum();
+            assert_eq!(covered, HASHES_PER_NONCE + 1);
+            assert!(runs.windows(2).all(|x| x[1].0 == x[0].1 + 1));
+        }
+        assert_eq!(KernelTuning::default().runs()[0], (0, 31));
+    }
+}
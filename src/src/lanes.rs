@@ -0,0 +1,490 @@
+use crate::buffer::NonceView;
+use crate::cpu_hasher::SimdExtension;
+use crate::poc_hashing::noncegen_lanes;
+use cfg_if::cfg_if;
+
+// One 32 bit word of N shabal states side by side, the layout the mshabal C
+// implementations keep in a vector register. Everything is inline(always) so the
+// hashing loops end up inside the target_feature functions below.
+pub trait Lanes<const N: usize>: Copy {
+    fn splat(x: u32) -> Self;
+    // N consecutive words, needn't be aligned
+    fn load(words: &[u32]) -> Self;
+    fn store(self, words: &mut [u32]);
+    fn add(self, other: Self) -> Self;
+    fn sub(self, other: Self) -> Self;
+    fn xor(self, other: Self) -> Self;
+    fn or(self, other: Self) -> Self;
+    // !self & other
+    fn andnot(self, other: Self) -> Self;
+    fn shl<const S: i32>(self) -> Self;
+    fn shr<const S: i32>(self) -> Self;
+
+    #[inline(always)]
+    fn not(self) -> Self {
+        self.xor(Self::splat(!0))
+    }
+
+    // rotate left by L, R = 32 - L
+    #[inline(always)]
+    fn rotl<const L: i32, const R: i32>(self) -> Self {
+        self.shl::<L>().or(self.shr::<R>())
+    }
+
+    #[inline(always)]
+    fn mul3(self) -> Self {
+        self.add(self.shl::<1>())
+    }
+
+    #[inline(always)]
+    fn mul5(self) -> Self {
+        self.add(self.shl::<2>())
+    }
+}
+
+// plain arrays, left to the auto-vectorizer
+impl<const N: usize> Lanes<N> for [u32; N] {
+    #[inline(always)]
+    fn splat(x: u32) -> Self {
+        [x; N]
+    }
+
+    #[inline(always)]
+    fn load(words: &[u32]) -> Self {
+        let mut x = [0u32; N];
+        x.copy_from_slice(&words[..N]);
+        x
+    }
+
+    #[inline(always)]
+    fn store(self, words: &mut [u32]) {
+        words[..N].copy_from_slice(&self);
+    }
+
+    #[inline(always)]
+    fn add(self, other: Self) -> Self {
+        std::array::from_fn(|i| self[i].wrapping_add(other[i]))
+    }
+
+    #[inline(always)]
+    fn sub(self, other: Self) -> Self {
+        std::array::from_fn(|i| self[i].wrapping_sub(other[i]))
+    }
+
+    #[inline(always)]
+    fn xor(self, other: Self) -> Self {
+        std::array::from_fn(|i| self[i] ^ other[i])
+    }
+
+    #[inline(always)]
+    fn or(self, other: Self) -> Self {
+        std::array::from_fn(|i| self[i] | other[i])
+    }
+
+    #[inline(always)]
+    fn andnot(self, other: Self) -> Self {
+        std::array::from_fn(|i| !self[i] & other[i])
+    }
+
+    #[inline(always)]
+    fn shl<const S: i32>(self) -> Self {
+        self.map(|x| x << S)
+    }
+
+    #[inline(always)]
+    fn shr<const S: i32>(self) -> Self {
+        self.map(|x| x >> S)
+    }
+}
+
+// The vector types are private and only used inside functions enabling their
+// target feature, which the dispatcher checks before calling them.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86 {
+    use super::Lanes;
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    #[derive(Clone, Copy)]
+    pub struct Sse2(__m128i);
+
+    #[derive(Clone, Copy)]
+    pub struct Avx2(__m256i);
+
+    #[derive(Clone, Copy)]
+    pub struct Avx512f(__m512i);
+
+    impl Lanes<4> for Sse2 {
+        #[inline(always)]
+        fn splat(x: u32) -> Self {
+            unsafe { Sse2(_mm_set1_epi32(x as i32)) }
+        }
+
+        #[inline(always)]
+        fn load(words: &[u32]) -> Self {
+            assert!(words.len() >= 4);
+            unsafe { Sse2(_mm_loadu_si128(words.as_ptr() as *const __m128i)) }
+        }
+
+        #[inline(always)]
+        fn store(self, words: &mut [u32]) {
+            assert!(words.len() >= 4);
+            unsafe { _mm_storeu_si128(words.as_mut_ptr() as *mut __m128i, self.0) }
+        }
+
+        #[inline(always)]
+        fn add(self, other: Self) -> Self {
+            unsafe { Sse2(_mm_add_epi32(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn sub(self, other: Self) -> Self {
+            unsafe { Sse2(_mm_sub_epi32(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn xor(self, other: Self) -> Self {
+            unsafe { Sse2(_mm_xor_si128(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn or(self, other: Self) -> Self {
+            unsafe { Sse2(_mm_or_si128(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn andnot(self, other: Self) -> Self {
+            unsafe { Sse2(_mm_andnot_si128(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn shl<const S: i32>(self) -> Self {
+            unsafe { Sse2(_mm_slli_epi32::<S>(self.0)) }
+        }
+
+        #[inline(always)]
+        fn shr<const S: i32>(self) -> Self {
+            unsafe { Sse2(_mm_srli_epi32::<S>(self.0)) }
+        }
+    }
+
+    impl Lanes<8> for Avx2 {
+        #[inline(always)]
+        fn splat(x: u32) -> Self {
+            unsafe { Avx2(_mm256_set1_epi32(x as i32)) }
+        }
+
+        #[inline(always)]
+        fn load(words: &[u32]) -> Self {
+            assert!(words.len() >= 8);
+            unsafe { Avx2(_mm256_loadu_si256(words.as_ptr() as *const __m256i)) }
+        }
+
+        #[inline(always)]
+        fn store(self, words: &mut [u32]) {
+            assert!(words.len() >= 8);
+            unsafe { _mm256_storeu_si256(words.as_mut_ptr() as *mut __m256i, self.0) }
+        }
+
+        #[inline(always)]
+        fn add(self, other: Self) -> Self {
+            unsafe { Avx2(_mm256_add_epi32(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn sub(self, other: Self) -> Self {
+            unsafe { Avx2(_mm256_sub_epi32(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn xor(self, other: Self) -> Self {
+            unsafe { Avx2(_mm256_xor_si256(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn or(self, other: Self) -> Self {
+            unsafe { Avx2(_mm256_or_si256(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn andnot(self, other: Self) -> Self {
+            unsafe { Avx2(_mm256_andnot_si256(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn shl<const S: i32>(self) -> Self {
+            unsafe { Avx2(_mm256_slli_epi32::<S>(self.0)) }
+        }
+
+        #[inline(always)]
+        fn shr<const S: i32>(self) -> Self {
+            unsafe { Avx2(_mm256_srli_epi32::<S>(self.0)) }
+        }
+    }
+
+    impl Lanes<16> for Avx512f {
+        #[inline(always)]
+        fn splat(x: u32) -> Self {
+            unsafe { Avx512f(_mm512_set1_epi32(x as i32)) }
+        }
+
+        #[inline(always)]
+        fn load(words: &[u32]) -> Self {
+            assert!(words.len() >= 16);
+            unsafe { Avx512f(_mm512_loadu_si512(words.as_ptr() as *const __m512i)) }
+        }
+
+        #[inline(always)]
+        fn store(self, words: &mut [u32]) {
+            assert!(words.len() >= 16);
+            unsafe { _mm512_storeu_si512(words.as_mut_ptr() as *mut __m512i, self.0) }
+        }
+
+        #[inline(always)]
+        fn add(self, other: Self) -> Self {
+            unsafe { Avx512f(_mm512_add_epi32(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn sub(self, other: Self) -> Self {
+            unsafe { Avx512f(_mm512_sub_epi32(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn xor(self, other: Self) -> Self {
+            unsafe { Avx512f(_mm512_xor_si512(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn or(self, other: Self) -> Self {
+            unsafe { Avx512f(_mm512_or_si512(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn andnot(self, other: Self) -> Self {
+            unsafe { Avx512f(_mm512_andnot_si512(self.0, other.0)) }
+        }
+
+        // the immediate shifts take an unsigned count here
+        #[inline(always)]
+        fn shl<const S: i32>(self) -> Self {
+            unsafe { Avx512f(_mm512_sll_epi32(self.0, _mm_cvtsi32_si128(S))) }
+        }
+
+        #[inline(always)]
+        fn shr<const S: i32>(self) -> Self {
+            unsafe { Avx512f(_mm512_srl_epi32(self.0, _mm_cvtsi32_si128(S))) }
+        }
+
+        // a single instruction instead of two shifts and an or
+        #[inline(always)]
+        fn rotl<const L: i32, const R: i32>(self) -> Self {
+            unsafe { Avx512f(_mm512_rol_epi32::<L>(self.0)) }
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arm {
+    use super::Lanes;
+    use std::arch::aarch64::*;
+
+    // neon is part of every aarch64 target
+    #[derive(Clone, Copy)]
+    pub struct Neon(uint32x4_t);
+
+    impl Lanes<4> for Neon {
+        #[inline(always)]
+        fn splat(x: u32) -> Self {
+            unsafe { Neon(vdupq_n_u32(x)) }
+        }
+
+        #[inline(always)]
+        fn load(words: &[u32]) -> Self {
+            assert!(words.len() >= 4);
+            unsafe { Neon(vld1q_u32(words.as_ptr())) }
+        }
+
+        #[inline(always)]
+        fn store(self, words: &mut [u32]) {
+            assert!(words.len() >= 4);
+            unsafe { vst1q_u32(words.as_mut_ptr(), self.0) }
+        }
+
+        #[inline(always)]
+        fn add(self, other: Self) -> Self {
+            unsafe { Neon(vaddq_u32(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn sub(self, other: Self) -> Self {
+            unsafe { Neon(vsubq_u32(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn xor(self, other: Self) -> Self {
+            unsafe { Neon(veorq_u32(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn or(self, other: Self) -> Self {
+            unsafe { Neon(vorrq_u32(self.0, other.0)) }
+        }
+
+        #[inline(always)]
+        fn andnot(self, other: Self) -> Self {
+            unsafe { Neon(vbicq_u32(other.0, self.0)) }
+        }
+
+        #[inline(always)]
+        fn not(self) -> Self {
+            unsafe { Neon(vmvnq_u32(self.0)) }
+        }
+
+        #[inline(always)]
+        fn shl<const S: i32>(self) -> Self {
+            unsafe { Neon(vshlq_n_u32::<S>(self.0)) }
+        }
+
+        #[inline(always)]
+        fn shr<const S: i32>(self) -> Self {
+            unsafe { Neon(vshrq_n_u32::<S>(self.0)) }
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] {
+        #[target_feature(enable = "sse2")]
+        unsafe fn noncegen_sse2(cache: &mut NonceView, numeric_id: u64, local_startnonce: u64) {
+            noncegen_lanes::<x86::Sse2, 4>(cache, numeric_id, local_startnonce);
+        }
+
+        #[target_feature(enable = "avx2")]
+        unsafe fn noncegen_avx2(cache: &mut NonceView, numeric_id: u64, local_startnonce: u64) {
+            noncegen_lanes::<x86::Avx2, 8>(cache, numeric_id, local_startnonce);
+        }
+
+        #[target_feature(enable = "avx512f")]
+        unsafe fn noncegen_avx512f(cache: &mut NonceView, numeric_id: u64, local_startnonce: u64) {
+            noncegen_lanes::<x86::Avx512f, 16>(cache, numeric_id, local_startnonce);
+        }
+
+        // 16, 8 or 4 lanes like the c backends, avx has no rust hasher of its own
+        pub fn lanes(simd_ext: &SimdExtension) -> usize {
+            match simd_ext {
+                SimdExtension::AVX512f => 16,
+                SimdExtension::AVX2 => 8,
+                SimdExtension::AVX | SimdExtension::SSE2 => 4,
+                _ => PORTABLE_LANES,
+            }
+        }
+
+        // hashes nonces with the extension's vectors, "rust" without any
+        pub fn noncegen(simd_ext: &SimdExtension, cache: &mut NonceView, numeric_id: u64, local_startnonce: u64) {
+            assert!(simd_ext.is_supported(), "this cpu doesn't support {}", simd_ext);
+            unsafe {
+                match simd_ext {
+                    SimdExtension::AVX512f => noncegen_avx512f(cache, numeric_id, local_startnonce),
+                    SimdExtension::AVX2 => noncegen_avx2(cache, numeric_id, local_startnonce),
+                    SimdExtension::AVX | SimdExtension::SSE2 => noncegen_sse2(cache, numeric_id, local_startnonce),
+                    _ => noncegen_lanes::<[u32; PORTABLE_LANES], PORTABLE_LANES>(cache, numeric_id, local_startnonce),
+                }
+            }
+        }
+    } else if #[cfg(target_arch = "aarch64")] {
+        pub fn lanes(simd_ext: &SimdExtension) -> usize {
+            match simd_ext {
+                SimdExtension::NEON => 4,
+                _ => PORTABLE_LANES,
+            }
+        }
+
+        pub fn noncegen(simd_ext: &SimdExtension, cache: &mut NonceView, numeric_id: u64, local_startnonce: u64) {
+            match simd_ext {
+                SimdExtension::NEON => noncegen_lanes::<arm::Neon, 4>(cache, numeric_id, local_startnonce),
+                _ => noncegen_lanes::<[u32; PORTABLE_LANES], PORTABLE_LANES>(cache, numeric_id, local_startnonce),
+            }
+        }
+    } else {
+        pub fn lanes(_simd_ext: &SimdExtension) -> usize {
+            PORTABLE_LANES
+        }
+
+        pub fn noncegen(_simd_ext: &SimdExtension, cache: &mut NonceView, numeric_id: u64, local_startnonce: u64) {
+            noncegen_lanes::<[u32; PORTABLE_LANES], PORTABLE_LANES>(cache, numeric_id, local_startnonce);
+        }
+    }
+}
+
+// "rust", and cpus without known vector units
+const PORTABLE_LANES: usize = 8;
+
+#[cfg(test)]
+mod lanes_tests {
+    use super::*;
+    use crate::buffer::PageAlignedByteBuffer;
+    use crate::poc_hashing::noncegen_rust;
+    use std::ops::Range;
+
+    use std::sync::OnceLock;
+
+    const NONCE_SIZE: usize = 4096 * 64;
+    // full rounds of 4, 8 and 16 lanes plus a tail
+    const NONCES: usize = 17;
+    const CACHE_SIZE: usize = NONCES + 2;
+
+    const VIEW: Range<u64> = Range { start: 1, end: 1 + NONCES as u64 };
+
+    fn check(lanes: usize, generate: impl Fn(&mut NonceView, u64, u64)) {
+        static SCALAR: OnceLock<Vec<u8>> = OnceLock::new();
+        let scalar = SCALAR.get_or_init(|| {
+            let mut cache = PageAlignedByteBuffer::new(CACHE_SIZE * NONCE_SIZE);
+            cache.as_mut_slice().fill(0);
+            noncegen_rust(&mut cache.lend(VIEW), 7900104405094198526, 1337);
+            cache.as_slice().to_vec()
+        });
+        let mut vector = PageAlignedByteBuffer::new(CACHE_SIZE * NONCE_SIZE);
+        vector.as_mut_slice().fill(0);
+        generate(&mut vector.lend(VIEW), 7900104405094198526, 1337);
+        assert!(
+            scalar == vector.as_slice(),
+            "{} lanes differ from noncegen_rust",
+            lanes
+        );
+    }
+
+    #[test]
+    fn portable_lanes_match_scalar() {
+        check(4, noncegen_lanes::<[u32; 4], 4>);
+        check(16, noncegen_lanes::<[u32; 16], 16>);
+    }
+
+    #[test]
+    fn vector_lanes_match_scalar() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("sse2") {
+                check(4, |a, b, c| unsafe { noncegen_sse2(a, b, c) });
+            }
+            if is_x86_feature_detected!("avx2") {
+                check(8, |a, b, c| unsafe { noncegen_avx2(a, b, c) });
+            }
+            if is_x86_feature_detected!("avx512f") {
+                check(16, |a, b, c| unsafe { noncegen_avx512f(a, b, c) });
+            }
+        }
+        let extensions = [
+            SimdExtension::None,
+            SimdExtension::SSE2,
+            SimdExtension::AVX2,
+            SimdExtension::AVX512f,
+            SimdExtension::NEON,
+        ];
+        for simd_ext in extensions.iter().filter(|x| x.is_supported()) {
+            check(lanes(simd_ext), |a, b, c| noncegen(simd_ext, a, b, c));
+        }
+    }
+}
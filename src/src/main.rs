@@ -1,468 +1,1048 @@
-use eframe::egui;
-use egui::{ComboBox, RichText, ScrollArea, TextEdit, Vec2};
-use std::fs;
-use std::io::{self, Read, Write}; // <-- Added Read and Write
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
-use std::thread;
-
-// Module declarations
-mod buffer;
-mod cpu_hasher;
-mod poc_hashing;
-mod gpu_hasher;
-mod ocl;
-mod scheduler;
-mod shabal256;
-mod utils;
-mod writer;
-
-mod plotter;
-use plotter::{Plotter, PlotterTask};
-use ocl_core::{
-    get_platform_ids, get_device_ids, get_device_info, DeviceInfo, DeviceType,
-};
-use ocl::platform_info;
-
-#[derive(serde::Deserialize, serde::Serialize, Default, Clone)]
-struct PlotterGui {
-    numeric_id: String,
-    start_nonce: String,
-    total_nonces: String,
-    plot_size_nonces: String,
-    temp_dir: String,
-    drives: String,
-    cpu_threads: String,
-    mem: String,
-    selected_gpu: String,
-    show_gpu_info: bool,
-
-    #[serde(skip)]
-    gpu_options: Vec<String>,
-    #[serde(skip)]
-    log: String,
-    #[serde(skip)]
-    current_progress: f64,
-    #[serde(skip)]
-    current_status: String,
-    #[serde(skip)]
-    is_plotting: bool,
-}
-
-impl eframe::App for PlotterGui {
-    fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        eframe::set_value(storage, eframe::APP_KEY, self);
-    }
-
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Signum Plotter GUI");
-
-            ui.horizontal(|ui| {
-                ui.label("Account ID:");
-                ui.text_edit_singleline(&mut self.numeric_id);
-            });
-
-            ui.horizontal(|ui| {
-                ui.label("Start Nonce:");
-                ui.text_edit_singleline(&mut self.start_nonce);
-            });
-
-            ui.horizontal(|ui| {
-                ui.label("Total Nonces:");
-                ui.text_edit_singleline(&mut self.total_nonces);
-            });
-
-            ui.horizontal(|ui| {
-                ui.label("Plot Size (nonces):");
-                ui.text_edit_singleline(&mut self.plot_size_nonces);
-                ui.label("(default ~4_194_304 ≈ 1 TiB)");
-            });
-
-            ui.horizontal(|ui| {
-                ui.label("SSD Temp Dir:");
-                ui.text_edit_singleline(&mut self.temp_dir);
-            });
-
-            ui.horizontal(|ui| {
-                ui.label("Final Drives (comma-separated):");
-                ui.text_edit_singleline(&mut self.drives);
-            });
-
-            ui.horizontal(|ui| {
-                ui.label("CPU Threads:");
-                ui.text_edit_singleline(&mut self.cpu_threads);
-            });
-
-            ui.horizontal(|ui| {
-                ui.label("Memory Limit:");
-                ui.text_edit_singleline(&mut self.mem);
-            });
-
-            ui.horizontal(|ui| {
-                ui.label("GPU Selection:");
-                ComboBox::from_id_source("gpu_combo")
-                    .width(450.0)
-                    .selected_text(&self.selected_gpu)
-                    .show_ui(ui, |ui| {
-                        for option in &self.gpu_options {
-                            ui.selectable_value(&mut self.selected_gpu, option.clone(), option);
-                        }
-                    });
-            });
-
-            ui.horizontal(|ui| {
-                ui.checkbox(&mut self.show_gpu_info, "Show detailed GPU info in console on start");
-            });
-
-            ui.add_space(10.0);
-
-            if self.is_plotting {
-                ui.horizontal(|ui| {
-                    ui.label("Status:");
-                    ui.label(RichText::new(&self.current_status).strong());
-                });
-                ui.add(egui::ProgressBar::new(self.current_progress as f32 / 100.0).text("Plotting..."));
-                if ui.button("Cancel").clicked() {
-                    self.is_plotting = false;
-                }
-            } else if ui.button("Start Plotting").clicked() {
-                self.start_plotting(ctx);
-            }
-
-            ui.add_space(10.0);
-            ui.separator();
-            ui.label("Log:");
-            ScrollArea::vertical()
-                .max_height(200.0)
-                .show(ui, |ui| {
-                    ui.add(
-                        TextEdit::multiline(&mut self.log.as_str())
-                            .desired_rows(10)
-                            .interactive(false),
-                    );
-                });
-        });
-    }
-}
-
-impl PlotterGui {
-    fn start_plotting(&mut self, ctx: &egui::Context) {
-        let numeric_id: u64 = match self.numeric_id.trim().parse() {
-            Ok(v) => v,
-            Err(_) => {
-                self.log += "Invalid Account ID\n";
-                return;
-            }
-        };
-        let mut current_nonce: u64 = self.start_nonce.trim().parse().unwrap_or(0);
-        let total_nonces: u64 = match self.total_nonces.trim().parse() {
-            Ok(v) => v,
-            Err(_) => {
-                self.log += "Invalid Total Nonces\n";
-                return;
-            }
-        };
-        let plot_size_nonces: u64 = self.plot_size_nonces.trim().parse().unwrap_or(4_194_304);
-        let temp_dir = if self.temp_dir.trim().is_empty() {
-            None
-        } else {
-            Some(PathBuf::from(self.temp_dir.trim()))
-        };
-        let final_dirs: Vec<PathBuf> = self
-            .drives
-            .split(',')
-            .map(|s| PathBuf::from(s.trim()))
-            .filter(|p| !p.as_os_str().is_empty())
-            .collect();
-        if final_dirs.is_empty() {
-            self.log += "No final drives specified\n";
-            return;
-        }
-
-        let cpu_threads: u8 = self.cpu_threads.trim().parse().unwrap_or(0);
-        let mem = if self.mem.trim().is_empty() {
-            "0B".to_string()
-        } else {
-            self.mem.trim().to_string()
-        };
-
-        let gpus: Option<Vec<String>> = if self.selected_gpu == "CPU Only" {
-            self.log += "Using CPU only\n\n";
-            None
-        } else if self.selected_gpu == "All GPUs" {
-            self.log += "Using all available GPUs\n\n";
-            Some(vec![])
-        } else if self.selected_gpu.starts_with("Device ") {
-            let id_str = self.selected_gpu
-                .split(": ")
-                .next()
-                .unwrap()
-                .split_whitespace()
-                .nth(1)
-                .unwrap();
-            self.log += &format!("Using GPU: {}\n\n", self.selected_gpu);
-            Some(vec![id_str.to_string()])
-        } else {
-            self.log += "No valid GPU selected — using CPU only\n\n";
-            None
-        };
-
-        self.is_plotting = true;
-        self.current_progress = 0.0;
-        self.current_status = "Starting...".to_string();
-        self.log += "Plotting started\n";
-
-        if self.show_gpu_info && gpus.is_some() {
-            self.log += "--- Detailed OpenCL Info ---\n";
-            self.log += "(Full list printed to console)\n";
-            self.log += "---------------------------\n\n";
-            platform_info();
-        }
-
-        let app_state = Arc::new(Mutex::new(self.clone()));
-        let ctx = ctx.clone();
-
-        thread::spawn(move || {
-            let mut remaining = total_nonces;
-            let mut drive_index = 0;
-
-            while remaining > 0 && app_state.lock().unwrap().is_plotting {
-                let this_plot_nonces = remaining.min(plot_size_nonces);
-                let final_dir = &final_dirs[drive_index];
-                let scoops = 4096;
-                let filename = format!(
-                    "{}_{}_{}_{}",
-                    numeric_id, current_nonce, this_plot_nonces, scoops
-                );
-
-                let write_dir = temp_dir.as_ref().unwrap_or(final_dir);
-                let output_path_str = write_dir.to_str().unwrap().to_string();
-
-                {
-                    let mut state = app_state.lock().unwrap();
-                    state.current_status = format!(
-                        "Plotting {} ({}/{})",
-                        filename,
-                        total_nonces - remaining + this_plot_nonces,
-                        total_nonces
-                    );
-                    state.log += &format!("Starting {} nonces → {}\n", this_plot_nonces, filename);
-                }
-                ctx.request_repaint();
-
-                let plotter = Plotter::new();
-                plotter.run(PlotterTask {
-                    numeric_id,
-                    start_nonce: current_nonce,
-                    nonces: this_plot_nonces,
-                    output_path: output_path_str.clone(),
-                    mem: mem.clone(),
-                    cpu_threads,
-                    gpus: gpus.clone(),
-                    direct_io: true,
-                    async_io: true,
-                    quiet: true,
-                    benchmark: false,
-                    zcb: false,
-                });
-
-                // === Ultra-robust cross-device file move/copy with progress and diagnostics ===
-                if temp_dir.is_some() {
-                    let temp_file = write_dir.join(&filename);
-                    let final_file = final_dir.join(&filename);
-                    let _ = fs::create_dir_all(final_dir);
-
-                    let mut state = app_state.lock().unwrap();
-
-                    state.log += &format!("=== FINALIZING PLOT {} ===\n", filename);
-                    state.log += &format!("Temp path: {}\n", temp_file.display());
-                    state.log += &format!("Final path: {}\n", final_file.display());
-
-                    if !temp_file.exists() {
-                        state.log += "ERROR: Temp file does not exist! Skipping move.\n";
-                        ctx.request_repaint();
-                    } else if temp_file.metadata().map(|m| m.len()).unwrap_or(0) == 0 {
-                        state.log += "ERROR: Temp file is empty (0 bytes)!\n";
-                        ctx.request_repaint();
-                    } else {
-                        state.log += &format!("Temp file size: {} GiB — ready for move/copy\n", temp_file.metadata().unwrap().len() / 1024 / 1024 / 1024);
-                        ctx.request_repaint();
-
-                        match fs::rename(&temp_file, &final_file) {
-                            Ok(_) => {
-                                state.log += "✓ Successfully moved (same drive — instant)\n";
-                            }
-                            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
-                                state.log += "Cross-device move detected — starting copy...\n";
-                                ctx.request_repaint();
-
-                                match Self::robust_copy(&temp_file, &final_file, &app_state, &ctx) {
-                                    Ok(copied) => {
-                                        state.log += &format!("✓ Successfully copied ({} GiB)\n", copied / 1024 / 1024 / 1024);
-                                        if let Err(del_err) = fs::remove_file(&temp_file) {
-                                            state.log += &format!("Warning: Failed to delete temp file: {}\n", del_err);
-                                        } else {
-                                            state.log += "✓ Temp file cleaned up\n";
-                                        }
-                                    }
-                                    Err(copy_err) => {
-                                        state.log += &format!("✗ COPY FAILED: {}\n", copy_err);
-                                        state.log += "Possible causes:\n";
-                                        state.log += "- Antivirus/Windows Defender blocking large file copy\n";
-                                        state.log += "- Insufficient permissions on final drive\n";
-                                        state.log += "- Final drive is full or read-only\n";
-                                        state.log += "- File is locked by another process\n";
-                                        state.log += "Plot remains in temp directory for safety.\n";
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                state.log += &format!("✗ Move failed: {}\n", e);
-                            }
-                        }
-                    }
-
-                    state.log += "=== END FINALIZE ===\n\n";
-                    ctx.request_repaint();
-                }
-
-                {
-                    let mut state = app_state.lock().unwrap();
-                    state.log += &format!("Completed {}\n", filename);
-                    state.current_progress =
-                        ((total_nonces - remaining + this_plot_nonces) as f64 / total_nonces as f64) * 100.0;
-                }
-                ctx.request_repaint();
-
-                remaining -= this_plot_nonces;
-                current_nonce += this_plot_nonces;
-                drive_index = (drive_index + 1) % final_dirs.len();
-            }
-
-            let mut state = app_state.lock().unwrap();
-            state.is_plotting = false;
-            state.current_status = "Finished!".to_string();
-            state.log += "All plotting complete!\n";
-            ctx.request_repaint();
-        });
-    }
-
-    // Helper function for robust copy with progress feedback
-    fn robust_copy(
-        src: &PathBuf,
-        dst: &PathBuf,
-        app_state: &Arc<Mutex<Self>>,
-        ctx: &egui::Context,
-    ) -> io::Result<u64> {
-        let metadata = fs::metadata(src)?;
-        let total_size = metadata.len();
-        let mut copied: u64 = 0;
-
-        let mut reader = fs::File::open(src)?;
-        let mut writer = fs::File::create(dst)?;
-
-        let mut buffer = vec![0u8; 8 * 1024 * 1024]; // 8 MiB buffer for speed
-
-        loop {
-            let bytes_read = reader.read(&mut buffer)?;
-            if bytes_read == 0 {
-                break;
-            }
-
-            writer.write_all(&buffer[..bytes_read])?;
-            copied += bytes_read as u64;
-
-            // Update log every 500 MiB or at end
-            if copied % (500 * 1024 * 1024) < bytes_read as u64 || copied == total_size {
-                let mut state = app_state.lock().unwrap();
-                let percent = if total_size > 0 {
-                    (copied as f64 / total_size as f64) * 100.0
-                } else {
-                    100.0
-                };
-                state.log += &format!(
-                    "Copy progress: {:.1}% ({} / {} GiB)\n",
-                    percent,
-                    copied / 1024 / 1024 / 1024,
-                    total_size / 1024 / 1024 / 1024
-                );
-                ctx.request_repaint();
-            }
-        }
-
-        writer.flush()?;
-        Ok(copied)
-    }
-}
-
-fn main() -> eframe::Result {
-    let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size(Vec2::new(800.0, 600.0)),
-        ..Default::default()
-    };
-
-    eframe::run_native(
-        "Signum Plotter GUI",
-        options,
-        Box::new(|cc| {
-            let mut app: PlotterGui = cc
-                .storage
-                .and_then(|s| eframe::get_value(s, eframe::APP_KEY))
-                .unwrap_or_default();
-
-            if app.plot_size_nonces.is_empty() {
-                app.plot_size_nonces = "4194304".to_string();
-            }
-
-            if app.gpu_options.is_empty() {
-                app.gpu_options.push("CPU Only".to_string());
-                app.gpu_options.push("All GPUs".to_string());
-
-                let platforms = match get_platform_ids() {
-                    Ok(p) => p,
-                    Err(_) => vec![],
-                };
-
-                let mut global_id = 0;
-                for platform in platforms {
-                    let devices = match get_device_ids(platform, Some(DeviceType::GPU), None) {
-                        Ok(d) => d,
-                        Err(_) => continue,
-                    };
-
-                    for device in devices {
-                        let name = get_device_info(device, DeviceInfo::Name)
-                            .ok()
-                            .map(|v| v.to_string())
-                            .unwrap_or_else(|| "Unknown Device".to_string());
-
-                        let vendor = get_device_info(device, DeviceInfo::Vendor)
-                            .ok()
-                            .map(|v| v.to_string())
-                            .unwrap_or_else(|| "Unknown Vendor".to_string());
-
-                        let label = format!("Device {}: {} ({})", global_id, name.trim(), vendor.trim());
-                        app.gpu_options.push(label);
-                        global_id += 1;
-                    }
-                }
-
-                if app.gpu_options.len() == 2 {
-                    app.gpu_options[1] = "All GPUs (none detected)".to_string();
-                }
-            }
-
-            if app.selected_gpu.is_empty() {
-                if app.gpu_options.len() > 2 {
-                    app.selected_gpu = "All GPUs".to_string();
-                } else {
-                    app.selected_gpu = "CPU Only".to_string();
-                }
-            }
-
-            Ok(Box::new(app))
-        }),
-    )
+use eframe::egui;
+use egui::{ComboBox, RichText, ScrollArea, TextEdit, Vec2};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// Module declarations
+mod autotune;
+mod buffer;
+mod cgroup;
+mod cpu_hasher;
+mod poc_hashing;
+mod pressure;
+mod queue;
+mod report;
+mod gpu_check;
+#[cfg(feature = "opencl")]
+mod gpu_hasher;
+#[cfg(feature = "opencl")]
+mod gpu_memory;
+mod gpu_selector;
+#[cfg(feature = "opencl")]
+mod kernel_cache;
+mod kernel_tuning;
+mod lanes;
+mod mover;
+mod numa;
+#[cfg(feature = "opencl")]
+mod ocl;
+mod scheduler;
+mod shabal256;
+mod topology;
+mod utils;
+#[cfg(test)]
+mod test_utils;
+mod write_backend;
+mod writer;
+
+mod plotter;
+use autotune::{compare_kernels, measure_task, run_trial, search, SearchSpace, TunedConfig, DEFAULT_TRIAL_NONCES};
+use mover::{move_plot, MoveMethod};
+use buffer::HugePages;
+use cpu_hasher::SimdExtension;
+use gpu_check::GpuCheck;
+use kernel_tuning::{parse_settings, KernelSetting};
+use plotter::{Plotter, PlotterTask};
+use topology::PinningPolicy;
+use write_backend::WriteBackendKind;
+use gpu_selector::{gpu_compute_units, select_gpus};
+#[cfg(feature = "opencl")]
+use ocl::platform_info;
+
+#[derive(serde::Deserialize, serde::Serialize, Default, Clone)]
+struct PlotterGui {
+    numeric_id: String,
+    start_nonce: String,
+    total_nonces: String,
+    plot_size_nonces: String,
+    temp_dir: String,
+    drives: String,
+    cpu_threads: String,
+    mem: String,
+    selected_gpu: String,
+    show_gpu_info: bool,
+    host_shuffle: bool,
+    gpu_check: String,
+    kernel: String,
+    report_dir: String,
+    transpose_via_temp: bool,
+    write_backend: String,
+    buffers: String,
+    huge_pages: String,
+    lock_memory: bool,
+    pinning: String,
+    adaptive_memory: bool,
+    simd: String,
+
+    #[serde(skip)]
+    gpu_options: Vec<String>,
+    #[serde(skip)]
+    log: String,
+    #[serde(skip)]
+    current_progress: f64,
+    #[serde(skip)]
+    current_status: String,
+    #[serde(skip)]
+    is_plotting: bool,
+    #[serde(skip)]
+    tuned: Option<TunedConfig>,
+    #[serde(skip)]
+    tuning: Arc<Mutex<TuneState>>,
+}
+
+// shared with the autotune thread, picked up by the next frame
+#[derive(Default)]
+struct TuneState {
+    running: bool,
+    log: String,
+    result: Option<TunedConfig>,
+}
+
+const TUNED_GPUS: &str = "Autotuned: ";
+// the cpu threads field for hashing on the gpus alone
+const NO_CPU_THREADS: &str = "none";
+
+// autotune's 0 threads leave the cpu out, the field's 0 means all cores
+fn tuned_threads(tuned: &TunedConfig) -> String {
+    if tuned.cpu_threads == 0 {
+        NO_CPU_THREADS.to_string()
+    } else {
+        tuned.cpu_threads.to_string()
+    }
+}
+
+impl eframe::App for PlotterGui {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, self);
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let (tuning, tuned) = {
+            let mut state = self.tuning.lock().unwrap();
+            self.log += &std::mem::take(&mut state.log);
+            (state.running, state.result.take())
+        };
+        if let Some(tuned) = tuned {
+            self.apply_tuned(tuned);
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Signum Plotter GUI");
+
+            ui.horizontal(|ui| {
+                ui.label("Account ID:");
+                ui.text_edit_singleline(&mut self.numeric_id);
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Start Nonce:");
+                ui.text_edit_singleline(&mut self.start_nonce);
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Total Nonces:");
+                ui.text_edit_singleline(&mut self.total_nonces);
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Plot Size (nonces):");
+                ui.text_edit_singleline(&mut self.plot_size_nonces);
+                ui.label("(default ~4_194_304 ≈ 1 TiB)");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("SSD Temp Dir:");
+                ui.text_edit_singleline(&mut self.temp_dir);
+            });
+
+            ui.horizontal(|ui| {
+                ui.checkbox(
+                    &mut self.transpose_via_temp,
+                    "Plot straight to the final drives, using the temp dir for sequential transposition",
+                );
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Final Drives (comma-separated):");
+                ui.text_edit_singleline(&mut self.drives);
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Writer:");
+                ComboBox::from_id_source("writer_combo")
+                    .selected_text(if self.write_backend.is_empty() { "sync" } else { &self.write_backend })
+                    .show_ui(ui, |ui| {
+                        for option in ["sync", "pwrite", "io_uring"] {
+                            ui.selectable_value(&mut self.write_backend, option.to_string(), option);
+                        }
+                    });
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("CPU Threads:");
+                ui.text_edit_singleline(&mut self.cpu_threads);
+                ui.label("(empty = all cores, none = GPUs only)");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("SIMD:");
+                ComboBox::from_id_source("simd_combo")
+                    .selected_text(if self.simd.is_empty() { "auto" } else { &self.simd })
+                    .show_ui(ui, |ui| {
+                        for option in ["auto", "AVX512f", "AVX2", "AVX", "SSE2", "NEON", "rust"] {
+                            ui.selectable_value(&mut self.simd, option.to_string(), option);
+                        }
+                    });
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("CPU Pinning:");
+                ui.text_edit_singleline(&mut self.pinning);
+                ui.label("(physical, smt, none or a cpu list like 2-7,10)");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Memory Limit:");
+                ui.text_edit_singleline(&mut self.mem);
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Buffers per Drive:");
+                ui.text_edit_singleline(&mut self.buffers);
+                ui.label("(empty = auto)");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Memory Pages:");
+                ComboBox::from_id_source("pages_combo")
+                    .selected_text(if self.huge_pages.is_empty() { "normal" } else { &self.huge_pages })
+                    .show_ui(ui, |ui| {
+                        for option in ["normal", "transparent", "2MiB", "1GiB"] {
+                            ui.selectable_value(&mut self.huge_pages, option.to_string(), option);
+                        }
+                    });
+                ui.checkbox(&mut self.lock_memory, "Lock buffers in RAM");
+                ui.checkbox(&mut self.adaptive_memory, "Shrink buffers under memory pressure");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("GPU Selection:");
+                ComboBox::from_id_source("gpu_combo")
+                    .width(450.0)
+                    .selected_text(&self.selected_gpu)
+                    .show_ui(ui, |ui| {
+                        for option in &self.gpu_options {
+                            ui.selectable_value(&mut self.selected_gpu, option.clone(), option);
+                        }
+                    });
+            });
+
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.show_gpu_info, "Show detailed GPU info in console on start");
+                ui.checkbox(&mut self.host_shuffle, "Unpack GPU output on the CPU");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("GPU Check:");
+                ui.text_edit_singleline(&mut self.gpu_check);
+                ui.label("(share of nonces re-hashed on the CPU, e.g. 0.001:quarantine, empty = off)");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Kernel:");
+                ui.text_edit_singleline(&mut self.kernel);
+                ui.label("(e.g. options=-cl-fast-relaxed-math;hashes=64 | gpu=0:1;workgroup=128, empty = built-in)");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Report Folder:");
+                ui.text_edit_singleline(&mut self.report_dir);
+                ui.label("(a JSON report per batch of plots, empty = none)");
+            });
+
+            ui.add_space(10.0);
+
+            if self.is_plotting {
+                ui.horizontal(|ui| {
+                    ui.label("Status:");
+                    ui.label(RichText::new(&self.current_status).strong());
+                });
+                ui.add(egui::ProgressBar::new(self.current_progress as f32 / 100.0).text("Plotting..."));
+                if ui.button("Cancel").clicked() {
+                    self.is_plotting = false;
+                }
+            } else if tuning {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Autotuning, running benchmark trials...");
+                });
+            } else {
+                ui.horizontal(|ui| {
+                    if ui.button("Start Plotting").clicked() {
+                        self.start_plotting(ctx);
+                    }
+                    if ui
+                        .button("Autotune")
+                        .on_hover_text("Benchmark threads, memory, GPU cores and i/o settings, keep the fastest")
+                        .clicked()
+                    {
+                        self.start_autotune(ctx);
+                    }
+                });
+            }
+
+            ui.add_space(10.0);
+            ui.separator();
+            ui.label("Log:");
+            ScrollArea::vertical()
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    ui.add(
+                        TextEdit::multiline(&mut self.log.as_str())
+                            .desired_rows(10)
+                            .interactive(false),
+                    );
+                });
+        });
+    }
+}
+
+impl PlotterGui {
+    fn start_plotting(&mut self, ctx: &egui::Context) {
+        let numeric_id: u64 = match self.numeric_id.trim().parse() {
+            Ok(v) => v,
+            Err(_) => {
+                self.log += "Invalid Account ID\n";
+                return;
+            }
+        };
+        let mut current_nonce: u64 = self.start_nonce.trim().parse().unwrap_or(0);
+        let total_nonces: u64 = match self.total_nonces.trim().parse() {
+            Ok(v) => v,
+            Err(_) => {
+                self.log += "Invalid Total Nonces\n";
+                return;
+            }
+        };
+        let plot_size_nonces: u64 = self.plot_size_nonces.trim().parse().unwrap_or(4_194_304);
+        let temp_dir = if self.temp_dir.trim().is_empty() {
+            None
+        } else {
+            Some(PathBuf::from(self.temp_dir.trim()))
+        };
+        // in transposition mode plots are written in place, the temp dir only stages hashes
+        let (temp_dir, transpose_dir) = if self.transpose_via_temp {
+            (None, temp_dir)
+        } else {
+            (temp_dir, None)
+        };
+        // every batch writes <first plot file>.json there
+        let report_dir = if self.report_dir.trim().is_empty() {
+            None
+        } else {
+            Some(PathBuf::from(self.report_dir.trim()))
+        };
+        if let Some(dir) = &report_dir {
+            if let Err(e) = fs::create_dir_all(dir) {
+                self.log += &format!("Invalid report folder {}: {}\n", dir.display(), e);
+                return;
+            }
+        }
+        let final_dirs: Vec<PathBuf> = self
+            .drives
+            .split(',')
+            .map(|s| PathBuf::from(s.trim()))
+            .filter(|p| !p.as_os_str().is_empty())
+            .collect();
+        if final_dirs.is_empty() {
+            self.log += "No final drives specified\n";
+            return;
+        }
+
+        let gpu_only = self.cpu_threads.trim().eq_ignore_ascii_case(NO_CPU_THREADS);
+        let cpu_threads: u32 = self.cpu_threads.trim().parse().unwrap_or(0);
+        let pinning: PinningPolicy = match self.pinning.parse() {
+            Ok(x) => x,
+            Err(e) => {
+                self.log += &format!("Invalid CPU pinning: {}\n", e);
+                return;
+            }
+        };
+        let mem = if self.mem.trim().is_empty() {
+            "0B".to_string()
+        } else {
+            self.mem.trim().to_string()
+        };
+
+        let buffers: u64 = self.buffers.trim().parse().unwrap_or(0);
+        let huge_pages: HugePages = self.huge_pages.parse().unwrap_or_default();
+        let lock_memory = self.lock_memory;
+        // i/o settings the autotuner found best, the plotter's defaults otherwise
+        let (zcb, async_io) = match &self.tuned {
+            Some(x) => (x.zcb, x.async_io),
+            None => (false, true),
+        };
+        let adaptive_memory = self.adaptive_memory;
+        let host_shuffle = self.host_shuffle;
+        let gpu_check: Option<GpuCheck> = match self.gpu_check.trim() {
+            "" => None,
+            x => match x.parse() {
+                Ok(x) => Some(x),
+                Err(e) => {
+                    self.log += &format!("Invalid GPU check: {}\n", e);
+                    return;
+                }
+            },
+        };
+        let kernel: Vec<KernelSetting> = match parse_settings(&self.kernel) {
+            Ok(x) => x,
+            Err(e) => {
+                self.log += &format!("Invalid kernel settings: {}\n", e);
+                return;
+            }
+        };
+        let simd: Option<SimdExtension> = match self.simd.trim() {
+            "" | "auto" => None,
+            x => match x.parse() {
+                Ok(x) => Some(x),
+                Err(e) => {
+                    self.log += &format!("Invalid SIMD extension: {}\n", e);
+                    return;
+                }
+            },
+        };
+        let write_backend: WriteBackendKind = if self.write_backend.trim().is_empty() {
+            WriteBackendKind::Sync
+        } else {
+            match self.write_backend.parse() {
+                Ok(x) => x,
+                Err(e) => {
+                    self.log += &format!("Invalid writer: {}\n", e);
+                    return;
+                }
+            }
+        };
+
+        // resolved here for the log, the plotter resolves the same way
+        let gpus = self.gpu_selection();
+        match gpus.as_ref().map(|x| select_gpus(x)) {
+            None => self.log += "Using CPU only\n\n",
+            Some(Ok(selected)) => {
+                let names: Vec<String> = selected
+                    .iter()
+                    .map(|x| format!("{} [{}]", x.name, x.spec()))
+                    .collect();
+                self.log += &format!("Using GPUs: {}\n\n", names.join(", "));
+            }
+            Some(Err(e)) => {
+                self.log += &format!("Invalid GPU selection: {}\n", e);
+                return;
+            }
+        }
+
+        self.is_plotting = true;
+        self.current_progress = 0.0;
+        self.current_status = "Starting...".to_string();
+        self.log += "Plotting started\n";
+
+        if self.show_gpu_info && gpus.is_some() {
+            self.log += "--- Detailed OpenCL Info ---\n";
+            self.log += "(Full list printed to console)\n";
+            self.log += "---------------------------\n\n";
+            #[cfg(feature = "opencl")]
+            platform_info();
+        }
+
+        let app_state = Arc::new(Mutex::new(self.clone()));
+        let ctx = ctx.clone();
+
+        thread::spawn(move || {
+            let mut remaining = total_nonces;
+            let mut drive_index = 0;
+
+            while remaining > 0 && app_state.lock().unwrap().is_plotting {
+                // without a temp dir every drive gets its own file, all fed by one hashing pipeline
+                let batch_size = if temp_dir.is_none() { final_dirs.len() } else { 1 };
+                let mut batch = Vec::new();
+                let mut batch_nonces = 0;
+                while batch.len() < batch_size && batch_nonces < remaining {
+                    let this_plot_nonces = (remaining - batch_nonces).min(plot_size_nonces);
+                    let start_nonce = current_nonce + batch_nonces;
+                    let scoops = 4096;
+                    let filename = format!(
+                        "{}_{}_{}_{}",
+                        numeric_id, start_nonce, this_plot_nonces, scoops
+                    );
+                    batch.push((filename, final_dirs[drive_index].clone(), start_nonce, this_plot_nonces));
+                    batch_nonces += this_plot_nonces;
+                    drive_index = (drive_index + 1) % final_dirs.len();
+                }
+
+                {
+                    let mut state = app_state.lock().unwrap();
+                    state.current_status = format!(
+                        "Plotting {} ({}/{})",
+                        batch.iter().map(|x| x.0.as_str()).collect::<Vec<_>>().join(", "),
+                        total_nonces - remaining + batch_nonces,
+                        total_nonces
+                    );
+                    for (filename, _, _, this_plot_nonces) in &batch {
+                        state.log += &format!("Starting {} nonces → {}\n", this_plot_nonces, filename);
+                    }
+                }
+                ctx.request_repaint();
+
+                let report_path = report_dir
+                    .as_ref()
+                    .map(|x| x.join(format!("{}.json", batch[0].0)).to_str().unwrap().to_string());
+                let tasks = batch
+                    .iter()
+                    .map(|(_, final_dir, start_nonce, this_plot_nonces)| {
+                        let write_dir = temp_dir.as_ref().unwrap_or(final_dir);
+                        PlotterTask {
+                            numeric_id,
+                            start_nonce: *start_nonce,
+                            nonces: *this_plot_nonces,
+                            output_path: write_dir.to_str().unwrap().to_string(),
+                            mem: mem.clone(),
+                            cpu_threads,
+                            gpu_only,
+                            gpus: gpus.clone(),
+                            direct_io: true,
+                            async_io,
+                            quiet: true,
+                            benchmark: false,
+                            zcb,
+                            host_shuffle,
+                            gpu_check,
+                            kernel: kernel.clone(),
+                            transpose_path: transpose_dir
+                                .as_ref()
+                                .map(|x| x.to_str().unwrap().to_string()),
+                            write_backend,
+                            buffers,
+                            huge_pages,
+                            lock_memory,
+                            pinning: pinning.clone(),
+                            adaptive_memory,
+                            report_path: report_path.clone(),
+                            simd: simd.clone(),
+                        }
+                    })
+                    .collect();
+
+                // a failed batch stops the rest, its files would fail the same way
+                let plotter = Plotter::new();
+                match plotter.run(tasks) {
+                    Ok(stats) => {
+                        for line in stats.summary {
+                            Self::log(&app_state, &ctx, format!("{}\n", line));
+                        }
+                        if let Some(path) = &report_path {
+                            Self::log(&app_state, &ctx, format!("Report: {}\n", path));
+                        }
+                    }
+                    Err(e) => {
+                        let mut state = app_state.lock().unwrap();
+                        state.log += &format!("✗ PLOTTING FAILED: {}\n", e);
+                        state.is_plotting = false;
+                        state.current_status = "Failed".to_string();
+                        ctx.request_repaint();
+                        return;
+                    }
+                }
+
+                let mut done_nonces = total_nonces - remaining;
+                for (filename, final_dir, _, this_plot_nonces) in &batch {
+                    let write_dir = temp_dir.as_ref().unwrap_or(final_dir);
+
+                    // === Ultra-robust cross-device file move/copy with progress and diagnostics ===
+                    if temp_dir.is_some() {
+                        let temp_file = write_dir.join(filename);
+                        let final_file = final_dir.join(filename);
+                        let _ = fs::create_dir_all(final_dir);
+
+                        Self::log(&app_state, &ctx, format!("=== FINALIZING PLOT {} ===\n", filename));
+                        Self::log(&app_state, &ctx, format!("Temp path: {}\n", temp_file.display()));
+                        Self::log(&app_state, &ctx, format!("Final path: {}\n", final_file.display()));
+
+                        let temp_size = temp_file.metadata().map(|m| m.len()).unwrap_or(0);
+                        if !temp_file.exists() {
+                            Self::log(&app_state, &ctx, "ERROR: Temp file does not exist! Skipping move.\n".to_string());
+                        } else if temp_size == 0 {
+                            Self::log(&app_state, &ctx, "ERROR: Temp file is empty (0 bytes)!\n".to_string());
+                        } else {
+                            Self::log(&app_state, &ctx, format!("Temp file size: {} GiB — ready for move/copy\n", temp_size / 1024 / 1024 / 1024));
+
+                            // the app state is only locked per progress line, never for the whole copy
+                            let mut last_logged = 0u64;
+                            let result = move_plot(&temp_file, &final_file, |copied, total| {
+                                // Update log every 500 MiB or at end
+                                if copied - last_logged >= 500 * 1024 * 1024 || copied == total {
+                                    last_logged = copied;
+                                    let percent = if total > 0 {
+                                        (copied as f64 / total as f64) * 100.0
+                                    } else {
+                                        100.0
+                                    };
+                                    Self::log(
+                                        &app_state,
+                                        &ctx,
+                                        format!(
+                                            "Copy progress: {:.1}% ({} / {} GiB)\n",
+                                            percent,
+                                            copied / 1024 / 1024 / 1024,
+                                            total / 1024 / 1024 / 1024
+                                        ),
+                                    );
+                                }
+                            });
+
+                            match result {
+                                Ok(moved) if moved.method == MoveMethod::Rename => {
+                                    Self::log(&app_state, &ctx, "✓ Successfully moved (same drive — instant)\n".to_string());
+                                }
+                                Ok(moved) => {
+                                    let mut msg = String::new();
+                                    if moved.resumed_at > 0 {
+                                        msg += &format!("Resumed interrupted copy at {} GiB\n", moved.resumed_at / 1024 / 1024 / 1024);
+                                    }
+                                    msg += &format!(
+                                        "✓ Successfully copied and verified ({} GiB, {:?})\n",
+                                        moved.bytes / 1024 / 1024 / 1024,
+                                        moved.method
+                                    );
+                                    msg += "✓ Temp file cleaned up\n";
+                                    Self::log(&app_state, &ctx, msg);
+                                }
+                                Err(copy_err) => {
+                                    let mut msg = format!("✗ MOVE FAILED: {}\n", copy_err);
+                                    msg += "Possible causes:\n";
+                                    msg += "- Antivirus/Windows Defender blocking large file copy\n";
+                                    msg += "- Insufficient permissions on final drive\n";
+                                    msg += "- Final drive is full or read-only\n";
+                                    msg += "- File is locked by another process\n";
+                                    msg += "Plot remains in temp directory for safety, an interrupted copy resumes on retry.\n";
+                                    Self::log(&app_state, &ctx, msg);
+                                }
+                            }
+                        }
+
+                        Self::log(&app_state, &ctx, "=== END FINALIZE ===\n\n".to_string());
+                    }
+
+                    done_nonces += this_plot_nonces;
+                    {
+                        let mut state = app_state.lock().unwrap();
+                        state.log += &format!("Completed {}\n", filename);
+                        state.current_progress = (done_nonces as f64 / total_nonces as f64) * 100.0;
+                    }
+                    ctx.request_repaint();
+                }
+
+                remaining -= batch_nonces;
+                current_nonce += batch_nonces;
+            }
+
+            let mut state = app_state.lock().unwrap();
+            state.is_plotting = false;
+            state.current_status = "Finished!".to_string();
+            state.log += "All plotting complete!\n";
+            ctx.request_repaint();
+        });
+    }
+
+    // Benchmarks the current GPU selection in the background, trial results go
+    // to the log and the winner is saved and applied by update()
+    fn start_autotune(&mut self, ctx: &egui::Context) {
+        let gpus = match self.gpu_selection().map(|x| gpu_compute_units(&x)).transpose() {
+            Ok(x) => x,
+            Err(e) => {
+                self.log += &format!("Invalid GPU selection: {}\n", e);
+                return;
+            }
+        };
+        let output_path = std::env::temp_dir().to_str().unwrap().to_string();
+
+        self.tuning.lock().unwrap().running = true;
+        self.log += "Autotune started\n";
+        let tuning = self.tuning.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let tuned = autotune(gpus, &output_path, DEFAULT_TRIAL_NONCES, |msg| {
+                tuning.lock().unwrap().log += &msg;
+                ctx.request_repaint();
+            });
+            let mut state = tuning.lock().unwrap();
+            state.running = false;
+            state.result = tuned;
+            ctx.request_repaint();
+        });
+    }
+
+    // the gpu selectors behind the combo box entry, None is CPU only
+    fn gpu_selection(&self) -> Option<Vec<String>> {
+        if self.selected_gpu.starts_with(TUNED_GPUS) {
+            self.tuned.as_ref().and_then(|x| x.gpus.clone())
+        } else if self.selected_gpu.starts_with("All GPUs") {
+            Some(vec!["all".to_string()])
+        } else if let Some(device) = self.selected_gpu.strip_prefix("Device ") {
+            // "Device <index>: <name> (<vendor>)"
+            device.split(':').next().map(|x| vec![x.to_string()])
+        } else {
+            None
+        }
+    }
+
+    // makes tuned settings the defaults of the form
+    fn apply_tuned(&mut self, tuned: TunedConfig) {
+        self.cpu_threads = tuned_threads(&tuned);
+        self.mem = tuned.mem.clone();
+        self.gpu_options.retain(|x| !x.starts_with(TUNED_GPUS));
+        match &tuned.gpus {
+            Some(gpus) => {
+                let label = format!("{}{}", TUNED_GPUS, gpus.join(", "));
+                self.gpu_options.push(label.clone());
+                self.selected_gpu = label;
+            }
+            None => self.selected_gpu = "CPU Only".to_string(),
+        }
+        self.tuned = Some(tuned);
+    }
+
+    // Appends to the log, holding the app state lock only for the append
+    fn log(app_state: &Arc<Mutex<Self>>, ctx: &egui::Context, msg: String) {
+        app_state.lock().unwrap().log += &msg;
+        ctx.request_repaint();
+    }
+}
+
+// Runs the trials for the given gpus and saves the winner for this machine,
+// progress goes to log
+fn autotune(
+    gpus: Option<Vec<(String, usize)>>,
+    output_path: &str,
+    trial_nonces: u64,
+    mut log: impl FnMut(String),
+) -> Option<TunedConfig> {
+    let limits = cgroup::CgroupLimits::detect();
+    let host_cores = sys_info::cpu_num().unwrap_or(1);
+    let cores = limits.cpus().map_or(host_cores, |x| host_cores.min(x as u32));
+    let avail_mem = sys_info::mem_info()
+        .map(|x| limits.apply(x).avail * 1024)
+        .unwrap_or(0);
+    let space = SearchSpace::for_machine(cores, avail_mem, gpus);
+
+    let mut trial = 0;
+    let tuned = search(
+        &space,
+        |config| run_trial(config, output_path, trial_nonces),
+        |config| {
+            trial += 1;
+            log(format!(
+                "Trial {}: threads={} mem={} gpus={} zcb={} async_io={} → {:.0} nonces/m\n",
+                trial,
+                config.cpu_threads,
+                config.mem,
+                config.gpus.as_ref().map_or("none".to_string(), |x| x.join(",")),
+                config.zcb,
+                config.async_io,
+                config.nonces_per_minute
+            ));
+        },
+    );
+
+    match &tuned {
+        Some(config) => {
+            log(format!(
+                "Best: threads={} mem={} gpus={} zcb={} async_io={}, {:.0} nonces/m\n",
+                config.cpu_threads,
+                config.mem,
+                config.gpus.as_ref().map_or("none".to_string(), |x| x.join(",")),
+                config.zcb,
+                config.async_io,
+                config.nonces_per_minute
+            ));
+            match config.save() {
+                Ok(path) => log(format!("Saved to {}\n", path.display())),
+                Err(e) => log(format!("Couldn't save autotune results: {}\n", e)),
+            }
+        }
+        None => log("Autotune failed, no trial completed\n".to_string()),
+    }
+    tuned
+}
+
+fn main() -> eframe::Result {
+    let matches = clap::App::new("signum-plotter")
+        .version(env!("CARGO_PKG_VERSION"))
+        .subcommand(
+            clap::SubCommand::with_name("autotune")
+                .about("Benchmarks plotting settings and saves the fastest as defaults for this machine")
+                .arg(
+                    clap::Arg::with_name("gpu")
+                        .long("gpu")
+                        .value_name("gpu")
+                        .multiple(true)
+                        .help("GPUs to tune: platform:device, an index, part of the name or \"all\", CPU only if omitted"),
+                )
+                .arg(
+                    clap::Arg::with_name("nonces")
+                        .long("nonces")
+                        .value_name("nonces")
+                        .help("Nonces to hash per trial"),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("benchmark")
+                .about("Hashes without writing and prints a JSON report")
+                .arg(
+                    clap::Arg::with_name("gpu")
+                        .long("gpu")
+                        .value_name("gpu")
+                        .multiple(true)
+                        .help("GPUs to hash with: platform:device[:cores], an index, part of the name or \"all\", CPU only if omitted"),
+                )
+                .arg(
+                    clap::Arg::with_name("threads")
+                        .long("threads")
+                        .value_name("threads")
+                        .help("CPU threads, all cores if omitted or 0, \"none\" hashes on the GPUs only"),
+                )
+                .arg(
+                    clap::Arg::with_name("mem")
+                        .long("mem")
+                        .value_name("mem")
+                        .help("Memory for buffers, e.g. 2GiB"),
+                )
+                .arg(
+                    clap::Arg::with_name("nonces")
+                        .long("nonces")
+                        .value_name("nonces")
+                        .help("Nonces to hash"),
+                )
+                .arg(
+                    clap::Arg::with_name("simd")
+                        .long("simd")
+                        .value_name("extension")
+                        .possible_values(&["avx512f", "avx2", "avx", "sse2", "neon", "rust"])
+                        .help("Hashing backend, the best one the CPU supports if omitted"),
+                )
+                .arg(
+                    clap::Arg::with_name("host-shuffle")
+                        .long("host-shuffle")
+                        .help("Unpack GPU output on the CPU instead of shuffling it on the GPU"),
+                )
+                .arg(
+                    clap::Arg::with_name("gpu-check")
+                        .long("gpu-check")
+                        .value_name("fraction[:abort|quarantine]")
+                        .help("Share of every GPU task re-hashed on the CPU and compared, off if omitted"),
+                )
+                .arg(
+                    clap::Arg::with_name("kernel")
+                        .long("kernel")
+                        .value_name("settings")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("OpenCL kernel settings, e.g. \"gpu=0:1;file=kernel.cl;options=-cl-fast-relaxed-math;hashes=64;workgroup=128\""),
+                )
+                .arg(
+                    clap::Arg::with_name("kernel-variant")
+                        .long("kernel-variant")
+                        .value_name("settings")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Kernel settings to compare with the --kernel ones, one run each instead of the report"),
+                )
+                .arg(
+                    clap::Arg::with_name("report")
+                        .long("report")
+                        .value_name("file")
+                        .help("Where to write the report, stdout if omitted"),
+                ),
+        )
+        .get_matches();
+
+    if let Some(matches) = matches.subcommand_matches("benchmark") {
+        let gpus = matches.values_of("gpu").map(|x| x.map(String::from).collect());
+        let parse_kernel = |name: &str| {
+            matches
+                .values_of(name)
+                .map_or(Ok(Vec::new()), |x| x.map(|x| x.parse()).collect::<Result<Vec<KernelSetting>, String>>())
+        };
+        let (kernel, variants) = match (parse_kernel("kernel"), parse_kernel("kernel-variant")) {
+            (Ok(kernel), Ok(variants)) => (kernel, variants),
+            (Err(e), _) | (_, Err(e)) => {
+                println!("Error: {}", e);
+                return Ok(());
+            }
+        };
+        let task = PlotterTask {
+            numeric_id: 0,
+            start_nonce: 0,
+            nonces: matches
+                .value_of("nonces")
+                .and_then(|x| x.parse().ok())
+                .unwrap_or(DEFAULT_TRIAL_NONCES),
+            output_path: std::env::temp_dir().to_str().unwrap().to_string(),
+            mem: matches.value_of("mem").unwrap_or("0B").to_string(),
+            cpu_threads: matches
+                .value_of("threads")
+                .and_then(|x| x.parse().ok())
+                .unwrap_or(0),
+            // kernel variants are compared on the gpus alone
+            gpu_only: match matches.value_of("threads") {
+                Some(x) => x.eq_ignore_ascii_case(NO_CPU_THREADS),
+                None => !variants.is_empty(),
+            },
+            gpus,
+            direct_io: false,
+            async_io: true,
+            quiet: true,
+            benchmark: true,
+            zcb: false,
+            host_shuffle: matches.is_present("host-shuffle"),
+            gpu_check: match matches.value_of("gpu-check").map(|x| x.parse()).transpose() {
+                Ok(x) => x,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    return Ok(());
+                }
+            },
+            kernel,
+            transpose_path: None,
+            write_backend: WriteBackendKind::Sync,
+            buffers: 0,
+            huge_pages: HugePages::Normal,
+            lock_memory: false,
+            pinning: PinningPolicy::default(),
+            adaptive_memory: false,
+            report_path: Some(matches.value_of("report").unwrap_or("-").to_string()),
+            simd: matches.value_of("simd").and_then(|x| x.parse().ok()),
+        };
+        if variants.is_empty() {
+            if let Err(e) = Plotter::new().run(vec![task]) {
+                println!("Error: {}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        if task.gpus.is_none() {
+            println!("Error: kernel variants need --gpu");
+            return Ok(());
+        }
+        let task = PlotterTask {
+            report_path: None,
+            ..task
+        };
+        let trials = compare_kernels(&task, &variants, measure_task, |trial| {
+            println!(
+                "{}: {:.0} nonces/m",
+                trial.variant.as_ref().map_or("baseline".to_string(), |x| x.to_string()),
+                trial.nonces_per_minute
+            );
+        });
+        let baseline = trials[0].nonces_per_minute;
+        let best = trials
+            .iter()
+            .filter(|x| x.nonces_per_minute > 0.0)
+            .max_by(|a, b| a.nonces_per_minute.total_cmp(&b.nonces_per_minute));
+        match best.map(|x| (&x.variant, x.nonces_per_minute)) {
+            None => println!("No trial completed"),
+            Some((None, _)) => println!("Fastest: baseline"),
+            Some((Some(variant), _)) if baseline == 0.0 => println!("Fastest: {}", variant),
+            Some((Some(variant), speed)) => println!(
+                "Fastest: {}, {:+.1}% over the baseline",
+                variant,
+                (speed / baseline - 1.0) * 100.0
+            ),
+        }
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("autotune") {
+        let gpus: Option<Vec<String>> = matches.values_of("gpu").map(|x| x.map(String::from).collect());
+        let gpus = match gpus.map(|x| gpu_compute_units(&x)).transpose() {
+            Ok(x) => x,
+            Err(e) => {
+                println!("Error: {}", e);
+                return Ok(());
+            }
+        };
+        let trial_nonces = matches
+            .value_of("nonces")
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(DEFAULT_TRIAL_NONCES);
+        let output_path = std::env::temp_dir().to_str().unwrap().to_string();
+        autotune(gpus, &output_path, trial_nonces, |msg| print!("{}", msg));
+        return Ok(());
+    }
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size(Vec2::new(800.0, 600.0)),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "Signum Plotter GUI",
+        options,
+        Box::new(|cc| {
+            let mut app: PlotterGui = cc
+                .storage
+                .and_then(|s| eframe::get_value(s, eframe::APP_KEY))
+                .unwrap_or_default();
+
+            // settings found by autotune are the defaults where nothing was entered
+            app.tuned = TunedConfig::load();
+            if let Some(tuned) = &app.tuned {
+                if app.cpu_threads.is_empty() {
+                    app.cpu_threads = tuned_threads(tuned);
+                }
+                if app.mem.is_empty() {
+                    app.mem = tuned.mem.clone();
+                }
+            }
+
+            if app.plot_size_nonces.is_empty() {
+                app.plot_size_nonces = "4194304".to_string();
+            }
+
+            if app.gpu_options.is_empty() {
+                app.gpu_options.push("CPU Only".to_string());
+                app.gpu_options.push("All GPUs".to_string());
+
+                // numbered like the selectors' global indices, cpu runtimes
+                // such as PoCL are listed too but not part of "All GPUs"
+                let devices = gpu_selector::devices();
+                for (index, device) in devices.iter().enumerate() {
+                    let kind = if device.gpu { "" } else { " [CPU]" };
+                    let label = format!("Device {}: {} ({}){}", index, device.name, device.vendor, kind);
+                    app.gpu_options.push(label);
+                }
+
+                if !devices.iter().any(|x| x.gpu) {
+                    app.gpu_options[1] = "All GPUs (none detected)".to_string();
+                }
+
+                if let Some(gpus) = app.tuned.as_ref().and_then(|x| x.gpus.as_ref()) {
+                    app.gpu_options.push(format!("{}{}", TUNED_GPUS, gpus.join(", ")));
+                }
+            }
+
+            // a device saved by an earlier session may be gone
+            if !app.gpu_options.contains(&app.selected_gpu) {
+                if app.gpu_options[1] == "All GPUs" {
+                    app.selected_gpu = "All GPUs".to_string();
+                } else {
+                    app.selected_gpu = "CPU Only".to_string();
+                }
+            }
+
+            Ok(Box::new(app))
+        }),
+    )
 }
\ No newline at end of file
@@ -0,0 +1,497 @@
+use crate::buffer::PageAlignedByteBuffer;
+use crate::utils::{open, open_r, open_using_direct_io};
+use cfg_if::cfg_if;
+use fs2::FileExt;
+use std::cmp::min;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{self, Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+// copy granularity: progress, resume checkpoints and fsyncs happen per chunk
+#[cfg(not(test))]
+const CHUNK_SIZE: u64 = 64 * 1024 * 1024;
+// small enough for the tests to checkpoint every MiB
+#[cfg(test)]
+const CHUNK_SIZE: u64 = 64 * 1024;
+// persist the resume offset roughly every GiB
+const CHECKPOINT_INTERVAL: u64 = 16;
+// sampled verification: number of regions and bytes per region
+const VERIFY_SAMPLES: u64 = 32;
+const VERIFY_SAMPLE_SIZE: u64 = 1024 * 1024;
+const DIRECT_IO_ALIGNMENT: u64 = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveMethod {
+    Rename,
+    Reflink,
+    CopyFileRange,
+    Sendfile,
+    ReadWrite,
+}
+
+pub struct MoveResult {
+    pub method: MoveMethod,
+    pub bytes: u64,
+    pub resumed_at: u64,
+}
+
+// Moves a finished plot from the temp directory to its final location.
+//
+// Same filesystem: plain rename. Otherwise the data is copied into '<dst>.part',
+// which is preallocated, checkpointed to '<dst>.part.resume' so an interrupted
+// copy continues at its last offset, fsynced, verified against the source on
+// sampled regions and only then renamed into place. The source is deleted last.
+pub fn move_plot<F>(src: &Path, dst: &Path, progress: F) -> io::Result<MoveResult>
+where
+    F: FnMut(u64, u64),
+{
+    match fs::rename(src, dst) {
+        Ok(_) => {
+            let bytes = fs::metadata(dst)?.len();
+            return Ok(MoveResult {
+                method: MoveMethod::Rename,
+                bytes,
+                resumed_at: 0,
+            });
+        }
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => (),
+        Err(e) => return Err(e),
+    }
+    copy_plot(src, dst, progress)
+}
+
+fn copy_plot<F>(src: &Path, dst: &Path, mut progress: F) -> io::Result<MoveResult>
+where
+    F: FnMut(u64, u64),
+{
+    let total = fs::metadata(src)?.len();
+    let part = with_suffix(dst, ".part");
+    let resume = with_suffix(dst, ".part.resume");
+
+    let resumed_at = if part.exists() {
+        read_checkpoint(&resume, total).unwrap_or(0)
+    } else {
+        0
+    };
+
+    let mut method = MoveMethod::ReadWrite;
+    let mut copied = resumed_at;
+
+    {
+        let reader = open_r(src)?;
+        let writer = open(&part)?;
+        if resumed_at == 0 {
+            writer.allocate(total)?;
+        }
+
+        if resumed_at == 0 && reflink(&reader, &writer).is_ok() {
+            method = MoveMethod::Reflink;
+            copied = total;
+            progress(copied, total);
+        }
+
+        let mut read_write = ReadWrite::default();
+        let mut chunks = 0u64;
+        while copied < total {
+            let len = min(CHUNK_SIZE, total - copied);
+            let (n, used) = copy_chunk(&reader, &writer, &part, &mut read_write, copied, len)?;
+            if n == 0 {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "source file ended before the expected size",
+                ));
+            }
+            method = used;
+            copied += n;
+            chunks += 1;
+
+            if chunks.is_multiple_of(CHECKPOINT_INTERVAL) {
+                writer.sync_data()?;
+                write_checkpoint(&resume, total, copied)?;
+            }
+            progress(copied, total);
+        }
+
+        writer.sync_all()?;
+    }
+
+    verify_samples(src, &part, total)?;
+
+    fs::rename(&part, dst)?;
+    sync_parent_dir(dst);
+    let _ = fs::remove_file(&resume);
+    fs::remove_file(src)?;
+
+    Ok(MoveResult {
+        method,
+        bytes: total,
+        resumed_at,
+    })
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// resume info: total size and bytes copied, both u64 le
+fn read_checkpoint(resume: &Path, total: u64) -> Option<u64> {
+    let data = fs::read(resume).ok()?;
+    if data.len() != 16 {
+        return None;
+    }
+    let mut expected = [0u8; 8];
+    let mut offset = [0u8; 8];
+    expected.copy_from_slice(&data[0..8]);
+    offset.copy_from_slice(&data[8..16]);
+    if u64::from_le_bytes(expected) != total {
+        return None;
+    }
+    let offset = u64::from_le_bytes(offset);
+    if offset > total {
+        None
+    } else {
+        Some(offset)
+    }
+}
+
+fn write_checkpoint(resume: &Path, total: u64, copied: u64) -> io::Result<()> {
+    let mut data = Vec::with_capacity(16);
+    data.extend_from_slice(&total.to_le_bytes());
+    data.extend_from_slice(&copied.to_le_bytes());
+    let mut file = File::create(resume)?;
+    file.write_all(&data)?;
+    file.sync_all()
+}
+
+// compares hashes of evenly spread regions (always including head and tail)
+fn verify_samples(src: &Path, dst: &Path, total: u64) -> io::Result<()> {
+    if total == 0 {
+        return Ok(());
+    }
+    let mut a = open_r(src)?;
+    let mut b = open_r(dst)?;
+    let sample_size = min(VERIFY_SAMPLE_SIZE, total);
+    let last = total - sample_size;
+    let mut buf_a = vec![0u8; sample_size as usize];
+    let mut buf_b = vec![0u8; sample_size as usize];
+
+    for i in 0..VERIFY_SAMPLES {
+        let offset = if VERIFY_SAMPLES > 1 {
+            last / (VERIFY_SAMPLES - 1) * i
+        } else {
+            0
+        };
+        let offset = if i == VERIFY_SAMPLES - 1 { last } else { offset };
+
+        a.seek(SeekFrom::Start(offset))?;
+        a.read_exact(&mut buf_a)?;
+        b.seek(SeekFrom::Start(offset))?;
+        b.read_exact(&mut buf_b)?;
+
+        if checksum(&buf_a) != checksum(&buf_b) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("verification failed at offset {}", offset),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn checksum(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(data);
+    hasher.finish()
+}
+
+// The portable fallback's state for one move: an aligned buffer and the target
+// opened with direct i/o, both made on first use and kept for every chunk.
+#[derive(Default)]
+struct ReadWrite {
+    buffer: Option<PageAlignedByteBuffer>,
+    // Some(None) if the target can't be opened with direct i/o
+    direct: Option<Option<File>>,
+}
+
+// portable fallback: aligned buffer, direct i/o on the target where the layout allows it
+fn copy_read_write(
+    reader: &File,
+    writer: &File,
+    part: &Path,
+    state: &mut ReadWrite,
+    offset: u64,
+    len: u64,
+) -> io::Result<u64> {
+    let aligned = offset.is_multiple_of(DIRECT_IO_ALIGNMENT) && len.is_multiple_of(DIRECT_IO_ALIGNMENT);
+    if state.buffer.as_ref().is_none_or(|x| x.size() < len as usize) {
+        state.buffer = Some(PageAlignedByteBuffer::new(len as usize));
+    }
+    let buf = state.buffer.as_mut().unwrap().as_mut_slice();
+
+    let mut reader = reader;
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut filled = 0usize;
+    while filled < len as usize {
+        let n = reader.read(&mut buf[filled..len as usize])?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    if filled == 0 {
+        return Ok(0);
+    }
+    // a short read breaks direct i/o alignment, write it buffered
+    let direct = if aligned && filled == len as usize {
+        state
+            .direct
+            .get_or_insert_with(|| open_using_direct_io(part).ok())
+            .as_ref()
+    } else {
+        None
+    };
+    let mut writer = direct.unwrap_or(writer);
+    writer.seek(SeekFrom::Start(offset))?;
+    writer.write_all(&buf[..filled])?;
+    Ok(filled as u64)
+}
+
+fn sync_parent_dir(path: &Path) {
+    #[cfg(unix)]
+    {
+        if let Some(parent) = path.parent() {
+            if let Ok(dir) = File::open(parent) {
+                let _ = dir.sync_all();
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use std::os::unix::io::AsRawFd;
+
+        // _IOW(0x94, 9, int), shares extents on btrfs/xfs (e.g. across subvolumes)
+        const FICLONE: u64 = 0x4004_9409;
+
+        fn reflink(reader: &File, writer: &File) -> io::Result<()> {
+            let ret = unsafe { libc::ioctl(writer.as_raw_fd(), FICLONE as _, reader.as_raw_fd()) };
+            if ret == 0 {
+                Ok(())
+            } else {
+                Err(Error::last_os_error())
+            }
+        }
+
+        fn is_unsupported(e: &Error) -> bool {
+            match e.raw_os_error() {
+                Some(code) => {
+                    code == libc::ENOSYS
+                        || code == libc::EXDEV
+                        || code == libc::EINVAL
+                        || code == libc::EOPNOTSUPP
+                }
+                None => false,
+            }
+        }
+
+        fn copy_chunk(
+            reader: &File,
+            writer: &File,
+            part: &Path,
+            read_write: &mut ReadWrite,
+            offset: u64,
+            len: u64,
+        ) -> io::Result<(u64, MoveMethod)> {
+            match copy_file_range(reader, writer, offset, len) {
+                Ok(n) => return Ok((n, MoveMethod::CopyFileRange)),
+                Err(e) if is_unsupported(&e) => (),
+                Err(e) => return Err(e),
+            }
+            match sendfile(reader, writer, offset, len) {
+                Ok(n) => return Ok((n, MoveMethod::Sendfile)),
+                Err(e) if is_unsupported(&e) => (),
+                Err(e) => return Err(e),
+            }
+            copy_read_write(reader, writer, part, read_write, offset, len).map(|n| (n, MoveMethod::ReadWrite))
+        }
+
+        fn copy_file_range(reader: &File, writer: &File, offset: u64, len: u64) -> io::Result<u64> {
+            let mut off_in = offset as libc::loff_t;
+            let mut off_out = offset as libc::loff_t;
+            let mut done = 0u64;
+            while done < len {
+                let ret = unsafe {
+                    libc::copy_file_range(
+                        reader.as_raw_fd(),
+                        &mut off_in,
+                        writer.as_raw_fd(),
+                        &mut off_out,
+                        (len - done) as usize,
+                        0,
+                    )
+                };
+                if ret < 0 {
+                    if done > 0 {
+                        break;
+                    }
+                    return Err(Error::last_os_error());
+                }
+                if ret == 0 {
+                    break;
+                }
+                done += ret as u64;
+            }
+            Ok(done)
+        }
+
+        fn sendfile(reader: &File, writer: &File, offset: u64, len: u64) -> io::Result<u64> {
+            let mut writer = writer.try_clone()?;
+            writer.seek(SeekFrom::Start(offset))?;
+            let mut off_in = offset as libc::off_t;
+            let mut done = 0u64;
+            while done < len {
+                let ret = unsafe {
+                    libc::sendfile(
+                        writer.as_raw_fd(),
+                        reader.as_raw_fd(),
+                        &mut off_in,
+                        (len - done) as usize,
+                    )
+                };
+                if ret < 0 {
+                    if done > 0 {
+                        break;
+                    }
+                    return Err(Error::last_os_error());
+                }
+                if ret == 0 {
+                    break;
+                }
+                done += ret as u64;
+            }
+            Ok(done)
+        }
+    } else {
+        fn reflink(_reader: &File, _writer: &File) -> io::Result<()> {
+            Err(Error::new(ErrorKind::Other, "reflink not supported"))
+        }
+
+        fn copy_chunk(
+            reader: &File,
+            writer: &File,
+            part: &Path,
+            read_write: &mut ReadWrite,
+            offset: u64,
+            len: u64,
+        ) -> io::Result<(u64, MoveMethod)> {
+            copy_read_write(reader, writer, part, read_write, offset, len).map(|n| (n, MoveMethod::ReadWrite))
+        }
+    }
+}
+
+#[cfg(test)]
+mod mover_tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    fn test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 + i / 4096) as u8).collect()
+    }
+
+    #[test]
+    fn copy_resumes_and_verifies() {
+        let dir = temp_dir("mover-resume");
+        let src = dir.join("plot");
+        let dst = dir.join("final");
+        let data = test_data(3 * 1024 * 1024 + 4096);
+        fs::write(&src, &data).unwrap();
+
+        // simulate an interrupted copy: first MiB done, checkpoint written
+        let part = with_suffix(&dst, ".part");
+        fs::write(&part, &data[..1024 * 1024]).unwrap();
+        write_checkpoint(&with_suffix(&dst, ".part.resume"), data.len() as u64, 1024 * 1024).unwrap();
+        assert_eq!(
+            read_checkpoint(&with_suffix(&dst, ".part.resume"), data.len() as u64),
+            Some(1024 * 1024)
+        );
+
+        let reader = open_r(&src).unwrap();
+        let writer = open(&part).unwrap();
+        let mut read_write = ReadWrite::default();
+        let mut copied = 1024 * 1024;
+        while copied < data.len() as u64 {
+            let len = data.len() as u64 - copied;
+            let (n, _) = copy_chunk(&reader, &writer, &part, &mut read_write, copied, len).unwrap();
+            copied += n;
+        }
+        writer.sync_all().unwrap();
+
+        verify_samples(&src, &part, data.len() as u64).unwrap();
+        assert_eq!(fs::read(&part).unwrap(), data);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn interrupted_move_resumes() {
+        let dir = temp_dir("mover-interrupt");
+        let src = dir.join("plot");
+        let dst = dir.join("final");
+        let part = with_suffix(&dst, ".part");
+        let resume = with_suffix(&dst, ".part.resume");
+        let data = test_data(3 * 1024 * 1024 + 4096);
+        let total = data.len() as u64;
+        fs::write(&src, &data).unwrap();
+
+        // the process dies after a MiB and a half, the last checkpoint was at one MiB
+        let interrupted = std::panic::catch_unwind(|| {
+            copy_plot(&src, &dst, |copied, total| {
+                if copied >= 3 * 512 * 1024 && copied < total {
+                    panic!("interrupted");
+                }
+            })
+        });
+        if let Ok(result) = interrupted {
+            // the file system cloned the file at once, there was nothing to interrupt
+            assert_eq!(result.unwrap().method, MoveMethod::Reflink);
+            let _ = fs::remove_dir_all(&dir);
+            return;
+        }
+        assert!(part.exists() && src.exists() && !dst.exists());
+        assert_eq!(read_checkpoint(&resume, total), Some(1024 * 1024));
+
+        let mut first = None;
+        let result = copy_plot(&src, &dst, |copied, _| {
+            first.get_or_insert(copied);
+        })
+        .unwrap();
+        assert_eq!(result.resumed_at, 1024 * 1024);
+        assert_eq!(result.bytes, total);
+        assert_eq!(first, Some(1024 * 1024 + CHUNK_SIZE));
+        assert_eq!(fs::read(&dst).unwrap(), data);
+        assert!(!src.exists() && !part.exists() && !resume.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn verification_detects_corruption() {
+        let dir = temp_dir("mover-verify");
+        let src = dir.join("a");
+        let dst = dir.join("b");
+        let data = test_data(2 * 1024 * 1024);
+        let mut bad = data.clone();
+        let last = bad.len() - 1;
+        bad[last] ^= 0xFF;
+        fs::write(&src, &data).unwrap();
+        fs::write(&dst, &bad).unwrap();
+
+        assert!(verify_samples(&src, &dst, data.len() as u64).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
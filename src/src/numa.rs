@@ -0,0 +1,151 @@
+use crate::plotter::SCOOP_SIZE;
+use std::fs;
+use std::ops::Range;
+
+// a NUMA node and the cpus attached to it, ids as the kernel numbers them
+#[derive(Clone, Debug, PartialEq)]
+pub struct NumaNode {
+    pub id: usize,
+    pub cpus: Vec<usize>,
+}
+
+// Reads the topology from sysfs. Without NUMA information (non-linux, containers
+// hiding sysfs) the machine is a single node holding every cpu.
+pub fn numa_nodes() -> Vec<NumaNode> {
+    let mut nodes = Vec::new();
+    if let Ok(entries) = fs::read_dir("/sys/devices/system/node") {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let id = match name.strip_prefix("node").and_then(|x| x.parse().ok()) {
+                Some(x) => x,
+                None => continue,
+            };
+            let cpus = fs::read_to_string(entry.path().join("cpulist"))
+                .ok()
+                .and_then(|x| parse_cpu_list(&x).ok())
+                .unwrap_or_default();
+            // memory-only nodes (cxl, hbm) have nobody to hash for them
+            if !cpus.is_empty() {
+                nodes.push(NumaNode { id, cpus });
+            }
+        }
+    }
+    nodes.sort_by_key(|x| x.id);
+
+    if nodes.is_empty() {
+        let cpus = core_affinity::get_core_ids()
+            .map(|x| x.iter().map(|x| x.id).collect())
+            .unwrap_or_default();
+        nodes.push(NumaNode { id: 0, cpus });
+    }
+    nodes
+}
+
+// parses the kernel's cpu list format, e.g. "0-3,8-11"
+pub fn parse_cpu_list(list: &str) -> Result<Vec<usize>, String> {
+    let mut cpus = Vec::new();
+    for part in list.trim().split(',').map(str::trim).filter(|x| !x.is_empty()) {
+        let invalid = || format!("invalid cpu list: {}", list.trim());
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let first: usize = first.trim().parse().map_err(|_| invalid())?;
+                let last: usize = last.trim().parse().map_err(|_| invalid())?;
+                if last < first {
+                    return Err(invalid());
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(part.parse().map_err(|_| invalid())?),
+        }
+    }
+    Ok(cpus)
+}
+
+// Splits the nonces of a buffer into one stripe per node, sized by the node's
+// share of hashing threads. Stripes start on page boundaries of each scoop row
+// where the buffer allows, so their memory can be bound to the node.
+pub fn stripes(buffer_nonces: u64, threads: &[u64]) -> Vec<Range<u64>> {
+    let total: u64 = threads.iter().sum();
+    if total == 0 {
+        return std::iter::once(0..buffer_nonces).collect();
+    }
+    let align = (page_size::get() as u64 / SCOOP_SIZE).max(1);
+    let align = if buffer_nonces >= align * threads.len() as u64 { align } else { 1 };
+
+    let mut stripes = Vec::new();
+    let mut start = 0;
+    let mut assigned_threads = 0;
+    for (i, &node_threads) in threads.iter().enumerate() {
+        assigned_threads += node_threads;
+        let end = if i + 1 == threads.len() {
+            buffer_nonces
+        } else {
+            buffer_nonces * assigned_threads / total / align * align
+        };
+        let end = end.max(start);
+        stripes.push(start..end);
+        start = end;
+    }
+    stripes
+}
+
+// Binds not yet touched pages to a node and migrates pages already faulted in
+// (e.g. by mlock). The range is widened to whole pages.
+#[cfg(target_os = "linux")]
+pub fn bind_memory(pointer: *mut u8, len: usize, node: usize) -> bool {
+    const MPOL_BIND: libc::c_long = 2;
+    const MPOL_MF_MOVE: libc::c_long = 1 << 1;
+
+    let page = page_size::get();
+    let start = pointer as usize / page * page;
+    let len = (pointer as usize + len).div_ceil(page) * page - start;
+    let mut nodemask = vec![0u64; node / 64 + 1];
+    nodemask[node / 64] |= 1 << (node % 64);
+    unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            start as *mut libc::c_void,
+            len,
+            MPOL_BIND,
+            nodemask.as_ptr(),
+            (nodemask.len() * 64) as libc::c_ulong,
+            MPOL_MF_MOVE,
+        ) == 0
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn bind_memory(_pointer: *mut u8, _len: usize, _node: usize) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod numa_tests {
+    use super::*;
+
+    #[test]
+    fn parse_cpu_lists() {
+        assert_eq!(parse_cpu_list("0-3,8-11\n"), Ok(vec![0, 1, 2, 3, 8, 9, 10, 11]));
+        assert_eq!(parse_cpu_list("5"), Ok(vec![5]));
+        assert_eq!(parse_cpu_list(""), Ok(vec![]));
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("a-b").is_err());
+    }
+
+    #[test]
+    fn stripes_cover_buffer_by_thread_share() {
+        let align = (page_size::get() as u64 / SCOOP_SIZE).max(1);
+        let stripes = stripes(align * 100, &[3, 1]);
+        assert_eq!(stripes.len(), 2);
+        assert_eq!(stripes[0].start, 0);
+        assert_eq!(stripes[0].end, align * 75);
+        assert_eq!(stripes[1].end, align * 100);
+
+        let whole = super::stripes(64, &[0, 0]);
+        assert_eq!(whole.len(), 1);
+        assert_eq!(whole[0], 0..64);
+        let tiny = super::stripes(3, &[1, 1]);
+        assert_eq!(tiny.last().unwrap().end, 3);
+        assert!(tiny.iter().all(|x| x.start <= x.end));
+    }
+}
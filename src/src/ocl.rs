@@ -1,647 +1,939 @@
-use self::core::{
-    ArgVal, ContextProperties, Event, KernelWorkGroupInfo,
-};
-use crate::gpu_hasher::GpuTask;
-use crate::plotter::NONCE_SIZE;
-use ocl_core::{DeviceType, get_device_ids, get_platform_ids, get_device_info, DeviceInfo};
-use ocl_core as core;
-use rayon::prelude::*;
-use std::cmp::min;
-use std::ffi::CString;
-use std::process;
-use std::slice::{from_raw_parts, from_raw_parts_mut};
-use std::sync::{Arc, Mutex};
-
-static SRC: &'static str = include_str!("ocl/kernel.cl");
-
-const GPU_HASHES_PER_RUN: usize = 32;
-const MSHABAL512_VECTOR_SIZE: u64 = 16;
-
-#[allow(dead_code)]
-pub struct GpuContext {
-    queue_a: core::CommandQueue,
-    queue_b: core::CommandQueue,
-    kernel: core::Kernel,
-    ldim1: [usize; 3],
-    gdim1: [usize; 3],
-    mapping: bool,
-    buffer_ptr_host: Option<core::MemMap<u8>>,
-    buffer_host: Option<core::Mem>,
-    buffer_gpu_a: core::Mem,
-    buffer_gpu_b: core::Mem,
-    pub worksize: usize,
-}
-
-unsafe impl Sync for GpuContext {}
-
-impl GpuContext {
-    pub fn new(
-        gpu_platform: usize,
-        gpu_id: usize,
-        cores: usize,
-        nvidia: bool,
-        mapping: bool,
-    ) -> GpuContext {
-        let platform_ids = core::get_platform_ids().unwrap();
-        let platform_id = platform_ids[gpu_platform];
-        let device_ids = core::get_device_ids(&platform_id, None, None).unwrap();
-        let device_id = device_ids[gpu_id];
-        let context_properties = ContextProperties::new().platform(platform_id);
-        let context =
-            core::create_context(Some(&context_properties), &[device_id], None, None).unwrap();
-        let src_cstring = CString::new(SRC).unwrap();
-        let program = core::create_program_with_source(&context, &[src_cstring]).unwrap();
-        core::build_program(
-            &program,
-            None::<&[()]>,
-            &CString::new("").unwrap(),
-            None,
-            None,
-        )
-        .unwrap();
-        let queue_a = core::create_command_queue(&context, &device_id, None).unwrap();
-        let queue_b = core::create_command_queue(&context, &device_id, None).unwrap();
-        let kernel = core::create_kernel(&program, "calculate_nonces").unwrap();
-        let kernel_workgroup_size = get_kernel_work_group_size(&kernel, device_id);
-        let workgroup_count = cores;
-        let base_worksize = kernel_workgroup_size * workgroup_count;
-        // Pad to next multiple of kernel_workgroup_size to match gdim1 rounding
-        let launch_worksize = ((base_worksize + kernel_workgroup_size - 1) / kernel_workgroup_size) * kernel_workgroup_size;
-        let gdim1 = [launch_worksize, 1, 1];
-        let ldim1 = [kernel_workgroup_size, 1, 1];
-
-        // Allocate using the padded launch size (safe for mapping/reading)
-        let alloc_size = launch_worksize * NONCE_SIZE as usize;
-
-        if mapping {
-            let buffer_gpu_a = unsafe {
-                core::create_buffer::<_, u8>(
-                    &context,
-                    core::MEM_READ_WRITE | core::MEM_ALLOC_HOST_PTR,
-                    alloc_size,
-                    None,
-                )
-                .unwrap()
-            };
-            let buffer_gpu_b = unsafe {
-                core::create_buffer::<_, u8>(
-                    &context,
-                    core::MEM_READ_WRITE | core::MEM_ALLOC_HOST_PTR,
-                    alloc_size,
-                    None,
-                )
-                .unwrap()
-            };
-            GpuContext {
-                queue_a,
-                queue_b,
-                kernel,
-                ldim1,
-                gdim1,
-                mapping,
-                buffer_gpu_a,
-                buffer_gpu_b,
-                buffer_ptr_host: None,
-                buffer_host: None,
-                worksize: launch_worksize,
-            }
-        } else {
-            let buffer_host = unsafe {
-                core::create_buffer::<_, u8>(
-                    &context,
-                    core::MEM_READ_WRITE | core::MEM_ALLOC_HOST_PTR,
-                    alloc_size,
-                    None,
-                )
-                .unwrap()
-            };
-            let buffer_ptr_host = unsafe {
-                Some(
-                    core::enqueue_map_buffer::<u8, _, _, _>(
-                        &queue_b,
-                        &buffer_host,
-                        true,
-                        core::MAP_READ,
-                        0,
-                        alloc_size,
-                        None::<Event>,
-                        None::<&mut Event>,
-                    )
-                    .unwrap(),
-                )
-            };
-            let buffer_gpu_a = if nvidia {
-                buffer_host.clone()
-            } else {
-                unsafe {
-                    core::create_buffer::<_, u8>(
-                        &context,
-                        core::MEM_READ_WRITE,
-                        alloc_size,
-                        None,
-                    )
-                    .unwrap()
-                }
-            };
-            let buffer_gpu_b = unsafe {
-                core::create_buffer::<_, u8>(
-                    &context,
-                    core::MEM_READ_WRITE,
-                    alloc_size,
-                    None,
-                )
-                .unwrap()
-            };
-
-            let buffer_host = if nvidia { None } else { Some(buffer_host) };
-            GpuContext {
-                queue_a,
-                queue_b,
-                kernel,
-                ldim1,
-                gdim1,
-                mapping,
-                buffer_gpu_a,
-                buffer_gpu_b,
-                buffer_ptr_host,
-                buffer_host,
-                worksize: launch_worksize,
-            }
-        }
-    }
-}
-
-pub fn get_kernel_work_group_size(x: &core::Kernel, y: core::DeviceId) -> usize {
-    match core::get_kernel_work_group_info(x, y, KernelWorkGroupInfo::WorkGroupSize).unwrap() {
-        core::KernelWorkGroupInfoResult::WorkGroupSize(kws) => kws,
-        _ => panic!("Unexpected error"),
-    }
-}
-
-pub fn gpu_hash(gpu_context: &Arc<Mutex<GpuContext>>, task: &GpuTask) {
-    let numeric_id_be: u64 = task.numeric_id.to_be();
-
-    let mut start;
-    let mut end;
-    let gpu_context = gpu_context.lock().unwrap();
-
-    core::set_kernel_arg(
-        &gpu_context.kernel,
-        0,
-        ArgVal::mem(&gpu_context.buffer_gpu_a),
-    )
-    .unwrap();
-    core::set_kernel_arg(
-        &gpu_context.kernel,
-        1,
-        ArgVal::primitive(&task.local_startnonce),
-    )
-    .unwrap();
-    core::set_kernel_arg(
-        &gpu_context.kernel,
-        5,
-        ArgVal::primitive(&task.local_nonces),
-    )
-    .unwrap();
-    core::set_kernel_arg(&gpu_context.kernel, 2, ArgVal::primitive(&numeric_id_be)).unwrap();
-
-    for i in (0..8192).step_by(GPU_HASHES_PER_RUN) {
-        if i + GPU_HASHES_PER_RUN < 8192 {
-            start = i;
-            end = i + GPU_HASHES_PER_RUN - 1;
-        } else {
-            start = i;
-            end = i + GPU_HASHES_PER_RUN;
-        }
-
-        core::set_kernel_arg(&gpu_context.kernel, 3, ArgVal::primitive(&(start as i32))).unwrap();
-        core::set_kernel_arg(&gpu_context.kernel, 4, ArgVal::primitive(&(end as i32))).unwrap();
-
-        unsafe {
-            core::enqueue_kernel(
-                &gpu_context.queue_a,
-                &gpu_context.kernel,
-                1,
-                None,
-                &gpu_context.gdim1,
-                Some(gpu_context.ldim1),
-                None::<Event>,
-                None::<&mut Event>,
-            )
-            .unwrap();
-        }
-    }
-    core::finish(&gpu_context.queue_a).unwrap();
-}
-
-pub fn gpu_transfer_to_host(
-    gpu_context: &Arc<Mutex<GpuContext>>,
-    buffer_id: u8,
-    transfer_task: &GpuTask,
-) {
-    let mut gpu_context = gpu_context.lock().unwrap();
-
-    let map = if gpu_context.mapping {
-        Some(mem_map_gpu_to_host(buffer_id, &gpu_context))
-    } else {
-        None
-    };
-
-    let buffer = if gpu_context.mapping {
-        map.as_ref().unwrap().as_ptr()
-    } else {
-        let ptr = gpu_context.buffer_ptr_host.as_mut().unwrap().as_mut_ptr();
-        let slice = unsafe { from_raw_parts_mut(ptr, gpu_context.worksize * NONCE_SIZE as usize) };
-        mem_transfer_gpu_to_host(buffer_id, &gpu_context, slice);
-        core::finish(&gpu_context.queue_b).unwrap();
-        ptr
-    };
-    unpack_shuffle_scatter(buffer, &gpu_context, &transfer_task);
-    if gpu_context.mapping {
-        mem_unmap_gpu_to_host(buffer_id, &gpu_context, map);
-        core::finish(&gpu_context.queue_a).unwrap();
-    }
-}
-
-pub fn gpu_hash_and_transfer_to_host(
-    gpu_context: &Arc<Mutex<GpuContext>>,
-    buffer_id: u8,
-    hasher_task: &GpuTask,
-    transfer_task: &GpuTask,
-) {
-    let mut gpu_context = gpu_context.lock().unwrap();
-
-    let map = if gpu_context.mapping {
-        Some(mem_map_gpu_to_host(buffer_id, &gpu_context))
-    } else {
-        None
-    };
-
-    let buffer = if gpu_context.mapping {
-        map.as_ref().unwrap().as_ptr()
-    } else {
-        let ptr = gpu_context.buffer_ptr_host.as_mut().unwrap().as_mut_ptr();
-        let slice = unsafe { from_raw_parts_mut(ptr, gpu_context.worksize * NONCE_SIZE as usize) };
-        mem_transfer_gpu_to_host(buffer_id, &gpu_context, slice);
-        ptr
-    };
-
-    let numeric_id_be: u64 = hasher_task.numeric_id.to_be();
-
-    let mut start;
-    let mut end;
-
-    core::set_kernel_arg(
-        &gpu_context.kernel,
-        0,
-        ArgVal::mem(if buffer_id == 0 {
-            &gpu_context.buffer_gpu_a
-        } else {
-            &gpu_context.buffer_gpu_b
-        }),
-    )
-    .unwrap();
-    core::set_kernel_arg(
-        &gpu_context.kernel,
-        1,
-        ArgVal::primitive(&hasher_task.local_startnonce),
-    )
-    .unwrap();
-    core::set_kernel_arg(
-        &gpu_context.kernel,
-        5,
-        ArgVal::primitive(&hasher_task.local_nonces),
-    )
-    .unwrap();
-    core::set_kernel_arg(&gpu_context.kernel, 2, ArgVal::primitive(&numeric_id_be)).unwrap();
-
-    for i in (0..8192).step_by(GPU_HASHES_PER_RUN) {
-        if i + GPU_HASHES_PER_RUN < 8192 {
-            start = i;
-            end = i + GPU_HASHES_PER_RUN - 1;
-        } else {
-            start = i;
-            end = i + GPU_HASHES_PER_RUN;
-        }
-        core::set_kernel_arg(&gpu_context.kernel, 3, ArgVal::primitive(&(start as i32))).unwrap();
-        core::set_kernel_arg(&gpu_context.kernel, 4, ArgVal::primitive(&(end as i32))).unwrap();
-        unsafe {
-            core::enqueue_kernel(
-                &gpu_context.queue_a,
-                &gpu_context.kernel,
-                1,
-                None,
-                &gpu_context.gdim1,
-                Some(gpu_context.ldim1),
-                None::<Event>,
-                None::<&mut Event>,
-            )
-            .unwrap();
-        }
-    }
-    core::finish(&gpu_context.queue_b).unwrap();
-    unpack_shuffle_scatter(buffer, &gpu_context, &transfer_task);
-    if gpu_context.mapping {
-        mem_unmap_gpu_to_host(buffer_id, &gpu_context, map);
-    }
-    core::finish(&gpu_context.queue_a).unwrap();
-}
-
-// Use padded worksize for mapping to match gdim1
-fn mem_map_gpu_to_host(buffer_id: u8, gpu_context: &GpuContext) -> core::MemMap<u8> {
-    unsafe {
-        if buffer_id == 1 {
-            core::enqueue_map_buffer::<u8, _, _, _>(
-                &gpu_context.queue_b,
-                &gpu_context.buffer_gpu_a,
-                true,
-                core::MAP_READ,
-                0,
-                gpu_context.worksize * NONCE_SIZE as usize,
-                None::<Event>,
-                None::<&mut Event>,
-            )
-            .unwrap()
-        } else {
-            core::enqueue_map_buffer::<u8, _, _, _>(
-                &gpu_context.queue_b,
-                &gpu_context.buffer_gpu_b,
-                true,
-                core::MAP_READ,
-                0,
-                gpu_context.worksize * NONCE_SIZE as usize,
-                None::<Event>,
-                None::<&mut Event>,
-            )
-            .unwrap()
-        }
-    }
-}
-
-fn mem_unmap_gpu_to_host(buffer_id: u8, gpu_context: &GpuContext, map: Option<core::MemMap<u8>>) {
-    if buffer_id == 1 {
-        core::enqueue_unmap_mem_object(
-            &gpu_context.queue_a,
-            &gpu_context.buffer_gpu_a,
-            &map.unwrap(),
-            None::<Event>,
-            None::<&mut Event>,
-        )
-        .unwrap()
-    } else {
-        core::enqueue_unmap_mem_object(
-            &gpu_context.queue_a,
-            &gpu_context.buffer_gpu_b,
-            &map.unwrap(),
-            None::<Event>,
-            None::<&mut Event>,
-        )
-        .unwrap()
-    };
-}
-
-fn mem_transfer_gpu_to_host(buffer_id: u8, gpu_context: &GpuContext, slice: &mut [u8]) {
-    unsafe {
-        if buffer_id == 1 {
-            core::enqueue_read_buffer(
-                &gpu_context.queue_b,
-                &gpu_context.buffer_gpu_a,
-                false,
-                0,
-                slice,
-                None::<Event>,
-                None::<&mut Event>,
-            )
-            .unwrap();
-        } else {
-            core::enqueue_read_buffer(
-                &gpu_context.queue_b,
-                &gpu_context.buffer_gpu_b,
-                false,
-                0,
-                slice,
-                None::<Event>,
-                None::<&mut Event>,
-            )
-            .unwrap();
-        }
-    }
-}
-
-fn unpack_shuffle_scatter(buffer: *const u8, gpu_context: &GpuContext, transfer_task: &GpuTask) {
-    unsafe {
-        let buffer = from_raw_parts(buffer, gpu_context.worksize * NONCE_SIZE as usize);
-        let iter: Vec<u64> = (0..transfer_task.local_nonces).step_by(16).collect();
-        iter.par_iter().for_each(|n| {
-            let data = from_raw_parts_mut(
-                transfer_task.cache.ptr,
-                NONCE_SIZE as usize * transfer_task.cache_size as usize,
-            );
-            for i in 0..8192 {
-                for j in (0..32).step_by(4) {
-                    for k in 0..MSHABAL512_VECTOR_SIZE {
-                        let data_offset = (((i & 1) * (4095 - (i >> 1)) + ((i + 1) & 1) * (i >> 1))
-                            * 64
-                            * transfer_task.cache_size
-                            + (*n + k + transfer_task.chunk_offset) * 64
-                            + (i & 1) * 32
-                            + j) as usize;
-                        let buffer_offset = (*n * NONCE_SIZE
-                            + (i * 32 + j) * MSHABAL512_VECTOR_SIZE
-                            + k * 4) as usize;
-                        data[data_offset..(data_offset + 4)]
-                            .clone_from_slice(&buffer[buffer_offset..(buffer_offset + 4)]);
-                    }
-                }
-            }
-        })
-    }
-}
-
-pub fn platform_info() {
-    let platform_ids = core::get_platform_ids().unwrap();
-    for (i, platform_id) in platform_ids.iter().enumerate() {
-        println!(
-            "OCL: platform {}, {} - {}",
-            i,
-            core::get_platform_info(platform_id, core::PlatformInfo::Name).unwrap().to_string(),
-            core::get_platform_info(platform_id, core::PlatformInfo::Version).unwrap().to_string()
-        );
-        let device_ids = core::get_device_ids(platform_id, None, None).unwrap();
-        for (j, device_id) in device_ids.iter().enumerate() {
-            println!(
-                "OCL:   device {}, {} - {}",
-                j,
-                core::get_device_info(device_id, DeviceInfo::Vendor).unwrap().to_string(),
-                core::get_device_info(device_id, DeviceInfo::Name).unwrap().to_string()
-            );
-
-            let cores = match core::get_device_info(device_id, DeviceInfo::MaxComputeUnits).unwrap() {
-                core::DeviceInfoResult::MaxComputeUnits(mcu) => mcu as usize,
-                _ => 1,
-            };
-            let kernel_workgroup_size = 256; // fallback
-            println!(
-                "OCL:     cores={},kernel_workgroupsize={}",
-                cores, kernel_workgroup_size
-            );
-        }
-        println!("OCL:");
-    }
-}
-
-pub fn gpu_get_info(gpus: &[String], quiet: bool) -> u64 {
-    let mut total_mem_needed = 0u64;
-
-    let gpu_list = if gpus.is_empty() {
-        let platform_ids = core::get_platform_ids().unwrap();
-        let mut list = Vec::new();
-        for (platform_idx, platform) in platform_ids.iter().enumerate() {
-            let device_ids = core::get_device_ids(platform, Some(DeviceType::GPU), None).unwrap_or_default();
-            for device_idx in 0..device_ids.len() {
-                list.push(format!("{}:{}", platform_idx, device_idx));
-            }
-        }
-        list
-    } else {
-        gpus.to_vec()
-    };
-
-    for gpu_str in &gpu_list {
-        let parts = gpu_str.split(':').collect::<Vec<&str>>();
-        let platform_id = parts.get(0).and_then(|s| s.parse::<usize>().ok()).unwrap_or(0);
-        let gpu_id = parts.get(1).and_then(|s| s.parse::<usize>().ok()).unwrap_or(0);
-        let gpu_cores = parts.get(2).and_then(|s| s.parse::<usize>().ok()).unwrap_or(0);
-
-        let platform_ids = core::get_platform_ids().unwrap();
-        if platform_id >= platform_ids.len() {
-            println!("Error: Selected OpenCL platform {} doesn't exist.", platform_id);
-            process::exit(0);
-        }
-        let platform = platform_ids[platform_id];
-        let device_ids = core::get_device_ids(&platform, None, None).unwrap();
-        if gpu_id >= device_ids.len() {
-            println!("Error: Selected OpenCL device {} doesn't exist on platform {}", gpu_id, platform_id);
-            process::exit(0);
-        }
-        let device = device_ids[gpu_id];
-
-        let max_compute_units = match core::get_device_info(&device, DeviceInfo::MaxComputeUnits).unwrap() {
-            core::DeviceInfoResult::MaxComputeUnits(mcu) => mcu,
-            _ => {
-                println!("Error: Can't obtain number of GPU cores.");
-                process::exit(0);
-            }
-        };
-
-        let mem = match core::get_device_info(&device, DeviceInfo::GlobalMemSize).unwrap() {
-            core::DeviceInfoResult::GlobalMemSize(gms) => gms,
-            _ => {
-                println!("Error: Can't obtain GPU memory size.");
-                process::exit(0);
-            }
-        };
-
-        let context_properties = ContextProperties::new().platform(platform);
-        let context = core::create_context(Some(&context_properties), &[device], None, None).unwrap();
-        let src_cstring = CString::new(SRC).unwrap();
-        let program = core::create_program_with_source(&context, &[src_cstring]).unwrap();
-        core::build_program(&program, None::<&[()]>, &CString::new("").unwrap(), None, None).unwrap();
-        let kernel = core::create_kernel(&program, "calculate_nonces").unwrap();
-        let kernel_workgroup_size = get_kernel_work_group_size(&kernel, device);
-
-        let gpu_cores = if gpu_cores == 0 {
-            max_compute_units as usize
-        } else {
-            min(gpu_cores, 2 * max_compute_units as usize)
-        };
-
-        let mem_needed = 2 * gpu_cores * kernel_workgroup_size * 256 * 1024;
-
-        if mem_needed > mem as usize {
-            println!("Error: Not enough GPU memory (need {} GiB, have {} GiB). Reduce cores.", mem_needed / 1024 / 1024 / 1024, mem / 1024 / 1024 / 1024);
-            process::exit(0);
-        }
-
-        if !quiet {
-            println!(
-                "GPU: {} - {} [using {} of {} cores]",
-                core::get_device_info(&device, DeviceInfo::Vendor).unwrap().to_string(),
-                core::get_device_info(&device, DeviceInfo::Name).unwrap().to_string(),
-                gpu_cores,
-                max_compute_units
-            );
-            println!(
-                "     GPU-RAM: Total={:.2} GiB, Usage={:.2} GiB",
-                mem as f64 / 1024.0 / 1024.0 / 1024.0,
-                mem_needed as f64 / 1024.0 / 1024.0 / 1024.0,
-            );
-        }
-
-        total_mem_needed += mem_needed as u64;
-    }
-
-    total_mem_needed
-}
-
-pub fn gpu_init(gpus: &[String], zcb: bool) -> Vec<Arc<Mutex<GpuContext>>> {
-    let mut result = Vec::new();
-
-    let gpu_list = if gpus.is_empty() {
-        let platform_ids = core::get_platform_ids().unwrap();
-        let mut list = Vec::new();
-        for (platform_idx, platform) in platform_ids.iter().enumerate() {
-            let device_ids = core::get_device_ids(platform, Some(DeviceType::GPU), None).unwrap_or_default();
-            for device_idx in 0..device_ids.len() {
-                list.push(format!("{}:{}", platform_idx, device_idx));
-            }
-        }
-        list
-    } else {
-        gpus.to_vec()
-    };
-
-    for gpu_str in gpu_list {
-        let parts = gpu_str.split(':').collect::<Vec<&str>>();
-        let platform_id = parts.get(0).and_then(|s| s.parse::<usize>().ok()).unwrap_or(0);
-        let gpu_id = parts.get(1).and_then(|s| s.parse::<usize>().ok()).unwrap_or(0);
-        let gpu_cores = parts.get(2).and_then(|s| s.parse::<usize>().ok()).unwrap_or(0);
-
-        let platform_ids = core::get_platform_ids().unwrap();
-        if platform_id >= platform_ids.len() {
-            println!("Error: Selected OpenCL platform {} doesn't exist.", platform_id);
-            process::exit(0);
-        }
-        let platform = platform_ids[platform_id];
-        let device_ids = core::get_device_ids(&platform, None, None).unwrap();
-        if gpu_id >= device_ids.len() {
-            println!("Error: Selected OpenCL device {} doesn't exist on platform {}", gpu_id, platform_id);
-            process::exit(0);
-        }
-        let device = device_ids[gpu_id];
-
-        let max_compute_units = match core::get_device_info(&device, DeviceInfo::MaxComputeUnits).unwrap() {
-            core::DeviceInfoResult::MaxComputeUnits(mcu) => mcu,
-            _ => panic!("Can't obtain number of GPU cores."),
-        };
-
-        let vendor = core::get_device_info(&device, DeviceInfo::Vendor).unwrap().to_string().to_uppercase();
-        let nvidia = vendor.contains("NVIDIA");
-
-        let gpu_cores = if gpu_cores == 0 {
-            max_compute_units as usize
-        } else {
-            min(gpu_cores, max_compute_units as usize)
-        };
-
-        result.push(Arc::new(Mutex::new(GpuContext::new(
-            platform_id,
-            gpu_id,
-            gpu_cores,
-            nvidia,
-            zcb,
-        ))));
-    }
-
-    result
-}
\ No newline at end of file
+use self::core::{
+    ArgVal, ContextProperties, Event, KernelWorkGroupInfo,
+};
+use crate::buffer::NonceView;
+use crate::gpu_memory::{DeviceMemory, HostBuffer};
+use crate::gpu_selector::{OclDevice, SelectedGpu};
+use crate::kernel_cache::{cache_key, KernelCache};
+use crate::kernel_tuning::{KernelSetting, KernelTuning};
+use crate::scheduler::HashTask;
+use crate::plotter::NONCE_SIZE;
+use ocl_core::{DeviceType, DeviceInfo};
+use ocl_core as core;
+use rayon::prelude::*;
+use std::cmp::min;
+use std::ffi::CString;
+use std::slice::{from_raw_parts, from_raw_parts_mut};
+use std::sync::{Arc, Mutex};
+
+static SRC: &'static str = include_str!("ocl/kernel.cl");
+
+const MSHABAL512_VECTOR_SIZE: u64 = 16;
+const NUM_SCOOPS: usize = 4096;
+
+#[allow(dead_code)]
+pub struct GpuContext {
+    queue_a: core::CommandQueue,
+    queue_b: core::CommandQueue,
+    kernel: core::Kernel,
+    ldim1: [usize; 3],
+    gdim1: [usize; 3],
+    mapping: bool,
+    buffer_ptr_host: Option<core::MemMap<u8>>,
+    buffer_host: Option<core::Mem>,
+    buffer_gpu_a: core::Mem,
+    buffer_gpu_b: core::Mem,
+    // shuffle_scoops and its output, None unpacks on the host
+    shuffle: Option<core::Kernel>,
+    buffer_scoops: Option<core::Mem>,
+    // first and last hash of each calculate_nonces run
+    runs: Vec<(i32, i32)>,
+    pub worksize: usize,
+    pub name: String,
+    pub cores: usize,
+}
+
+unsafe impl Sync for GpuContext {}
+
+impl GpuContext {
+    pub fn new(
+        program: GpuProgram,
+        cores: usize,
+        host_buffer: HostBuffer,
+        host_shuffle: bool,
+    ) -> GpuContext {
+        let GpuProgram {
+            device: device_id,
+            name,
+            context,
+            program,
+            tuning,
+        } = program;
+        let queue_a = core::create_command_queue(&context, &device_id, None).unwrap();
+        let queue_b = core::create_command_queue(&context, &device_id, None).unwrap();
+        let kernel = core::create_kernel(&program, "calculate_nonces").unwrap();
+        let kernel_workgroup_size = tuning.workgroup_size(get_kernel_work_group_size(&kernel, device_id));
+        let runs = tuning.runs();
+        let workgroup_count = cores;
+        let base_worksize = kernel_workgroup_size * workgroup_count;
+        // Pad to next multiple of kernel_workgroup_size to match gdim1 rounding
+        let launch_worksize = ((base_worksize + kernel_workgroup_size - 1) / kernel_workgroup_size) * kernel_workgroup_size;
+        let gdim1 = [launch_worksize, 1, 1];
+        let ldim1 = [kernel_workgroup_size, 1, 1];
+
+        // Allocate using the padded launch size (safe for mapping/reading)
+        let alloc_size = launch_worksize * NONCE_SIZE as usize;
+        let mapping = host_buffer == HostBuffer::Mapped;
+        // the buffer the host maps lives in host memory
+        let host_flags = if mapping {
+            core::MEM_READ_WRITE | core::MEM_ALLOC_HOST_PTR
+        } else {
+            core::MEM_READ_WRITE
+        };
+
+        // a device that can't run the shuffle falls back to the host
+        let shuffle = if host_shuffle {
+            None
+        } else {
+            core::create_kernel(&program, "shuffle_scoops").ok()
+        };
+        let buffer_scoops = shuffle.as_ref().and_then(|_| unsafe {
+            core::create_buffer::<_, u8>(&context, host_flags, alloc_size, None).ok()
+        });
+        let shuffle = shuffle.filter(|_| buffer_scoops.is_some());
+
+        if mapping {
+            let hash_flags = if shuffle.is_some() {
+                core::MEM_READ_WRITE
+            } else {
+                host_flags
+            };
+            let buffer_gpu_a = unsafe {
+                core::create_buffer::<_, u8>(&context, hash_flags, alloc_size, None).unwrap()
+            };
+            let buffer_gpu_b = unsafe {
+                core::create_buffer::<_, u8>(&context, hash_flags, alloc_size, None).unwrap()
+            };
+            GpuContext {
+                queue_a,
+                queue_b,
+                kernel,
+                ldim1,
+                gdim1,
+                mapping,
+                buffer_gpu_a,
+                buffer_gpu_b,
+                buffer_ptr_host: None,
+                buffer_host: None,
+                shuffle,
+                buffer_scoops,
+                runs,
+                worksize: launch_worksize,
+                name,
+                cores,
+            }
+        } else {
+            // only the host unpack stages the output, the device shuffle reads its rows
+            // straight into the plot buffers
+            let buffer_host = if shuffle.is_none() {
+                unsafe {
+                    Some(
+                        core::create_buffer::<_, u8>(
+                            &context,
+                            core::MEM_READ_WRITE | core::MEM_ALLOC_HOST_PTR,
+                            alloc_size,
+                            None,
+                        )
+                        .unwrap(),
+                    )
+                }
+            } else {
+                None
+            };
+            let buffer_ptr_host = buffer_host.as_ref().map(|buffer_host| unsafe {
+                core::enqueue_map_buffer::<u8, _, _, _>(
+                    &queue_b,
+                    buffer_host,
+                    true,
+                    core::MAP_READ,
+                    0,
+                    alloc_size,
+                    None::<Event>,
+                    None::<&mut Event>,
+                )
+                .unwrap()
+            });
+            let buffer_gpu_a = match &buffer_host {
+                Some(buffer_host) if host_buffer == HostBuffer::Shared => buffer_host.clone(),
+                _ => unsafe {
+                    core::create_buffer::<_, u8>(
+                        &context,
+                        core::MEM_READ_WRITE,
+                        alloc_size,
+                        None,
+                    )
+                    .unwrap()
+                },
+            };
+            let buffer_gpu_b = unsafe {
+                core::create_buffer::<_, u8>(
+                    &context,
+                    core::MEM_READ_WRITE,
+                    alloc_size,
+                    None,
+                )
+                .unwrap()
+            };
+
+            let buffer_host = if host_buffer == HostBuffer::Shared {
+                None
+            } else {
+                buffer_host
+            };
+            GpuContext {
+                queue_a,
+                queue_b,
+                kernel,
+                ldim1,
+                gdim1,
+                mapping,
+                buffer_gpu_a,
+                buffer_gpu_b,
+                buffer_ptr_host,
+                buffer_host,
+                shuffle,
+                buffer_scoops,
+                runs,
+                worksize: launch_worksize,
+                name,
+                cores,
+            }
+        }
+    }
+}
+
+// cpu runtimes (PoCL, Intel) allow work groups of thousands of nonces, at 256 KiB
+// each that is gigabytes per buffer, so they get groups of one 16 lane vector
+// kernel.cl, or the tuning's kernel file, built for one device, in a context
+// of its own. The memory check builds it and the gpu's hasher reuses it.
+pub struct GpuProgram {
+    device: core::DeviceId,
+    name: String,
+    context: core::Context,
+    program: core::Program,
+    tuning: KernelTuning,
+}
+
+impl GpuProgram {
+    pub fn new(gpu: &SelectedGpu, tuning: &KernelTuning) -> Result<GpuProgram, String> {
+        let source = tuning.source(SRC)?;
+        let platform = core::get_platform_ids().unwrap()[gpu.platform];
+        let device = core::get_device_ids(&platform, None, None).unwrap()[gpu.device];
+        let context_properties = ContextProperties::new().platform(platform);
+        let context = core::create_context(Some(&context_properties), &[device], None, None)
+            .map_err(|e| format!("can't create an OpenCL context for {}: {}", gpu.name, e))?;
+
+        let driver = core::get_device_info(&device, DeviceInfo::DriverVersion)
+            .map(|x| x.to_string())
+            .unwrap_or_default();
+        let key = cache_key(&gpu.name, &driver, &source, &tuning.options);
+        let cache = KernelCache::open();
+
+        let cached = cache.as_ref().and_then(|cache| {
+            let binary = cache.load(&key)?;
+            let program = core::create_program_with_binary(&context, &[device], &[&binary[..]])
+                .ok()
+                .filter(|x| build(x, &tuning.options).is_ok());
+            if program.is_none() {
+                cache.remove(&key);
+            }
+            program
+        });
+        let program = match cached {
+            Some(x) => x,
+            None => {
+                let src_cstring = CString::new(source)
+                    .map_err(|_| format!("the OpenCL kernel for {} contains a nul byte", gpu.name))?;
+                let program = core::create_program_with_source(&context, &[src_cstring])
+                    .map_err(|e| format!("can't load the OpenCL kernel for {}: {}", gpu.name, e))?;
+                if build(&program, &tuning.options).is_err() {
+                    return Err(format!(
+                        "building the OpenCL kernel for {} failed:\n{}",
+                        gpu.name,
+                        build_log(&program, device)
+                    ));
+                }
+                // not caching only costs the next start a rebuild
+                if let (Some(cache), Ok(core::ProgramInfoResult::Binaries(binaries))) =
+                    (&cache, core::get_program_info(&program, core::ProgramInfo::Binaries))
+                {
+                    if let Some(binary) = binaries.first() {
+                        let _ = cache.store(&key, binary);
+                    }
+                }
+                program
+            }
+        };
+
+        Ok(GpuProgram {
+            device,
+            name: gpu.name.clone(),
+            context,
+            program,
+            tuning: tuning.clone(),
+        })
+    }
+}
+
+fn build(program: &core::Program, options: &str) -> core::Result<()> {
+    let options = CString::new(options).unwrap_or_default();
+    core::build_program(
+        program,
+        None::<&[()]>,
+        &options,
+        None,
+        None,
+    )
+}
+
+fn build_log(program: &core::Program, device: core::DeviceId) -> String {
+    match core::get_program_build_info(program, device, core::ProgramBuildInfo::BuildLog) {
+        Ok(core::ProgramBuildInfoResult::BuildLog(log)) => log.trim().to_string(),
+        _ => "(no build log)".to_string(),
+    }
+}
+
+pub fn get_kernel_work_group_size(x: &core::Kernel, y: core::DeviceId) -> usize {
+    let kws = match core::get_kernel_work_group_info(x, y, KernelWorkGroupInfo::WorkGroupSize).unwrap() {
+        core::KernelWorkGroupInfoResult::WorkGroupSize(kws) => kws,
+        _ => panic!("Unexpected error"),
+    };
+    if is_gpu(&y) {
+        kws
+    } else {
+        min(kws, MSHABAL512_VECTOR_SIZE as usize)
+    }
+}
+
+// anything else (cpu, accelerator) hashes the same, it's only sized differently
+fn is_gpu(device: &core::DeviceId) -> bool {
+    match core::get_device_info(device, DeviceInfo::Type) {
+        Ok(core::DeviceInfoResult::Type(x)) => x.contains(DeviceType::GPU),
+        _ => false,
+    }
+}
+
+pub fn gpu_hash(gpu_context: &Arc<Mutex<GpuContext>>, task: &HashTask) {
+    let gpu_context = gpu_context.lock().unwrap();
+    enqueue_hash(&gpu_context, &gpu_context.buffer_gpu_a, task);
+    core::finish(&gpu_context.queue_a).unwrap();
+}
+
+// The transfers hand back the task's nonces, still lent out, so they can be
+// checked before the scheduler hears they're hashed.
+pub fn gpu_transfer_to_host(
+    gpu_context: &Arc<Mutex<GpuContext>>,
+    buffer_id: u8,
+    transfer_task: HashTask,
+) -> Vec<NonceView> {
+    let mut gpu_context = gpu_context.lock().unwrap();
+
+    if gpu_context.shuffle.is_some() {
+        return shuffle_transfer_to_host(buffer_id, &gpu_context, transfer_task.view);
+    }
+
+    let map = if gpu_context.mapping {
+        Some(mem_map_gpu_to_host(buffer_id, &gpu_context))
+    } else {
+        None
+    };
+
+    let buffer = if gpu_context.mapping {
+        map.as_ref().unwrap().as_ptr()
+    } else {
+        let ptr = gpu_context.buffer_ptr_host.as_mut().unwrap().as_mut_ptr();
+        let slice = unsafe { from_raw_parts_mut(ptr, gpu_context.worksize * NONCE_SIZE as usize) };
+        mem_transfer_gpu_to_host(buffer_id, &gpu_context, slice);
+        core::finish(&gpu_context.queue_b).unwrap();
+        ptr
+    };
+    let views = unpack_shuffle_scatter(buffer, &gpu_context, transfer_task.view);
+    if gpu_context.mapping {
+        mem_unmap_gpu_to_host(buffer_id, &gpu_context, map);
+        core::finish(&gpu_context.queue_a).unwrap();
+    }
+    views
+}
+
+pub fn gpu_hash_and_transfer_to_host(
+    gpu_context: &Arc<Mutex<GpuContext>>,
+    buffer_id: u8,
+    hasher_task: &HashTask,
+    transfer_task: HashTask,
+) -> Vec<NonceView> {
+    let mut gpu_context = gpu_context.lock().unwrap();
+    let hash_buffer = if buffer_id == 0 {
+        gpu_context.buffer_gpu_a.clone()
+    } else {
+        gpu_context.buffer_gpu_b.clone()
+    };
+
+    if gpu_context.shuffle.is_some() {
+        enqueue_hash(&gpu_context, &hash_buffer, hasher_task);
+        let views = shuffle_transfer_to_host(buffer_id, &gpu_context, transfer_task.view);
+        core::finish(&gpu_context.queue_a).unwrap();
+        return views;
+    }
+
+    let map = if gpu_context.mapping {
+        Some(mem_map_gpu_to_host(buffer_id, &gpu_context))
+    } else {
+        None
+    };
+
+    let buffer = if gpu_context.mapping {
+        map.as_ref().unwrap().as_ptr()
+    } else {
+        let ptr = gpu_context.buffer_ptr_host.as_mut().unwrap().as_mut_ptr();
+        let slice = unsafe { from_raw_parts_mut(ptr, gpu_context.worksize * NONCE_SIZE as usize) };
+        mem_transfer_gpu_to_host(buffer_id, &gpu_context, slice);
+        ptr
+    };
+
+    enqueue_hash(&gpu_context, &hash_buffer, hasher_task);
+    core::finish(&gpu_context.queue_b).unwrap();
+    let views = unpack_shuffle_scatter(buffer, &gpu_context, transfer_task.view);
+    if gpu_context.mapping {
+        mem_unmap_gpu_to_host(buffer_id, &gpu_context, map);
+    }
+    core::finish(&gpu_context.queue_a).unwrap();
+    views
+}
+
+// queues the task's hashing rounds on queue_a
+fn enqueue_hash(gpu_context: &GpuContext, buffer: &core::Mem, task: &HashTask) {
+    let numeric_id_be: u64 = task.numeric_id.to_be();
+    let nonces = task.view.nonces();
+
+    core::set_kernel_arg(&gpu_context.kernel, 0, ArgVal::mem(buffer)).unwrap();
+    core::set_kernel_arg(
+        &gpu_context.kernel,
+        1,
+        ArgVal::primitive(&task.local_startnonce),
+    )
+    .unwrap();
+    core::set_kernel_arg(
+        &gpu_context.kernel,
+        5,
+        ArgVal::primitive(&nonces),
+    )
+    .unwrap();
+    core::set_kernel_arg(&gpu_context.kernel, 2, ArgVal::primitive(&numeric_id_be)).unwrap();
+
+    for (start, end) in gpu_context.runs.iter() {
+        core::set_kernel_arg(&gpu_context.kernel, 3, ArgVal::primitive(start)).unwrap();
+        core::set_kernel_arg(&gpu_context.kernel, 4, ArgVal::primitive(end)).unwrap();
+        unsafe {
+            core::enqueue_kernel(
+                &gpu_context.queue_a,
+                &gpu_context.kernel,
+                1,
+                None,
+                &gpu_context.gdim1,
+                Some(gpu_context.ldim1),
+                None::<Event>,
+                None::<&mut Event>,
+            )
+            .unwrap();
+        }
+    }
+}
+
+// Shuffles the hashed buffer into scoop rows on the device and reads each row
+// straight into its place in the host buffer, one dma per scoop instead of
+// unpack_shuffle_scatter on the cpu. Zero-copy maps the rows and copies them.
+fn shuffle_transfer_to_host(
+    buffer_id: u8,
+    gpu_context: &GpuContext,
+    mut view: NonceView,
+) -> Vec<NonceView> {
+    let kernel = gpu_context.shuffle.as_ref().unwrap();
+    let buffer_scoops = gpu_context.buffer_scoops.as_ref().unwrap();
+    let nonces = view.nonces();
+
+    core::set_kernel_arg(
+        kernel,
+        0,
+        ArgVal::mem(if buffer_id == 1 {
+            &gpu_context.buffer_gpu_a
+        } else {
+            &gpu_context.buffer_gpu_b
+        }),
+    )
+    .unwrap();
+    core::set_kernel_arg(kernel, 1, ArgVal::mem(buffer_scoops)).unwrap();
+    core::set_kernel_arg(kernel, 2, ArgVal::primitive(&nonces)).unwrap();
+    unsafe {
+        core::enqueue_kernel(
+            &gpu_context.queue_b,
+            kernel,
+            2,
+            None,
+            &[nonces as usize, NUM_SCOOPS, 1],
+            None,
+            None::<Event>,
+            None::<&mut Event>,
+        )
+        .unwrap();
+    }
+
+    if gpu_context.mapping {
+        let size = nonces as usize * NONCE_SIZE as usize;
+        let map = unsafe {
+            core::enqueue_map_buffer::<u8, _, _, _>(
+                &gpu_context.queue_b,
+                buffer_scoops,
+                true,
+                core::MAP_READ,
+                0,
+                size,
+                None::<Event>,
+                None::<&mut Event>,
+            )
+            .unwrap()
+        };
+        let rows = unsafe { from_raw_parts(map.as_ptr(), size) };
+        for scoop in 0..NUM_SCOOPS {
+            let row = view.scoop_mut(scoop);
+            let offset = scoop * row.len();
+            row.copy_from_slice(&rows[offset..offset + row.len()]);
+        }
+        core::enqueue_unmap_mem_object(
+            &gpu_context.queue_b,
+            buffer_scoops,
+            &map,
+            None::<Event>,
+            None::<&mut Event>,
+        )
+        .unwrap();
+        core::finish(&gpu_context.queue_b).unwrap();
+        return vec![view];
+    }
+
+    // the rows are read asynchronously, the view lives until queue_b is done
+    for scoop in 0..NUM_SCOOPS {
+        let row = view.scoop_mut(scoop);
+        let offset = scoop * row.len();
+        unsafe {
+            core::enqueue_read_buffer(
+                &gpu_context.queue_b,
+                buffer_scoops,
+                false,
+                offset,
+                row,
+                None::<Event>,
+                None::<&mut Event>,
+            )
+            .unwrap();
+        }
+    }
+    core::finish(&gpu_context.queue_b).unwrap();
+    vec![view]
+}
+
+// Use padded worksize for mapping to match gdim1
+fn mem_map_gpu_to_host(buffer_id: u8, gpu_context: &GpuContext) -> core::MemMap<u8> {
+    unsafe {
+        if buffer_id == 1 {
+            core::enqueue_map_buffer::<u8, _, _, _>(
+                &gpu_context.queue_b,
+                &gpu_context.buffer_gpu_a,
+                true,
+                core::MAP_READ,
+                0,
+                gpu_context.worksize * NONCE_SIZE as usize,
+                None::<Event>,
+                None::<&mut Event>,
+            )
+            .unwrap()
+        } else {
+            core::enqueue_map_buffer::<u8, _, _, _>(
+                &gpu_context.queue_b,
+                &gpu_context.buffer_gpu_b,
+                true,
+                core::MAP_READ,
+                0,
+                gpu_context.worksize * NONCE_SIZE as usize,
+                None::<Event>,
+                None::<&mut Event>,
+            )
+            .unwrap()
+        }
+    }
+}
+
+fn mem_unmap_gpu_to_host(buffer_id: u8, gpu_context: &GpuContext, map: Option<core::MemMap<u8>>) {
+    if buffer_id == 1 {
+        core::enqueue_unmap_mem_object(
+            &gpu_context.queue_a,
+            &gpu_context.buffer_gpu_a,
+            &map.unwrap(),
+            None::<Event>,
+            None::<&mut Event>,
+        )
+        .unwrap()
+    } else {
+        core::enqueue_unmap_mem_object(
+            &gpu_context.queue_a,
+            &gpu_context.buffer_gpu_b,
+            &map.unwrap(),
+            None::<Event>,
+            None::<&mut Event>,
+        )
+        .unwrap()
+    };
+}
+
+fn mem_transfer_gpu_to_host(buffer_id: u8, gpu_context: &GpuContext, slice: &mut [u8]) {
+    unsafe {
+        if buffer_id == 1 {
+            core::enqueue_read_buffer(
+                &gpu_context.queue_b,
+                &gpu_context.buffer_gpu_a,
+                false,
+                0,
+                slice,
+                None::<Event>,
+                None::<&mut Event>,
+            )
+            .unwrap();
+        } else {
+            core::enqueue_read_buffer(
+                &gpu_context.queue_b,
+                &gpu_context.buffer_gpu_b,
+                false,
+                0,
+                slice,
+                None::<Event>,
+                None::<&mut Event>,
+            )
+            .unwrap();
+        }
+    }
+}
+
+// out of the gpu's 16 lane layout into the scoops of the task's nonces
+fn unpack_shuffle_scatter(
+    buffer: *const u8,
+    gpu_context: &GpuContext,
+    view: NonceView,
+) -> Vec<NonceView> {
+    let buffer = unsafe { from_raw_parts(buffer, gpu_context.worksize * NONCE_SIZE as usize) };
+    let mut chunks = view.chunks(MSHABAL512_VECTOR_SIZE);
+    chunks
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(chunk, view)| {
+            let n = chunk as u64 * MSHABAL512_VECTOR_SIZE;
+            // the last chunk may not fill all lanes
+            let lanes = view.nonces();
+            for i in 0..8192 {
+                let scoop = (i & 1) * (4095 - (i >> 1)) + ((i + 1) & 1) * (i >> 1);
+                let data = view.scoop_mut(scoop as usize);
+                for j in (0..32).step_by(4) {
+                    for k in 0..lanes {
+                        let data_offset = (k * 64 + (i & 1) * 32 + j) as usize;
+                        let buffer_offset = (n * NONCE_SIZE
+                            + (i * 32 + j) * MSHABAL512_VECTOR_SIZE
+                            + k * 4) as usize;
+                        data[data_offset..(data_offset + 4)]
+                            .clone_from_slice(&buffer[buffer_offset..(buffer_offset + 4)]);
+                    }
+                }
+            }
+        });
+    chunks
+}
+
+pub fn platform_info() {
+    let platform_ids = core::get_platform_ids().unwrap();
+    for (i, platform_id) in platform_ids.iter().enumerate() {
+        println!(
+            "OCL: platform {}, {} - {}",
+            i,
+            core::get_platform_info(platform_id, core::PlatformInfo::Name).unwrap().to_string(),
+            core::get_platform_info(platform_id, core::PlatformInfo::Version).unwrap().to_string()
+        );
+        let device_ids = core::get_device_ids(platform_id, None, None).unwrap();
+        for (j, device_id) in device_ids.iter().enumerate() {
+            println!(
+                "OCL:   device {}, {} - {}",
+                j,
+                core::get_device_info(device_id, DeviceInfo::Vendor).unwrap().to_string(),
+                core::get_device_info(device_id, DeviceInfo::Name).unwrap().to_string()
+            );
+
+            let cores = match core::get_device_info(device_id, DeviceInfo::MaxComputeUnits).unwrap() {
+                core::DeviceInfoResult::MaxComputeUnits(mcu) => mcu as usize,
+                _ => 1,
+            };
+            let kernel_workgroup_size = 256; // fallback
+            println!(
+                "OCL:     cores={},kernel_workgroupsize={}",
+                cores, kernel_workgroup_size
+            );
+        }
+        println!("OCL:");
+    }
+}
+
+// every device of every platform, indexed like get_device_ids without a type filter
+pub fn ocl_devices() -> Vec<OclDevice> {
+    let mut list = Vec::new();
+    for (platform_idx, platform) in core::get_platform_ids().unwrap_or_default().iter().enumerate() {
+        let device_ids = core::get_device_ids(platform, None, None).unwrap_or_default();
+        for (device_idx, device) in device_ids.iter().enumerate() {
+            let info = |x| {
+                core::get_device_info(device, x)
+                    .map(|x| x.to_string().trim().to_string())
+                    .unwrap_or_default()
+            };
+            let compute_units = match core::get_device_info(device, DeviceInfo::MaxComputeUnits) {
+                Ok(core::DeviceInfoResult::MaxComputeUnits(mcu)) => mcu as usize,
+                _ => 1,
+            };
+            list.push(OclDevice {
+                platform: platform_idx,
+                device: device_idx,
+                name: info(DeviceInfo::Name),
+                vendor: info(DeviceInfo::Vendor),
+                gpu: is_gpu(device),
+                compute_units,
+            });
+        }
+    }
+    list
+}
+
+// PlotterTask::kernel applied to each selected gpu
+pub fn kernel_tunings(gpus: &[SelectedGpu], settings: &[KernelSetting]) -> Result<Vec<KernelTuning>, String> {
+    let devices = ocl_devices();
+    gpus.iter()
+        .map(|x| KernelTuning::resolve(settings, x, &devices))
+        .collect()
+}
+
+// A selected gpu sized to its memory by gpu_get_info, its program already
+// built. gpu_init turns it into a context.
+pub struct GpuSetup {
+    program: GpuProgram,
+    cores: usize,
+    host_buffer: HostBuffer,
+}
+
+fn device_memory(device: &core::DeviceId) -> Option<DeviceMemory> {
+    let global = match core::get_device_info(device, DeviceInfo::GlobalMemSize) {
+        Ok(core::DeviceInfoResult::GlobalMemSize(x)) => x,
+        _ => return None,
+    };
+    let max_alloc = match core::get_device_info(device, DeviceInfo::MaxMemAllocSize) {
+        Ok(core::DeviceInfoResult::MaxMemAllocSize(x)) => x,
+        _ => return None,
+    };
+    // deprecated since OpenCL 2.0 but still reported, missing means discrete
+    let unified = match core::get_device_info(device, DeviceInfo::HostUnifiedMemory) {
+        Ok(core::DeviceInfoResult::HostUnifiedMemory(x)) => x,
+        _ => false,
+    };
+    Some(DeviceMemory {
+        global,
+        max_alloc,
+        unified,
+    })
+}
+
+// Sizes every selected gpu to its memory, with fewer cores than asked for if
+// need be, and returns the host memory they need and their setups for gpu_init
+pub fn gpu_get_info(
+    gpus: &[SelectedGpu],
+    tunings: &[KernelTuning],
+    zcb: bool,
+    host_shuffle: bool,
+    quiet: bool,
+    warnings: &mut Vec<String>,
+) -> Result<(u64, Vec<GpuSetup>), String> {
+    let mut total_mem_needed = 0u64;
+    let mut setups = Vec::new();
+
+    for (gpu, tuning) in gpus.iter().zip(tunings) {
+        let platform = core::get_platform_ids().unwrap()[gpu.platform];
+        let device = core::get_device_ids(&platform, None, None).unwrap()[gpu.device];
+
+        let max_compute_units = match core::get_device_info(&device, DeviceInfo::MaxComputeUnits) {
+            Ok(core::DeviceInfoResult::MaxComputeUnits(mcu)) => mcu as usize,
+            _ => return Err(format!("can't obtain the number of cores of {}", gpu.name)),
+        };
+
+        let memory = device_memory(&device)
+            .ok_or_else(|| format!("can't obtain the memory size of {}", gpu.name))?;
+
+        let program = GpuProgram::new(gpu, tuning)?;
+        let kernel = core::create_kernel(&program.program, "calculate_nonces").unwrap();
+        let max_workgroup_size = get_kernel_work_group_size(&kernel, device);
+        let kernel_workgroup_size = tuning.workgroup_size(max_workgroup_size);
+        if let Some(requested) = tuning.workgroup_size.filter(|&x| x > kernel_workgroup_size) {
+            warnings.push(format!(
+                "Warning: {} runs work groups of {} nonces, {} is more than the kernel allows.",
+                gpu.name, kernel_workgroup_size, requested
+            ));
+        }
+
+        let vendor = core::get_device_info(&device, DeviceInfo::Vendor).unwrap().to_string();
+        // as GpuContext::new decides, a device without the shuffle kernel unpacks on the host
+        let device_shuffle = !host_shuffle && core::create_kernel(&program.program, "shuffle_scoops").is_ok();
+
+        let requested = match gpu.cores {
+            Some(cores) => min(cores, max_compute_units),
+            None => max_compute_units,
+        };
+        let (host_buffer, gpu_cores) =
+            memory.host_buffer(requested, kernel_workgroup_size, zcb, device_shuffle, NONCE_SIZE);
+        // the others still plot
+        if gpu_cores == 0 {
+            warnings.push(format!(
+                "Warning: not enough memory on {} for a work group of {} nonces, it's left out.",
+                gpu.name, kernel_workgroup_size
+            ));
+            continue;
+        }
+        if gpu_cores < requested {
+            warnings.push(format!(
+                "Warning: {} uses {} of {} cores, more don't fit its memory.",
+                gpu.name, gpu_cores, requested
+            ));
+        }
+
+        let buffer_size = (gpu_cores * kernel_workgroup_size) as u64 * NONCE_SIZE;
+        let device_mem_needed = host_buffer.device_buffers(device_shuffle) as u64 * buffer_size;
+        let mem_needed = host_buffer.host_buffers(device_shuffle) * buffer_size;
+
+        if !quiet {
+            println!(
+                "GPU: {} - {} [using {} of {} cores]",
+                vendor,
+                gpu.name,
+                gpu_cores,
+                max_compute_units
+            );
+            println!(
+                "     GPU-RAM: Total={:.2} GiB, Usage={:.2} GiB{}",
+                memory.global as f64 / 1024.0 / 1024.0 / 1024.0,
+                device_mem_needed as f64 / 1024.0 / 1024.0 / 1024.0,
+                match host_buffer {
+                    HostBuffer::Mapped => ", zero-copy",
+                    HostBuffer::Copied => "",
+                    HostBuffer::Shared => ", hashing into host memory",
+                },
+            );
+            if *tuning != KernelTuning::default() {
+                println!(
+                    "     Kernel: {}, {} hashes per run, work groups of {}{}",
+                    tuning.file.as_deref().unwrap_or("built-in"),
+                    tuning.hashes_per_run,
+                    kernel_workgroup_size,
+                    if tuning.options.is_empty() {
+                        String::new()
+                    } else {
+                        format!(", options {}", tuning.options)
+                    },
+                );
+            }
+        }
+
+        total_mem_needed += mem_needed;
+        setups.push(GpuSetup {
+            program,
+            cores: gpu_cores,
+            host_buffer,
+        });
+    }
+
+    Ok((total_mem_needed, setups))
+}
+
+pub fn gpu_init(setups: Vec<GpuSetup>, host_shuffle: bool) -> Vec<Arc<Mutex<GpuContext>>> {
+    setups
+        .into_iter()
+        .map(|x| {
+            Arc::new(Mutex::new(GpuContext::new(
+                x.program,
+                x.cores,
+                x.host_buffer,
+                host_shuffle,
+            )))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod ocl_tests {
+    use super::*;
+    use crate::buffer::PageAlignedByteBuffer;
+    use crate::gpu_selector::select_gpus;
+    use crate::poc_hashing::noncegen_rust;
+
+    const NUMERIC_ID: u64 = 7900104405094198526;
+
+    // Plots through the kernel on the first OpenCL device, CI has PoCL and no
+    // GPUs, and compares with the rust reference. Two tasks run through the
+    // hasher thread's sequence: hash, hash and transfer, transfer. Testing the
+    // opencl feature without an OpenCL runtime fails.
+    fn plot_matches_rust(host_buffer: HostBuffer, host_shuffle: bool, tuning: &KernelTuning) {
+        let device = match select_gpus(&["0".to_string()]) {
+            Ok(x) => x[0].clone(),
+            Err(e) => panic!("no OpenCL device to test with, install PoCL or a GPU runtime: {}", e),
+        };
+        // work groups of at least 16, the tasks leave the last lanes empty
+        let program = GpuProgram::new(&device, tuning).unwrap();
+        let gpu = Arc::new(Mutex::new(GpuContext::new(program, 1, host_buffer, host_shuffle)));
+
+        let mut expected = PageAlignedByteBuffer::new(20 * NONCE_SIZE as usize);
+        let mut plotted = PageAlignedByteBuffer::new(20 * NONCE_SIZE as usize);
+        expected.as_mut_slice().fill(0);
+        plotted.as_mut_slice().fill(0);
+        noncegen_rust(&mut expected.lend(1..19), NUMERIC_ID, 1337);
+
+        let first = HashTask {
+            view: plotted.lend(1..10),
+            numeric_id: NUMERIC_ID,
+            local_startnonce: 1337,
+        };
+        let second = HashTask {
+            view: plotted.lend(10..19),
+            numeric_id: NUMERIC_ID,
+            local_startnonce: 1346,
+        };
+        gpu_hash(&gpu, &first);
+        gpu_hash_and_transfer_to_host(&gpu, 1, &second, first);
+        gpu_transfer_to_host(&gpu, 0, second);
+        assert!(expected.as_slice() == plotted.as_slice(), "{} differs", device.name);
+    }
+
+    #[test]
+    fn kernel_matches_rust() {
+        plot_matches_rust(HostBuffer::Copied, true, &KernelTuning::default());
+        plot_matches_rust(HostBuffer::Shared, true, &KernelTuning::default());
+    }
+
+    #[test]
+    fn kernel_matches_rust_zero_copy() {
+        plot_matches_rust(HostBuffer::Mapped, true, &KernelTuning::default());
+    }
+
+    #[test]
+    fn device_shuffle_matches_rust() {
+        plot_matches_rust(HostBuffer::Copied, false, &KernelTuning::default());
+        plot_matches_rust(HostBuffer::Mapped, false, &KernelTuning::default());
+    }
+
+    #[test]
+    fn tuned_kernel_matches_rust() {
+        let tuning = KernelTuning {
+            file: None,
+            options: "-cl-mad-enable".to_string(),
+            hashes_per_run: 256,
+            workgroup_size: Some(16),
+        };
+        plot_matches_rust(HostBuffer::Copied, false, &tuning);
+    }
+}
@@ -1,406 +1,1042 @@
-use humanize_rs::bytes::Bytes;
-use pbr::{MultiBar, Units};
-use raw_cpuid::CpuId;
-
-use crate::cpu_hasher::{SimdExtension, init_simd};
-use crate::buffer::PageAlignedByteBuffer;
-#[cfg(feature = "opencl")]
-use crate::ocl::gpu_get_info;
-use crate::scheduler::create_scheduler_thread;
-#[cfg(windows)]
-use crate::utils::set_thread_ideal_processor;
-use crate::utils::{free_disk_space, get_sector_size, preallocate};
-use crate::writer::{create_writer_thread, read_resume_info, write_resume_info};
-use core_affinity;
-use crossbeam_channel::bounded;
-use std::cmp::{max, min};
-use std::path::Path;
-use std::process;
-use std::sync::Arc;
-use std::thread;
-use stopwatch::Stopwatch;
-
-pub const SCOOP_SIZE: u64 = 64;
-pub const NUM_SCOOPS: u64 = 4096;
-pub const NONCE_SIZE: u64 = SCOOP_SIZE * NUM_SCOOPS;
-
-pub struct Plotter {}
-
-pub struct PlotterTask {
-    pub numeric_id: u64,
-    pub start_nonce: u64,
-    pub nonces: u64,
-    pub output_path: String,
-    pub mem: String,
-    pub cpu_threads: u8,
-    pub gpus: Option<Vec<String>>,
-    pub direct_io: bool,
-    pub async_io: bool,
-    pub quiet: bool,
-    pub benchmark: bool,
-    pub zcb: bool,
-}
-
-impl Plotter {
-    pub fn new() -> Plotter {
-        Plotter {}
-    }
-
-    pub fn run(self, mut task: PlotterTask) {
-        let cpuid = CpuId::new();
-        let cpu_name = cpuid.get_extended_function_info().unwrap();
-        let cpu_name = cpu_name.processor_brand_string().unwrap().trim();
-        let cores = sys_info::cpu_num().unwrap();
-        let memory = sys_info::mem_info().unwrap();
-
-        let simd_ext = init_simd();
-
-        if !task.quiet {
-            println!("signum-plotter {}\n", env!("CARGO_PKG_VERSION"));
-        }
-
-        if !task.quiet && task.benchmark {
-            println!("*BENCHMARK MODE*\n");
-        }
-
-        if !task.quiet {
-            println!(
-                "CPU: {} [using {} of {} cores{}{:?}]",
-                cpu_name,
-                task.cpu_threads,
-                cores,
-                if let SimdExtension::None = &simd_ext { "" } else { " + " },
-                &simd_ext
-            );
-        }
-
-        #[cfg(not(feature = "opencl"))]
-        let gpu_mem_needed = 0u64;
-        #[cfg(feature = "opencl")]
-        let gpu_mem_needed = match &task.gpus {
-            Some(x) => gpu_get_info(&x, task.quiet),
-            None => 0,
-        };
-
-        #[cfg(feature = "opencl")]
-        let gpu_mem_needed = if task.zcb {
-            gpu_mem_needed
-        } else {
-            gpu_mem_needed / 2
-        };
-
-        // use all available disk space if nonce parameter has been omitted
-        let free_disk_space = free_disk_space(&task.output_path);
-        if task.nonces == 0 {
-            task.nonces = free_disk_space / NONCE_SIZE;
-        }
-
-        let gpu = task.gpus.is_some();
-
-        // align number of nonces with sector size if direct i/o
-        let mut rounded_nonces_to_sector_size = false;
-        let mut nonces_per_sector = 1;
-        if task.direct_io {
-            let sector_size = get_sector_size(&task.output_path);
-            nonces_per_sector = sector_size / SCOOP_SIZE;
-            if task.nonces % nonces_per_sector > 0 {
-                rounded_nonces_to_sector_size = true;
-                task.nonces /= nonces_per_sector;
-                task.nonces *= nonces_per_sector;
-            }
-        }
-
-        let plotsize = task.nonces * NONCE_SIZE;
-
-        let file = Path::new(&task.output_path).join(format!(
-            "{}_{}_{}",
-            task.numeric_id, task.start_nonce, task.nonces
-        ));
-
-        if !file.parent().unwrap().exists() {
-            println!(
-                "Error: specified target path does not exist, path={}",
-                &task.output_path
-            );
-            println!("Shutting down...");
-            return;
-        }
-
-        // check available disk space
-        if free_disk_space < plotsize && !file.exists() && !task.benchmark {
-            println!(
-                "Error: insufficient disk space, MiB_required={:.2}, MiB_available={:.2}",
-                plotsize as f64 / 1024.0 / 1024.0,
-                free_disk_space as f64 / 1024.0 / 1024.0
-            );
-            println!("Shutting down...");
-            return;
-        }
-
-        // calculate memory usage
-        let mem = match calculate_mem_to_use(&task, &memory, nonces_per_sector, gpu, gpu_mem_needed)
-        {
-            Ok(x) => x,
-            Err(_) => return,
-        };
-
-        if !task.quiet {
-            println!(
-                "RAM: Total={:.2} GiB, Free={:.2} GiB, Usage={:.2} GiB",
-                memory.total as f64 / 1024.0 / 1024.0,
-                get_avail_mem(&memory) as f64 / 1024.0 / 1024.0,
-                (mem + gpu_mem_needed) as f64 / 1024.0 / 1024.0 / 1024.0
-            );
-
-            #[cfg(feature = "opencl")]
-            println!(
-                "     HDDcache={:.2} GiB, GPUcache={:.2} GiB,\n",
-                mem as f64 / 1024.0 / 1024.0 / 1024.0,
-                gpu_mem_needed as f64 / 1024.0 / 1024.0 / 1024.0
-            );
-
-            println!("Numeric ID:  {}", task.numeric_id);
-            println!("Start Nonce: {}", task.start_nonce);
-            println!(
-                "Nonces:      {}{}",
-                task.nonces,
-                if rounded_nonces_to_sector_size {
-                    " (rounded to sector size for fast direct i/o)"
-                } else {
-                    ""
-                }
-            );
-        }
-
-        if !task.quiet {
-            println!("Output File: {}\n", file.display());
-        }
-
-        let mut progress = 0;
-        if file.exists() {
-            if !task.quiet {
-                println!("File already exists, reading resume info...");
-            }
-            let resume_info = read_resume_info(&file);
-            match resume_info {
-                Ok(x) => progress = x,
-                Err(_) => {
-                    println!("Error: couldn't read resume info from file '{}'", file.display());
-                    println!("If you are sure that this file is incomplete \
-                              or corrupted, then delete it before continuing.");
-                    println!("Shutting Down...");
-                    return;
-                }
-            }
-            if !task.quiet {
-                println!("OK");
-            }
-        } else {
-            if !task.quiet {
-                print!("Fast file pre-allocation...");
-            }
-            if !task.benchmark {
-                preallocate(&file, plotsize, task.direct_io);
-                if write_resume_info(&file, 0u64).is_err() {
-                    println!("Error: couldn't write resume info");
-                }
-            }
-            if !task.quiet {
-                println!("OK");
-            }
-        }
-
-        if !task.quiet {
-            if progress == 0 {
-                println!("Starting plotting...\n");
-            } else {
-                println!("Resuming plotting from nonce offset {}...\n", progress);
-            }
-        }
-
-        // determine buffer size
-        let num_buffer = if task.async_io { 2 } else { 1 };
-        let buffer_size = mem / num_buffer;
-        let (tx_empty_buffers, rx_empty_buffers) = bounded(num_buffer as usize);
-        let (tx_full_buffers, rx_full_buffers) = bounded(num_buffer as usize);
-
-        for _ in 0..num_buffer {
-            let buffer = PageAlignedByteBuffer::new(buffer_size as usize);
-            tx_empty_buffers.send(buffer).unwrap();
-        }
-
-        let mb = MultiBar::new();
-
-        let p1x = if !task.quiet {
-            let mut p1 = mb.create_bar(plotsize - progress * NONCE_SIZE);
-            p1.format("│██░│");
-            p1.set_units(Units::Bytes);
-            p1.message("Hashing: ");
-            p1.show_counter = false;
-            p1.set(0);
-            Some(p1)
-        } else {
-            None
-        };
-
-        let p2x = if !task.quiet {
-            let mut p2 = mb.create_bar(plotsize - progress * NONCE_SIZE);
-            p2.format("│██░│");
-            p2.set_units(Units::Bytes);
-            p2.message("Writing: ");
-            p2.show_counter = false;
-            p2.set(0);
-            Some(p2)
-        } else {
-            None
-        };
-
-        let sw = Stopwatch::start_new();
-        let task = Arc::new(task);
-
-        // hi bold! might make this optional in future releases.
-        let thread_pinning = true;
-        let core_ids = if thread_pinning {
-            core_affinity::get_core_ids().unwrap()
-        } else {
-            Vec::new()
-        };
-
-        let hasher = thread::spawn({
-            create_scheduler_thread(
-                task.clone(),
-                rayon::ThreadPoolBuilder::new()
-                    .num_threads(task.cpu_threads as usize)
-                    .start_handler(move |id| {
-                        if thread_pinning {
-                            #[cfg(not(windows))]
-                            let core_id = core_ids[id % core_ids.len()];
-                            #[cfg(not(windows))]
-                            core_affinity::set_for_current(core_id);
-                            #[cfg(windows)]
-                            set_thread_ideal_processor(id % core_ids.len());
-                        }
-                    })
-                    .build()
-                    .unwrap(),
-                progress,
-                p1x,
-                rx_empty_buffers.clone(),
-                tx_full_buffers.clone(),
-                simd_ext,
-            )
-        });
-
-        let writer = thread::spawn({
-            create_writer_thread(
-                task.clone(),
-                progress,
-                p2x,
-                rx_full_buffers.clone(),
-                tx_empty_buffers.clone(),
-            )
-        });
-
-        if !task.quiet {
-            mb.listen();
-        }
-        writer.join().unwrap();
-        hasher.join().unwrap();
-
-        let elapsed = sw.elapsed_ms() as u64;
-        let hours = elapsed / 1000 / 60 / 60;
-        let minutes = elapsed / 1000 / 60 - hours * 60;
-        let seconds = elapsed / 1000 - hours * 60 * 60 - minutes * 60;
-
-        if !task.quiet {
-            println!(
-                "\nGenerated {} nonces in {}h{:02}m{:02}s, {:.2} MiB/s, {:.0} nonces/m.",
-                task.nonces - progress,
-                hours,
-                minutes,
-                seconds,
-                (task.nonces - progress) as f64 * 1000.0 / (elapsed as f64 + 1.0) / 4.0,
-                (task.nonces - progress) as f64 * 1000.0 / (elapsed as f64 + 1.0) * 60.0
-            );
-        }
-    }
-}
-
-fn calculate_mem_to_use(
-    task: &PlotterTask,
-    memory: &sys_info::MemInfo,
-    nonces_per_sector: u64,
-    gpu: bool,
-    gpu_mem_needed: u64,
-) -> Result<u64, &'static str> {
-    let plotsize = task.nonces * NONCE_SIZE;
-
-    let mut mem = match task.mem.parse::<Bytes>() {
-    Ok(x) => x.size() as u64,
-    Err(_) => {
-        println!(
-            "Error: Can't parse memory limit parameter, input={}",
-            task.mem,
-        );
-        println!("\nPlease specify a number followed by a unit. If no unit is provided, bytes will be assumed.");
-        println!("Supported units: B, KiB, MiB, GiB, TiB, PiB, EiB, KB, MB, GB, TB, PB, EB");
-        println!("Example: --mem 10GiB\n");
-        println!("Shutting down...");
-        return Err("invalid unit");
-    }
-};
-
-// If no memory limit specified (or "0B"), use full plot size for buffer
-if mem == 0 {
-    mem = plotsize;
-}
-    if gpu && mem > 0 {
-    let min_required = gpu_mem_needed + nonces_per_sector * NONCE_SIZE;
-    if mem < min_required {
-        println!("Warning: Requested host memory ({:.2} GiB) is less than recommended ({:.2} GiB) for GPU plotting.", 
-                 mem as f64 / 1024.0 / 1024.0 / 1024.0, 
-                 min_required as f64 / 1024.0 / 1024.0 / 1024.0);
-        println!("Continuing anyway — may be slower or unstable.");
-        // Don't exit — let it try
-    }
-}
-
-    if gpu && mem > 0 {
-        mem -= gpu_mem_needed;
-    }
-
-    if mem == 0 {
-        mem = plotsize;
-    }
-    mem = min(mem, plotsize + gpu_mem_needed);
-
-    // opencl requires buffer to be a multiple of 16 (data coalescence magic)
-    let nonces_per_sector = if gpu {
-        max(16, nonces_per_sector)
-    } else {
-        nonces_per_sector
-    };
-
-    // don't exceed free memory and leave some elbow room 1-1000/1024
-    mem = min(mem, get_avail_mem(&memory) * 1000 - gpu_mem_needed);
-
-    // rounding single/double buffer
-    let num_buffer = if task.async_io { 2 } else { 1 };
-    mem /= num_buffer * NONCE_SIZE * nonces_per_sector;
-    mem *= num_buffer * NONCE_SIZE * nonces_per_sector;
-
-    // ensure a minimum buffer
-    mem = max(mem, num_buffer * NONCE_SIZE * nonces_per_sector);
-    Ok(mem)
-}
-
-// sys_info ex, displays 0 avail on win
-#[cfg(not(windows))]
-fn get_avail_mem(memory: &sys_info::MemInfo) -> u64 {
-    memory.avail
-}
-
-#[cfg(windows)]
-fn get_avail_mem(memory: &sys_info::MemInfo) -> u64 {
-    memory.free
-}
\ No newline at end of file
+use humanize_rs::bytes::Bytes;
+use pbr::{MultiBar, Units};
+
+use crate::cpu_hasher::{init_simd_with, self_test, CpuPool, SimdExtension};
+#[cfg(feature = "opencl")]
+use crate::cpu_hasher::Rehasher;
+use crate::lanes::lanes;
+use crate::buffer::{Allocation, HugePages, PageAlignedByteBuffer};
+use crate::cgroup::CgroupLimits;
+use crate::gpu_check::GpuCheck;
+use crate::kernel_tuning::KernelSetting;
+#[cfg(feature = "opencl")]
+use crate::ocl::{gpu_get_info, gpu_init, kernel_tunings};
+#[cfg(feature = "opencl")]
+use crate::gpu_selector::select_gpus;
+#[cfg(feature = "opencl")]
+use crate::gpu_hasher::GpuDevice;
+use crate::numa::{self, bind_memory, numa_nodes, NumaNode};
+use crate::pressure::MemoryGovernor;
+use crate::queue::{DepthGovernor, MeteredReceiver};
+use crate::report::{device_reports, queue_report, writer_report, BufferReport, CpuReport, Report, WaitReport};
+use crate::topology::{cpu_topology, order_cpus, PinningPolicy};
+use crate::scheduler::{create_scheduler_thread, HashDevice};
+#[cfg(windows)]
+use crate::utils::set_thread_ideal_processor;
+use crate::utils::{cpu_brand, free_disk_space, get_sector_size, preallocate};
+use crate::write_backend::WriteBackendKind;
+use crate::writer::{create_writer_thread, read_resume_info, write_resume_info, WriterStats};
+use core_affinity;
+use crossbeam_channel::bounded;
+use std::cmp::{max, min};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use stopwatch::Stopwatch;
+
+pub const SCOOP_SIZE: u64 = 64;
+pub const NUM_SCOOPS: u64 = 4096;
+pub const NONCE_SIZE: u64 = SCOOP_SIZE * NUM_SCOOPS;
+
+const AUTO_MIN_BUFFER_SIZE: u64 = 1024 * 1024 * 1024;
+const AUTO_MIN_BUFFERS: u64 = 2;
+const AUTO_MAX_BUFFERS: u64 = 4;
+
+pub struct Plotter {}
+
+#[derive(Clone)]
+pub struct PlotterTask {
+    pub numeric_id: u64,
+    pub start_nonce: u64,
+    pub nonces: u64,
+    pub output_path: String,
+    pub mem: String,
+    // 0 uses all available cores, or all cpus of an explicit pinning list
+    pub cpu_threads: u32,
+    // hash on the gpus alone, without a cpu pool
+    pub gpu_only: bool,
+    // gpu selectors, see gpu_selector, None hashes on the cpu only
+    pub gpus: Option<Vec<String>>,
+    pub direct_io: bool,
+    pub async_io: bool,
+    pub quiet: bool,
+    pub benchmark: bool,
+    #[cfg_attr(not(feature = "opencl"), allow(dead_code))]
+    pub zcb: bool,
+    // unpack gpu output on the cpu as before instead of shuffling on the gpu
+    #[cfg_attr(not(feature = "opencl"), allow(dead_code))]
+    pub host_shuffle: bool,
+    // re-hash a share of every gpu task on the cpu to catch unstable gpus
+    #[cfg_attr(not(feature = "opencl"), allow(dead_code))]
+    pub gpu_check: Option<GpuCheck>,
+    // kernel file, build options, hashes per run and work group size, per gpu
+    pub kernel: Vec<KernelSetting>,
+    // directory for the two-phase mode: hash to a temp file there, then transpose
+    // scoop by scoop into the plot file so its drive only sees sequential writes
+    pub transpose_path: Option<String>,
+    pub write_backend: WriteBackendKind,
+    // in-flight buffers per plot file, 0 picks a depth from the memory available
+    // and adapts it to the queue waits while plotting
+    pub buffers: u64,
+    pub huge_pages: HugePages,
+    pub lock_memory: bool,
+    pub pinning: PinningPolicy,
+    // shrink buffers while the system runs short of memory, grow them back later
+    pub adaptive_memory: bool,
+    // json report of the run, "-" for stdout
+    pub report_path: Option<String>,
+    // hashing backend, None picks the best the cpu supports
+    pub simd: Option<SimdExtension>,
+}
+
+impl Plotter {
+    pub fn new() -> Plotter {
+        Plotter {}
+    }
+
+    // Plots one or more files from a single hashing pipeline. Hashing settings
+    // (cpu, gpu, memory, i/o mode) are taken from the first task; every file gets
+    // its own writer thread, so write throughput scales with the number of drives.
+    // Errs with the reason when it can't plot, nothing is hashed then.
+    pub fn run(self, mut tasks: Vec<PlotterTask>) -> Result<RunStats, String> {
+        if tasks.is_empty() {
+            return Err("nothing to plot".to_string());
+        }
+
+        let cpu_name = cpu_brand();
+        // containers and systemd slices may grant less than the host has
+        let limits = CgroupLimits::detect();
+        let host_cores = sys_info::cpu_num().unwrap();
+        let cores = limits
+            .cpus()
+            .map_or(host_cores, |x| min(x as u32, host_cores));
+        let memory = limits.apply(sys_info::mem_info().unwrap());
+
+        let quiet = tasks[0].quiet;
+        let mut summary = Summary {
+            quiet,
+            lines: Vec::new(),
+        };
+
+        let simd_ext = init_simd_with(tasks[0].simd.clone())?;
+        // never plot with a backend that computes wrong hashes
+        if let Err(e) = self_test(&simd_ext) {
+            return Err(format!("hashing self-test failed, {}", e));
+        }
+        let benchmark = tasks[0].benchmark;
+
+        if !quiet {
+            println!("signum-plotter {}\n", env!("CARGO_PKG_VERSION"));
+        }
+
+        if !quiet && benchmark {
+            println!("*BENCHMARK MODE*\n");
+        }
+
+        // order every node's cpus by the pinning policy, nodes left without cpus don't hash
+        let topology = cpu_topology();
+        let pinning = tasks[0].pinning.clone();
+        let nodes: Vec<NumaNode> = numa_nodes()
+            .into_iter()
+            .map(|mut node| {
+                if let Some(cpuset) = &limits.cpuset {
+                    node.cpus.retain(|x| cpuset.contains(x));
+                }
+                node.cpus = order_cpus(&node.cpus, &pinning, &topology);
+                node
+            })
+            .filter(|node| !node.cpus.is_empty())
+            .collect();
+        if nodes.is_empty() {
+            return Err(format!("none of the cpus to pin to exist, pinning={}", pinning));
+        }
+
+        if limits.is_limited() {
+            summary.add(format!("Limits: {}", limits));
+        }
+        if tasks[0].cpu_threads == 0 {
+            let threads = default_threads(cores, &pinning, &nodes);
+            for task in tasks.iter_mut() {
+                task.cpu_threads = threads;
+            }
+        } else if tasks[0].cpu_threads > cores {
+            if !quiet {
+                println!(
+                    "Warning: {} cpu threads requested, limited to the {} available",
+                    tasks[0].cpu_threads, cores
+                );
+            }
+            for task in tasks.iter_mut() {
+                task.cpu_threads = cores;
+            }
+        }
+
+        let cpu_threads = if tasks[0].gpu_only { 0 } else { tasks[0].cpu_threads };
+
+        let simd = match simd_ext {
+            SimdExtension::None => format!("Rust x{}", lanes(&simd_ext)),
+            _ if cfg!(c_simd) => format!("{:?}", simd_ext),
+            _ => format!("Rust {:?} x{}", simd_ext, lanes(&simd_ext)),
+        };
+        if !quiet {
+            println!(
+                "CPU: {} [using {} of {} cores + {}]",
+                cpu_name, cpu_threads, cores, simd
+            );
+        }
+
+        if !quiet {
+            println!("Pinning: {}", pinning);
+            if let PinningPolicy::Explicit(list) = &pinning {
+                let unknown: Vec<String> = list
+                    .iter()
+                    .filter(|cpu| !nodes.iter().any(|node| node.cpus.contains(cpu)))
+                    .map(|cpu| cpu.to_string())
+                    .collect();
+                if !unknown.is_empty() {
+                    println!("Warning: ignoring unknown cpus {}", unknown.join(","));
+                }
+            }
+        }
+
+        // the same resolution for the gui, the cli and autotune
+        #[cfg(feature = "opencl")]
+        let gpus = tasks[0].gpus.as_ref().map(|x| select_gpus(x)).transpose()?;
+        #[cfg(feature = "opencl")]
+        let tunings = gpus
+            .as_ref()
+            .map(|x| kernel_tunings(x, &tasks[0].kernel))
+            .transpose()?
+            .unwrap_or_default();
+
+        #[cfg(not(feature = "opencl"))]
+        let gpu_mem_needed = 0u64;
+        // sized to their memory, with the programs built on the way reused by gpu_init
+        #[cfg(feature = "opencl")]
+        let (gpu_mem_needed, gpu_setups) = match &gpus {
+            Some(x) => {
+                let mut warnings = Vec::new();
+                let info = gpu_get_info(x, &tunings, tasks[0].zcb, tasks[0].host_shuffle, quiet, &mut warnings);
+                for warning in warnings {
+                    summary.add(warning);
+                }
+                info?
+            }
+            None => (0, Vec::new()),
+        };
+
+        let gpu = tasks[0].gpus.is_some();
+
+        let mut files = Vec::new();
+        let mut nonces_per_sector = 1;
+        for task in tasks.iter_mut() {
+            let file = prepare_plot_file(task)?;
+            nonces_per_sector = max(nonces_per_sector, file.nonces_per_sector);
+            files.push(file);
+        }
+
+        // calculate memory usage, every file gets its own set of buffers
+        let plotsize = tasks.iter().map(|x| x.nonces * NONCE_SIZE).sum();
+        let (mem, num_buffer) = calculate_mem_to_use(
+            &tasks[0],
+            plotsize,
+            tasks.len() as u64,
+            &memory,
+            nonces_per_sector,
+            gpu,
+            gpu_mem_needed,
+        )?;
+
+        if !quiet {
+            println!(
+                "RAM: Total={:.2} GiB, Free={:.2} GiB, Usage={:.2} GiB",
+                memory.total as f64 / 1024.0 / 1024.0,
+                get_avail_mem(&memory) as f64 / 1024.0 / 1024.0,
+                (mem + gpu_mem_needed) as f64 / 1024.0 / 1024.0 / 1024.0
+            );
+            println!(
+                "     Buffers={} per file{}",
+                num_buffer,
+                if tasks[0].buffers == 0 { " (auto)" } else { "" }
+            );
+
+            #[cfg(feature = "opencl")]
+            println!(
+                "     HDDcache={:.2} GiB, GPUcache={:.2} GiB,\n",
+                mem as f64 / 1024.0 / 1024.0 / 1024.0,
+                gpu_mem_needed as f64 / 1024.0 / 1024.0 / 1024.0
+            );
+        }
+
+        for (task, plot_file) in tasks.iter().zip(files.iter_mut()) {
+            if !quiet {
+                println!("Numeric ID:  {}", task.numeric_id);
+                println!("Start Nonce: {}", task.start_nonce);
+                println!(
+                    "Nonces:      {}{}",
+                    task.nonces,
+                    if plot_file.rounded_nonces_to_sector_size {
+                        " (rounded to sector size for fast direct i/o)"
+                    } else {
+                        ""
+                    }
+                );
+                println!("Writer:      {}", task.write_backend);
+                if let Some(dir) = &task.transpose_path {
+                    println!("Output File: {}", plot_file.path.display());
+                    println!("Transposing: via {}\n", dir);
+                } else {
+                    println!("Output File: {}\n", plot_file.path.display());
+                }
+            }
+
+            plot_file.progress = resume_or_preallocate(task, &plot_file.path)?;
+
+            if !quiet {
+                if plot_file.progress == 0 {
+                    println!("Starting plotting...\n");
+                } else {
+                    println!(
+                        "Resuming plotting from nonce offset {}...\n",
+                        plot_file.progress
+                    );
+                }
+            }
+        }
+
+        // files that are already complete don't take part in hashing
+        let mut active_tasks = Vec::new();
+        let mut active_files = Vec::new();
+        let mut progress = Vec::new();
+        for (task, plot_file) in tasks.into_iter().zip(files.iter()) {
+            if plot_file.progress < task.nonces {
+                progress.push(plot_file.progress);
+                active_files.push(plot_file.path.clone());
+                active_tasks.push(Arc::new(task));
+            }
+        }
+        if active_tasks.is_empty() {
+            if !quiet {
+                println!("Nothing to do, all plot files are complete.");
+            }
+            return Ok(RunStats {
+                nonces: 0,
+                elapsed_ms: 0,
+                summary: Vec::new(),
+            });
+        }
+
+        // determine buffer size
+        let unit = buffer_unit(nonces_per_sector, gpu);
+        let buffer_size = buffer_size(mem, num_buffer * active_tasks.len() as u64, unit);
+        let (tx_empty_buffers, rx_empty_buffers) =
+            bounded((num_buffer as usize) * active_tasks.len());
+        let (rx_empty_buffers, hasher_stats) = MeteredReceiver::new(rx_empty_buffers);
+        let mut tx_full_buffers = Vec::new();
+        let mut rx_full_buffers = Vec::new();
+        let mut writer_stats = Vec::new();
+        for _ in 0..active_tasks.len() {
+            let (tx, rx) = bounded(num_buffer as usize);
+            let (rx, stats) = MeteredReceiver::new(rx);
+            tx_full_buffers.push(tx);
+            rx_full_buffers.push(rx);
+            writer_stats.push(stats);
+        }
+
+        // hashing threads are spread round-robin over the numa nodes, every node
+        // with threads gets a pinned pool and its own stripe of every buffer
+        let mut node_threads = vec![0u64; nodes.len()];
+        for i in 0..cpu_threads as usize {
+            node_threads[i % nodes.len()] += 1;
+        }
+        let pool_nodes: Vec<(NumaNode, u64)> = nodes
+            .into_iter()
+            .zip(node_threads)
+            .filter(|x| x.1 > 0)
+            .collect();
+        let pool_threads: Vec<u64> = pool_nodes.iter().map(|x| x.1).collect();
+        // without pinning the threads move freely, their memory doesn't follow them
+        let bind_numa = pool_nodes.len() > 1 && pinning != PinningPolicy::None;
+        let mut numa_bound = bind_numa;
+
+        // interleave buffers of all files so hashing alternates between drives
+        let requested = Allocation {
+            pages: active_tasks[0].huge_pages,
+            locked: active_tasks[0].lock_memory,
+        };
+        let mut allocations = Vec::new();
+        for _ in 0..num_buffer {
+            for drive in 0..active_tasks.len() {
+                let mut buffer = PageAlignedByteBuffer::with_allocation(buffer_size as usize, requested);
+                if !allocations.contains(&buffer.allocation()) {
+                    allocations.push(buffer.allocation());
+                }
+                if bind_numa {
+                    numa_bound &= bind_buffer_to_nodes(&mut buffer, &pool_nodes);
+                }
+                tx_empty_buffers.send((drive, buffer)).unwrap();
+            }
+        }
+
+        // resized and added buffers get the same placement as the initial ones
+        let allocate = {
+            let pool_nodes = pool_nodes.clone();
+            move |size| {
+                let mut buffer = PageAlignedByteBuffer::with_allocation(size, requested);
+                if bind_numa {
+                    bind_buffer_to_nodes(&mut buffer, &pool_nodes);
+                }
+                buffer
+            }
+        };
+        let governor = if active_tasks[0].adaptive_memory {
+            Some(Arc::new(MemoryGovernor::new(
+                buffer_size,
+                unit,
+                num_buffer * active_tasks.len() as u64,
+                Box::new(allocate.clone()),
+            )))
+        } else {
+            None
+        };
+        // a depth picked automatically follows the queue waits
+        let depth = if active_tasks[0].buffers == 0 && num_buffer > AUTO_MIN_BUFFERS {
+            Some(Arc::new(DepthGovernor::new(
+                AUTO_MIN_BUFFERS,
+                num_buffer,
+                hasher_stats.clone(),
+                writer_stats.clone(),
+                Box::new(allocate),
+            )))
+        } else {
+            None
+        };
+
+        for allocation in &allocations {
+            if *allocation == requested {
+                summary.add(format!("Buffer memory: {}", allocation));
+            } else {
+                summary.add(format!(
+                    "Warning: buffer memory uses {} instead of requested {}",
+                    allocation, requested
+                ));
+            }
+        }
+        if pool_nodes.len() > 1 {
+            summary.add(format!(
+                "NUMA: {} nodes, threads per node {}{}",
+                pool_nodes.len(),
+                pool_threads
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join("+"),
+                if numa_bound {
+                    ", buffers striped across nodes"
+                } else {
+                    ""
+                }
+            ));
+            if bind_numa && !numa_bound {
+                summary.add("Warning: couldn't bind buffer memory to numa nodes".to_string());
+            }
+        }
+        if !quiet {
+            println!();
+        }
+
+        let mb = MultiBar::new();
+
+        let total_to_hash: u64 = active_tasks
+            .iter()
+            .zip(progress.iter())
+            .map(|(task, progress)| (task.nonces - progress) * NONCE_SIZE)
+            .sum();
+
+        let p1x = if !quiet {
+            let mut p1 = mb.create_bar(total_to_hash);
+            p1.format("│██░│");
+            p1.set_units(Units::Bytes);
+            p1.message("Hashing: ");
+            p1.show_counter = false;
+            p1.set(0);
+            Some(p1)
+        } else {
+            None
+        };
+
+        let mut p2x = Vec::new();
+        for (drive, (task, progress)) in active_tasks.iter().zip(progress.iter()).enumerate() {
+            p2x.push(if !quiet {
+                let mut p2 = mb.create_bar((task.nonces - progress) * NONCE_SIZE);
+                p2.format("│██░│");
+                p2.set_units(Units::Bytes);
+                if active_tasks.len() > 1 {
+                    p2.message(&format!("Writing {}: ", drive + 1));
+                } else {
+                    p2.message("Writing: ");
+                }
+                p2.show_counter = false;
+                p2.set(0);
+                Some(p2)
+            } else {
+                None
+            });
+        }
+
+        let sw = Stopwatch::start_new();
+
+        let thread_pinning = pinning != PinningPolicy::None;
+        let node_count = pool_nodes.len();
+        let mut pools = Vec::new();
+        let mut devices: Vec<Box<dyn HashDevice>> = Vec::new();
+        for (stripe, (node, threads)) in pool_nodes.into_iter().enumerate() {
+            let cpus = node.cpus;
+            let pool = Arc::new(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads as usize)
+                    .start_handler(move |id| {
+                        if thread_pinning {
+                            #[cfg(not(windows))]
+                            core_affinity::set_for_current(core_affinity::CoreId {
+                                id: cpus[id % cpus.len()],
+                            });
+                            #[cfg(windows)]
+                            set_thread_ideal_processor(cpus[id % cpus.len()]);
+                        }
+                    })
+                    .build()
+                    .unwrap(),
+            );
+            let name = if node_count > 1 {
+                format!("CPU node {}", node.id)
+            } else {
+                "CPU".to_string()
+            };
+            pools.push(pool.clone());
+            devices.push(Box::new(CpuPool::new(pool, name, stripe, simd_ext.clone())));
+        }
+        // quarantined gpus' work goes to the same pools and backend
+        #[cfg(feature = "opencl")]
+        let rehasher = Rehasher::new(pools, simd_ext.clone());
+        #[cfg(feature = "opencl")]
+        for context in gpu_init(gpu_setups, active_tasks[0].host_shuffle) {
+            devices.push(Box::new(GpuDevice::new(
+                context,
+                active_tasks[0].gpu_check,
+                rehasher.clone(),
+            )));
+        }
+        // the scheduler would wait forever for a device to report
+        if devices.is_empty() {
+            return Err("nothing to hash with, no cpu threads and no gpus".to_string());
+        }
+
+        let hasher = thread::spawn({
+            create_scheduler_thread(
+                active_tasks.clone(),
+                devices,
+                progress.clone(),
+                p1x,
+                rx_empty_buffers.clone(),
+                tx_full_buffers,
+            )
+        });
+
+        let mut writers = Vec::new();
+        for (drive, (rx_full, p2)) in rx_full_buffers.into_iter().zip(p2x).enumerate() {
+            writers.push(thread::spawn({
+                create_writer_thread(
+                    active_tasks[drive].clone(),
+                    drive,
+                    progress[drive],
+                    p2,
+                    rx_full,
+                    tx_empty_buffers.clone(),
+                    governor.clone(),
+                    depth.clone(),
+                )
+            }));
+        }
+
+        if !quiet {
+            mb.listen();
+        }
+        let written: Vec<Result<WriterStats, String>> = writers.into_iter().map(|x| x.join().unwrap()).collect();
+        let devices = hasher.join().unwrap()?;
+        // a plot a writer gave up on is incomplete, it must not be moved
+        let written = written.into_iter().collect::<Result<Vec<WriterStats>, String>>()?;
+
+        let elapsed = sw.elapsed_ms() as u64;
+        let hours = elapsed / 1000 / 60 / 60;
+        let minutes = elapsed / 1000 / 60 - hours * 60;
+        let seconds = elapsed / 1000 - hours * 60 * 60 - minutes * 60;
+
+        let nonces: u64 = active_tasks
+            .iter()
+            .zip(progress.iter())
+            .map(|(task, progress)| task.nonces - progress)
+            .sum();
+
+        if !quiet {
+            println!(
+                "\nGenerated {} nonces in {}h{:02}m{:02}s, {:.2} MiB/s, {:.0} nonces/m.",
+                nonces,
+                hours,
+                minutes,
+                seconds,
+                nonces as f64 * 1000.0 / (elapsed as f64 + 1.0) / 4.0,
+                nonces as f64 * 1000.0 / (elapsed as f64 + 1.0) * 60.0
+            );
+        }
+
+        for device in device_reports(&devices, elapsed) {
+            summary.add(format!(
+                "{}: {} nonces, {:.0} nonces/m while busy, {:.0}% utilised.",
+                device.name,
+                device.nonces,
+                device.nonces as f64 * 1000.0 / (device.busy_ms as f64 + 1.0) * 60.0,
+                device.utilisation * 100.0
+            ));
+        }
+
+        let queues = queue_report(
+            WaitReport::from(hasher_stats.as_ref()),
+            writer_stats.iter().map(|x| WaitReport::from(x.as_ref())).collect(),
+        );
+        for line in queues.lines() {
+            summary.add(line);
+        }
+        if let Some(pressure) = governor.as_ref().and_then(|x| x.summary()) {
+            summary.add(pressure);
+        }
+        if let Some(depth) = depth.as_ref().and_then(|x| x.summary()) {
+            summary.add(depth);
+        }
+
+        if let Some(path) = &active_tasks[0].report_path {
+            let report = Report {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                benchmark,
+                cpu: CpuReport {
+                    brand: cpu_name.clone(),
+                    simd,
+                    threads: cpu_threads,
+                    cores,
+                    buffers_bound: numa_bound,
+                    threads_per_node: pool_threads,
+                },
+                buffers: BufferReport {
+                    per_file: num_buffer,
+                    files: active_tasks.len() as u64,
+                    size: buffer_size,
+                    requested: requested.to_string(),
+                    allocations: allocations.iter().map(|x| x.to_string()).collect(),
+                },
+                nonces,
+                elapsed_ms: elapsed,
+                nonces_per_minute: nonces as f64 * 1000.0 / (elapsed as f64 + 1.0) * 60.0,
+                devices: device_reports(&devices, elapsed),
+                writers: if benchmark {
+                    Vec::new()
+                } else {
+                    active_files
+                        .iter()
+                        .zip(written.iter())
+                        .map(|(file, stats)| writer_report(&file.display().to_string(), stats))
+                        .collect()
+                },
+                queues,
+                limits,
+            };
+            if let Err(e) = report.save(path) {
+                println!("Error: couldn't write report to {}: {}", path, e);
+            }
+        }
+
+        Ok(RunStats {
+            nonces,
+            elapsed_ms: elapsed,
+            summary: summary.lines,
+        })
+    }
+}
+
+// what a finished run did, used to compare settings
+#[derive(Clone, Debug)]
+pub struct RunStats {
+    pub nonces: u64,
+    pub elapsed_ms: u64,
+    // what the console shows about the run, for the gui's log
+    pub summary: Vec<String>,
+}
+
+// lines worth keeping after the run, printed as they come unless quiet
+struct Summary {
+    quiet: bool,
+    lines: Vec<String>,
+}
+
+impl Summary {
+    fn add(&mut self, line: String) {
+        if !self.quiet {
+            println!("{}", line);
+        }
+        self.lines.push(line);
+    }
+}
+
+impl RunStats {
+    pub fn nonces_per_minute(&self) -> f64 {
+        self.nonces as f64 * 1000.0 / (self.elapsed_ms as f64 + 1.0) * 60.0
+    }
+}
+
+// binds the pages of every stripe in each scoop row to the node hashing it
+fn bind_buffer_to_nodes(buffer: &mut PageAlignedByteBuffer, pool_nodes: &[(NumaNode, u64)]) -> bool {
+    let buffer_nonces = buffer.nonces();
+    let pool_threads: Vec<u64> = pool_nodes.iter().map(|x| x.1).collect();
+    let stripes = numa::stripes(buffer_nonces, &pool_threads);
+    let ptr = buffer.as_mut_slice().as_mut_ptr();
+    let mut bound = true;
+    for scoop in 0..NUM_SCOOPS {
+        for (stripe, (node, _)) in stripes.iter().zip(pool_nodes) {
+            if stripe.is_empty() {
+                continue;
+            }
+            let offset = (scoop * buffer_nonces + stripe.start) * SCOOP_SIZE;
+            bound &= bind_memory(
+                unsafe { ptr.add(offset as usize) },
+                ((stripe.end - stripe.start) * SCOOP_SIZE) as usize,
+                node.id,
+            );
+        }
+    }
+    bound
+}
+
+struct PlotFile {
+    path: PathBuf,
+    progress: u64,
+    nonces_per_sector: u64,
+    rounded_nonces_to_sector_size: bool,
+}
+
+// sizes the task to its drive and checks the target
+fn prepare_plot_file(task: &mut PlotterTask) -> Result<PlotFile, String> {
+    // use all available disk space if nonce parameter has been omitted
+    let free_disk_space = free_disk_space(&task.output_path);
+    if task.nonces == 0 {
+        task.nonces = free_disk_space / NONCE_SIZE;
+    }
+
+    // align number of nonces with sector size if direct i/o
+    let mut rounded_nonces_to_sector_size = false;
+    let mut nonces_per_sector = 1;
+    if task.direct_io {
+        let sector_size = get_sector_size(&task.output_path);
+        nonces_per_sector = sector_size / SCOOP_SIZE;
+        if task.nonces % nonces_per_sector > 0 {
+            rounded_nonces_to_sector_size = true;
+            task.nonces /= nonces_per_sector;
+            task.nonces *= nonces_per_sector;
+        }
+    }
+
+    let plotsize = task.nonces * NONCE_SIZE;
+
+    let file = Path::new(&task.output_path).join(format!(
+        "{}_{}_{}",
+        task.numeric_id, task.start_nonce, task.nonces
+    ));
+
+    if !file.parent().unwrap().exists() {
+        return Err(format!(
+            "specified target path does not exist, path={}",
+            &task.output_path
+        ));
+    }
+
+    // check available disk space
+    if free_disk_space < plotsize && !file.exists() && !task.benchmark {
+        return Err(format!(
+            "insufficient disk space, MiB_required={:.2}, MiB_available={:.2}",
+            plotsize as f64 / 1024.0 / 1024.0,
+            free_disk_space as f64 / 1024.0 / 1024.0
+        ));
+    }
+
+    // the temp file of the two-phase mode holds the whole plot at worst
+    if let Some(dir) = &task.transpose_path {
+        if !Path::new(dir).exists() {
+            return Err(format!("specified transpose path does not exist, path={}", dir));
+        }
+        let free_temp_space = crate::utils::free_disk_space(dir);
+        if free_temp_space < plotsize && !task.benchmark {
+            return Err(format!(
+                "insufficient temp space for transposition, MiB_required={:.2}, MiB_available={:.2}",
+                plotsize as f64 / 1024.0 / 1024.0,
+                free_temp_space as f64 / 1024.0 / 1024.0
+            ));
+        }
+    }
+
+    Ok(PlotFile {
+        path: file,
+        progress: 0,
+        nonces_per_sector,
+        rounded_nonces_to_sector_size,
+    })
+}
+
+// returns the nonce offset to continue at, Err if the resume info is unreadable
+fn resume_or_preallocate(task: &PlotterTask, file: &Path) -> Result<u64, String> {
+    let plotsize = task.nonces * NONCE_SIZE;
+    let mut progress = 0;
+    if file.exists() {
+        if !task.quiet {
+            println!("File already exists, reading resume info...");
+        }
+        let resume_info = read_resume_info(file);
+        match resume_info {
+            Ok(x) => progress = x,
+            Err(_) => {
+                return Err(format!(
+                    "couldn't read resume info from file '{}'. If you are sure that this file \
+                     is incomplete or corrupted, then delete it before continuing.",
+                    file.display()
+                ));
+            }
+        }
+        if !task.quiet {
+            println!("OK");
+        }
+    } else {
+        if !task.quiet {
+            print!("Fast file pre-allocation...");
+        }
+        if !task.benchmark {
+            preallocate(file, plotsize, task.direct_io);
+            if write_resume_info(file, 0u64).is_err() {
+                println!("Error: couldn't write resume info");
+            }
+        }
+        if !task.quiet {
+            println!("OK");
+        }
+    }
+    Ok(progress)
+}
+
+// returns the memory to use for buffers and the number of buffers per file
+fn calculate_mem_to_use(
+    task: &PlotterTask,
+    plotsize: u64,
+    files: u64,
+    memory: &sys_info::MemInfo,
+    nonces_per_sector: u64,
+    gpu: bool,
+    gpu_mem_needed: u64,
+) -> Result<(u64, u64), String> {
+
+    let mut mem = match task.mem.parse::<Bytes>() {
+    Ok(x) => x.size() as u64,
+    Err(_) => {
+        return Err(format!(
+            "can't parse memory limit parameter, input={}. Please specify a number followed \
+             by a unit (B, KiB, MiB, GiB, TiB, KB, MB, GB, TB), e.g. 10GiB",
+            task.mem,
+        ));
+    }
+};
+
+// If no memory limit specified (or "0B"), use full plot size for buffer
+if mem == 0 {
+    mem = plotsize;
+}
+    if gpu && mem > 0 {
+    let min_required = gpu_mem_needed + nonces_per_sector * NONCE_SIZE;
+    if mem < min_required {
+        println!("Warning: Requested host memory ({:.2} GiB) is less than recommended ({:.2} GiB) for GPU plotting.", 
+                 mem as f64 / 1024.0 / 1024.0 / 1024.0, 
+                 min_required as f64 / 1024.0 / 1024.0 / 1024.0);
+        println!("Continuing anyway — may be slower or unstable.");
+        // Don't exit — let it try
+    }
+}
+
+    if gpu && mem > 0 {
+        mem -= gpu_mem_needed;
+    }
+
+    if mem == 0 {
+        mem = plotsize;
+    }
+    mem = min(mem, plotsize + gpu_mem_needed);
+
+    let unit = buffer_unit(nonces_per_sector, gpu);
+
+    // don't exceed free memory and leave some elbow room 1-1000/1024
+    mem = min(mem, get_avail_mem(&memory) * 1000 - gpu_mem_needed);
+
+    // a deeper queue rides out hashing and writing stalls, but every buffer costs
+    // a full round of scoop seeks, so auto mode keeps buffers reasonably large
+    let num_buffer = if task.buffers > 0 {
+        task.buffers
+    } else if task.async_io {
+        (mem / files / AUTO_MIN_BUFFER_SIZE).clamp(AUTO_MIN_BUFFERS, AUTO_MAX_BUFFERS)
+    } else {
+        1
+    };
+
+    // rounding to equal sector aligned buffers (per plot file)
+    let buffers = num_buffer * files;
+    mem /= buffers * unit;
+    mem *= buffers * unit;
+
+    // ensure a minimum buffer
+    mem = max(mem, buffers * unit);
+    Ok((mem, num_buffer))
+}
+
+// threads for cpu_threads 0: one per core, or per listed cpu when pinning to a
+// list, more would only share those cpus
+fn default_threads(cores: u32, pinning: &PinningPolicy, nodes: &[NumaNode]) -> u32 {
+    match pinning {
+        PinningPolicy::Explicit(_) => {
+            let pinned = nodes.iter().map(|x| x.cpus.len() as u32).sum();
+            min(cores, pinned)
+        }
+        _ => cores,
+    }
+}
+
+// bytes every buffer size has to be a multiple of: whole sectors for direct i/o,
+// opencl requires a multiple of 16 nonces (data coalescence magic)
+fn buffer_unit(nonces_per_sector: u64, gpu: bool) -> u64 {
+    let nonces_per_sector = if gpu {
+        max(16, nonces_per_sector)
+    } else {
+        nonces_per_sector
+    };
+    nonces_per_sector * NONCE_SIZE
+}
+
+// mem was rounded for all files, with complete ones left out the share of each
+// active buffer has to be rounded down to the unit again
+fn buffer_size(mem: u64, buffers: u64, unit: u64) -> u64 {
+    max(mem / buffers / unit * unit, unit)
+}
+
+// sys_info ex, displays 0 avail on win
+#[cfg(not(windows))]
+fn get_avail_mem(memory: &sys_info::MemInfo) -> u64 {
+    memory.avail
+}
+
+#[cfg(windows)]
+fn get_avail_mem(memory: &sys_info::MemInfo) -> u64 {
+    memory.free
+}
+#[cfg(test)]
+mod plotter_tests {
+    use super::*;
+
+    fn task(mem: &str, buffers: u64) -> PlotterTask {
+        PlotterTask {
+            numeric_id: 0,
+            start_nonce: 0,
+            nonces: 0,
+            output_path: String::new(),
+            mem: mem.to_string(),
+            cpu_threads: 1,
+            gpu_only: false,
+            gpus: None,
+            direct_io: true,
+            async_io: true,
+            quiet: true,
+            benchmark: false,
+            zcb: false,
+            host_shuffle: false,
+            gpu_check: None,
+            kernel: Vec::new(),
+            transpose_path: None,
+            write_backend: WriteBackendKind::Sync,
+            buffers,
+            huge_pages: HugePages::Normal,
+            lock_memory: false,
+            pinning: PinningPolicy::default(),
+            adaptive_memory: false,
+            report_path: None,
+            simd: None,
+        }
+    }
+
+    fn memory(avail_gib: u64) -> sys_info::MemInfo {
+        sys_info::MemInfo {
+            total: avail_gib * 1024 * 1024,
+            free: avail_gib * 1024 * 1024,
+            avail: avail_gib * 1024 * 1024,
+            buffers: 0,
+            cached: 0,
+            swap_total: 0,
+            swap_free: 0,
+        }
+    }
+
+    #[test]
+    fn buffers_are_equal_and_sector_aligned() {
+        let plotsize = 1024 * 1024 * NONCE_SIZE;
+        let (mem, num_buffer) =
+            calculate_mem_to_use(&task("10000MiB", 3), plotsize, 2, &memory(64), 16, false, 0).unwrap();
+        assert_eq!(num_buffer, 3);
+        assert_eq!(mem % (6 * 16 * NONCE_SIZE), 0);
+        assert!(mem <= 10000 * 1024 * 1024);
+    }
+
+    #[test]
+    fn buffers_stay_aligned_when_files_are_complete() {
+        let plotsize = 1024 * 1024 * NONCE_SIZE;
+        // rounded for 3 files with 2 buffers each, one of them is already complete
+        let (mem, num_buffer) =
+            calculate_mem_to_use(&task("10000MiB", 2), plotsize, 3, &memory(64), 16, true, 0).unwrap();
+        let unit = buffer_unit(16, true);
+        let size = buffer_size(mem, num_buffer * 2, unit);
+        assert_eq!(size % unit, 0);
+        assert!(size * num_buffer * 2 <= mem);
+        assert_eq!(buffer_size(unit, 4, unit), unit);
+    }
+
+    #[test]
+    fn auto_depth_follows_memory() {
+        let plotsize = 1024 * 1024 * NONCE_SIZE;
+        let depth = |mem: &str| {
+            calculate_mem_to_use(&task(mem, 0), plotsize, 1, &memory(64), 16, false, 0)
+                .unwrap()
+                .1
+        };
+        assert_eq!(depth("1GiB"), 2);
+        assert_eq!(depth("3GiB"), 3);
+        assert_eq!(depth("32GiB"), AUTO_MAX_BUFFERS);
+
+        let mut sync = task("32GiB", 0);
+        sync.async_io = false;
+        assert_eq!(
+            calculate_mem_to_use(&sync, plotsize, 1, &memory(64), 16, false, 0).unwrap().1,
+            1
+        );
+    }
+
+    #[test]
+    fn explicit_pinning_defaults_to_the_listed_cpus() {
+        let nodes = vec![
+            NumaNode { id: 0, cpus: vec![2, 3] },
+            NumaNode { id: 1, cpus: vec![8] },
+        ];
+        assert_eq!(default_threads(16, &PinningPolicy::Explicit(vec![2, 3, 8, 99]), &nodes), 3);
+        // a cgroup quota still caps it
+        assert_eq!(default_threads(2, &PinningPolicy::Explicit(vec![2, 3, 8]), &nodes), 2);
+        assert_eq!(default_threads(16, &PinningPolicy::None, &nodes), 16);
+    }
+}
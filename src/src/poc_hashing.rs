@@ -1,107 +1,250 @@
-use crate::shabal256::shabal256_fast;
-
-const HASH_SIZE: usize = 32;
-const HASH_CAP: usize = 4096;
-const NUM_SCOOPS: usize = 4096;
-const SCOOP_SIZE: usize = 64;
-const NONCE_SIZE: usize = NUM_SCOOPS * SCOOP_SIZE;
-const MESSAGE_SIZE: usize = 16;
-
-// cache:		    cache to save to
-// local_num:		thread number
-// numeric_id:		numeric account id
-// loc_startnonce	nonce to start generation at
-// local_nonces: 	number of nonces to generate
-pub fn noncegen_rust(
-    cache: &mut [u8],
-    cache_offset: usize,
-    numeric_id: u64,
-    local_startnonce: u64,
-    local_nonces: u64,
-) {
-    let numeric_id: [u32; 2] = unsafe { std::mem::transmute(numeric_id.to_be()) };
-
-    let mut buffer = [0u8; NONCE_SIZE];
-    let mut final_buffer = [0u8; HASH_SIZE];
-
-    // prepare termination strings
-    let mut t1 = [0u32; MESSAGE_SIZE];
-    t1[0..2].clone_from_slice(&numeric_id);
-    t1[4] = 0x80;
-
-    let mut t2 = [0u32; MESSAGE_SIZE];
-    t2[8..10].clone_from_slice(&numeric_id);
-    t2[12] = 0x80;
-
-    let mut t3 = [0u32; MESSAGE_SIZE];
-    t3[0] = 0x80;
-
-    for n in 0..local_nonces {
-        // generate nonce numbers & change endianness
-        let nonce: [u32; 2] = unsafe { std::mem::transmute((local_startnonce + n).to_be()) };
-
-        // store nonce numbers in relevant termination strings
-        t1[2..4].clone_from_slice(&nonce);
-        t2[10..12].clone_from_slice(&nonce);
-
-        // start shabal rounds
-
-        // 3 cases: first 128 rounds uses case 1 or 2, after that case 3
-        // case 1: first 128 rounds, hashes are even: use termination string 1
-        // case 2: first 128 rounds, hashes are odd: use termination string 2
-        // case 3: round > 128: use termination string 3
-        // round 1
-        let hash = shabal256_fast(&[], &t1);
-
-        buffer[NONCE_SIZE - HASH_SIZE..NONCE_SIZE].clone_from_slice(&hash);
-        let hash = unsafe { std::mem::transmute::<[u8; 32], [u32; 8]>(hash) };
-
-        // store first hash into smart termination string 2
-        t2[0..8].clone_from_slice(&hash);
-        // round 2 - 128
-        for i in (NONCE_SIZE - HASH_CAP + HASH_SIZE..=NONCE_SIZE - HASH_SIZE)
-            .rev()
-            .step_by(HASH_SIZE)
-        {
-            // check if msg can be divided into 512bit packages without a
-            // remainder
-            if i % 64 == 0 {
-                // last msg = seed + termination
-                let hash = &shabal256_fast(&buffer[i..NONCE_SIZE], &t1);
-                buffer[i - HASH_SIZE..i].clone_from_slice(hash);
-            } else {
-                // last msg = 256 bit data + seed + termination
-                let hash = &shabal256_fast(&buffer[i..NONCE_SIZE], &t2);
-                buffer[i - HASH_SIZE..i].clone_from_slice(hash);
-            }
-        }
-
-        // round 128-8192
-        for i in (HASH_SIZE..=NONCE_SIZE - HASH_CAP).rev().step_by(HASH_SIZE) {
-            let hash = &shabal256_fast(&buffer[i..i + HASH_CAP], &t3);
-            buffer[i - HASH_SIZE..i].clone_from_slice(hash);
-        }
-
-        // generate final hash
-        final_buffer.clone_from_slice(&shabal256_fast(&buffer[0..NONCE_SIZE], &t1));
-
-        // XOR with final
-        for i in 0..NONCE_SIZE {
-            buffer[i] ^= final_buffer[i % HASH_SIZE];
-        }
-
-        // PoC2 shuffle
-        let cache_size = cache.len() / NONCE_SIZE;
-        for i in 0..NUM_SCOOPS {
-            let offset = i * cache_size * SCOOP_SIZE + (n as usize + cache_offset) * SCOOP_SIZE;
-            cache[offset..offset + HASH_SIZE]
-                .clone_from_slice(&buffer[i * SCOOP_SIZE..i * SCOOP_SIZE + HASH_SIZE]);
-            let mirror_offset = (4095 - i) * cache_size * SCOOP_SIZE
-                + (n as usize + cache_offset) * SCOOP_SIZE
-                + HASH_SIZE;
-            cache[mirror_offset..mirror_offset + HASH_SIZE].clone_from_slice(
-                &buffer[i * SCOOP_SIZE + HASH_SIZE..i * SCOOP_SIZE + 2 * HASH_SIZE],
-            );
-        }
-    }
-}
+use crate::buffer::NonceView;
+use crate::lanes::Lanes;
+use crate::shabal256::{shabal256_fast, shabal256_lanes};
+
+const HASH_SIZE: usize = 32;
+const HASH_CAP: usize = 4096;
+const NUM_SCOOPS: usize = 4096;
+const SCOOP_SIZE: usize = 64;
+const NONCE_SIZE: usize = NUM_SCOOPS * SCOOP_SIZE;
+const MESSAGE_SIZE: usize = 16;
+
+// cache:		    nonces of the buffer to save to
+// numeric_id:		numeric account id
+// loc_startnonce	nonce to start generation at
+pub fn noncegen_rust(cache: &mut NonceView, numeric_id: u64, local_startnonce: u64) {
+    let local_nonces = cache.nonces();
+    let numeric_id = be_words(numeric_id);
+
+    let mut buffer = [0u8; NONCE_SIZE];
+    let mut final_buffer = [0u8; HASH_SIZE];
+
+    // prepare termination strings
+    let mut t1 = [0u32; MESSAGE_SIZE];
+    t1[0..2].clone_from_slice(&numeric_id);
+    t1[4] = 0x80;
+
+    let mut t2 = [0u32; MESSAGE_SIZE];
+    t2[8..10].clone_from_slice(&numeric_id);
+    t2[12] = 0x80;
+
+    let mut t3 = [0u32; MESSAGE_SIZE];
+    t3[0] = 0x80;
+
+    for n in 0..local_nonces {
+        // generate nonce numbers & change endianness
+        let nonce = be_words(local_startnonce + n);
+
+        // store nonce numbers in relevant termination strings
+        t1[2..4].clone_from_slice(&nonce);
+        t2[10..12].clone_from_slice(&nonce);
+
+        // start shabal rounds
+
+        // 3 cases: first 128 rounds uses case 1 or 2, after that case 3
+        // case 1: first 128 rounds, hashes are even: use termination string 1
+        // case 2: first 128 rounds, hashes are odd: use termination string 2
+        // case 3: round > 128: use termination string 3
+        // round 1
+        let hash = shabal256_fast(&[], &t1);
+
+        buffer[NONCE_SIZE - HASH_SIZE..NONCE_SIZE].clone_from_slice(&hash);
+        let hash = le_words(&hash);
+
+        // store first hash into smart termination string 2
+        t2[0..8].clone_from_slice(&hash);
+        // round 2 - 128
+        for i in (NONCE_SIZE - HASH_CAP + HASH_SIZE..=NONCE_SIZE - HASH_SIZE)
+            .rev()
+            .step_by(HASH_SIZE)
+        {
+            // check if msg can be divided into 512bit packages without a
+            // remainder
+            if i % 64 == 0 {
+                // last msg = seed + termination
+                let hash = &shabal256_fast(&buffer[i..NONCE_SIZE], &t1);
+                buffer[i - HASH_SIZE..i].clone_from_slice(hash);
+            } else {
+                // last msg = 256 bit data + seed + termination
+                let hash = &shabal256_fast(&buffer[i..NONCE_SIZE], &t2);
+                buffer[i - HASH_SIZE..i].clone_from_slice(hash);
+            }
+        }
+
+        // round 128-8192
+        for i in (HASH_SIZE..=NONCE_SIZE - HASH_CAP).rev().step_by(HASH_SIZE) {
+            let hash = &shabal256_fast(&buffer[i..i + HASH_CAP], &t3);
+            buffer[i - HASH_SIZE..i].clone_from_slice(hash);
+        }
+
+        // generate final hash
+        final_buffer.clone_from_slice(&shabal256_fast(&buffer[0..NONCE_SIZE], &t1));
+
+        // XOR with final
+        for i in 0..NONCE_SIZE {
+            buffer[i] ^= final_buffer[i % HASH_SIZE];
+        }
+
+        // PoC2 shuffle
+        let offset = n as usize * SCOOP_SIZE;
+        for i in 0..NUM_SCOOPS {
+            cache.scoop_mut(i)[offset..offset + HASH_SIZE]
+                .clone_from_slice(&buffer[i * SCOOP_SIZE..i * SCOOP_SIZE + HASH_SIZE]);
+            cache.scoop_mut(4095 - i)[offset + HASH_SIZE..offset + SCOOP_SIZE].clone_from_slice(
+                &buffer[i * SCOOP_SIZE + HASH_SIZE..i * SCOOP_SIZE + 2 * HASH_SIZE],
+            );
+        }
+    }
+}
+
+// Same as noncegen_rust, N nonces at a time through shabal256_lanes. Like the
+// mshabal noncegen the nonce buffers of all lanes are interleaved word by word,
+// so every message word is a single vector load. Nonces that don't fill all
+// lanes go through noncegen_rust.
+#[inline(always)]
+pub fn noncegen_lanes<V: Lanes<N>, const N: usize>(
+    cache: &mut NonceView,
+    numeric_id: u64,
+    local_startnonce: u64,
+) {
+    let full = cache.nonces() / N as u64 * N as u64;
+    if full < cache.nonces() {
+        noncegen_rust(&mut cache.split_off(full), numeric_id, local_startnonce + full);
+    }
+    if full == 0 {
+        return;
+    }
+
+    let numeric_id = be_words(numeric_id);
+
+    // word index of a byte offset into the nonce
+    let at = |offset: usize| offset / 4 * N;
+    let mut buffer = vec![0u32; at(NONCE_SIZE)];
+
+    let mut t1 = [V::splat(0); MESSAGE_SIZE];
+    t1[0] = V::splat(numeric_id[0]);
+    t1[1] = V::splat(numeric_id[1]);
+    t1[4] = V::splat(0x80);
+
+    let mut t2 = [V::splat(0); MESSAGE_SIZE];
+    t2[8] = V::splat(numeric_id[0]);
+    t2[9] = V::splat(numeric_id[1]);
+    t2[12] = V::splat(0x80);
+
+    let mut t3 = [V::splat(0); MESSAGE_SIZE];
+    t3[0] = V::splat(0x80);
+
+    for n in (0..full).step_by(N) {
+        let mut high = [0u32; N];
+        let mut low = [0u32; N];
+        for (lane, (high, low)) in high.iter_mut().zip(low.iter_mut()).enumerate() {
+            [*high, *low] = be_words(local_startnonce + n + lane as u64);
+        }
+        t1[2] = V::load(&high);
+        t1[3] = V::load(&low);
+        t2[10] = V::load(&high);
+        t2[11] = V::load(&low);
+
+        // round 1
+        let hash = shabal256_lanes::<V, N>(&[], &t1);
+        store_hash(&mut buffer[at(NONCE_SIZE - HASH_SIZE)..], &hash);
+        t2[0..8].copy_from_slice(&hash);
+
+        // round 2 - 128
+        for i in (NONCE_SIZE - HASH_CAP + HASH_SIZE..=NONCE_SIZE - HASH_SIZE)
+            .rev()
+            .step_by(HASH_SIZE)
+        {
+            let term = if i % 64 == 0 { &t1 } else { &t2 };
+            let hash = shabal256_lanes::<V, N>(&buffer[at(i)..], term);
+            store_hash(&mut buffer[at(i - HASH_SIZE)..], &hash);
+        }
+
+        // round 128-8192
+        for i in (HASH_SIZE..=NONCE_SIZE - HASH_CAP).rev().step_by(HASH_SIZE) {
+            let hash = shabal256_lanes::<V, N>(&buffer[at(i)..at(i + HASH_CAP)], &t3);
+            store_hash(&mut buffer[at(i - HASH_SIZE)..], &hash);
+        }
+
+        // generate final hash
+        let final_hash = shabal256_lanes::<V, N>(&buffer, &t1);
+
+        // XOR with final
+        for words in buffer.chunks_exact_mut(at(HASH_SIZE)) {
+            for (i, final_word) in final_hash.iter().enumerate() {
+                V::load(&words[i * N..])
+                    .xor(*final_word)
+                    .store(&mut words[i * N..]);
+            }
+        }
+
+        // PoC2 shuffle, lane by lane out of the interleaved buffer
+        for lane in 0..N {
+            let offset = (n as usize + lane) * SCOOP_SIZE;
+            for i in 0..NUM_SCOOPS {
+                copy_lane(
+                    &mut cache.scoop_mut(i)[offset..offset + HASH_SIZE],
+                    &buffer[at(i * SCOOP_SIZE)..],
+                    lane,
+                    N,
+                );
+                copy_lane(
+                    &mut cache.scoop_mut(4095 - i)[offset + HASH_SIZE..offset + SCOOP_SIZE],
+                    &buffer[at(i * SCOOP_SIZE + HASH_SIZE)..],
+                    lane,
+                    N,
+                );
+            }
+        }
+    }
+}
+
+#[inline(always)]
+fn store_hash<V: Lanes<N>, const N: usize>(words: &mut [u32], hash: &[V; 8]) {
+    for (i, word) in hash.iter().enumerate() {
+        word.store(&mut words[i * N..]);
+    }
+}
+
+// one lane's words of an interleaved buffer as bytes
+#[inline(always)]
+fn copy_lane(bytes: &mut [u8], words: &[u32], lane: usize, lanes: usize) {
+    for (i, bytes) in bytes.chunks_exact_mut(4).enumerate() {
+        bytes.copy_from_slice(&words[i * lanes + lane].to_le_bytes());
+    }
+}
+
+// a number as the two message words shabal sees for its big endian bytes
+fn be_words(x: u64) -> [u32; 2] {
+    let bytes = x.to_be_bytes();
+    [
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+    ]
+}
+
+fn le_words(hash: &[u8; HASH_SIZE]) -> [u32; 8] {
+    let mut words = [0u32; 8];
+    for (word, bytes) in words.iter_mut().zip(hash.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    words
+}
+
+#[cfg(test)]
+mod poc_hashing_tests {
+    use super::*;
+    use crate::buffer::PageAlignedByteBuffer;
+
+    #[test]
+    fn lanes_match_scalar() {
+        // two rounds of two lanes, the second one only half used
+        let mut scalar = PageAlignedByteBuffer::new(4 * NONCE_SIZE);
+        let mut lanes = PageAlignedByteBuffer::new(4 * NONCE_SIZE);
+        scalar.as_mut_slice().fill(0);
+        lanes.as_mut_slice().fill(0);
+        noncegen_rust(&mut scalar.lend(1..4), 7900104405094198526, 1337);
+        noncegen_lanes::<[u32; 2], 2>(&mut lanes.lend(1..4), 7900104405094198526, 1337);
+        assert!(scalar.as_slice() == lanes.as_slice());
+    }
+}
@@ -0,0 +1,206 @@
+use crate::buffer::PageAlignedByteBuffer;
+use crate::cgroup::CgroupLimits;
+use std::cmp::{max, min};
+use std::fs;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+// shrink below 5% available memory or when tasks stall on memory >10% of the time
+const LOW_WATERMARK_PERCENT: u64 = 5;
+const PSI_SHRINK: f64 = 10.0;
+// grow back only with plenty of headroom and no stalls
+const HIGH_WATERMARK_PERCENT: u64 = 15;
+const PSI_GROW: f64 = 1.0;
+// never shrink below 1/8 of the initial buffer size
+const MIN_SIZE_DIVISOR: u64 = 8;
+
+// what the system looks like right now, bytes
+#[derive(Clone, Copy, Debug)]
+struct MemorySample {
+    total: u64,
+    available: u64,
+    // /proc/pressure/memory "some avg10", None on kernels without psi
+    psi: Option<f64>,
+}
+
+// Watches available memory and PSI while plotting and resizes buffers as they
+// come back empty from the writers, so nothing hashed is ever lost. Sizes stay
+// multiples of the sector aligned unit calculate_mem_to_use rounds to.
+pub struct MemoryGovernor {
+    base_size: u64,
+    unit: u64,
+    num_buffers: u64,
+    allocate: Box<dyn Fn(usize) -> PageAlignedByteBuffer + Send + Sync>,
+    state: Mutex<GovernorState>,
+}
+
+struct GovernorState {
+    target: u64,
+    last_check: Instant,
+    shrinks: u64,
+    grows: u64,
+    smallest: u64,
+}
+
+impl MemoryGovernor {
+    pub fn new(
+        base_size: u64,
+        unit: u64,
+        num_buffers: u64,
+        allocate: Box<dyn Fn(usize) -> PageAlignedByteBuffer + Send + Sync>,
+    ) -> MemoryGovernor {
+        MemoryGovernor {
+            base_size,
+            unit,
+            num_buffers,
+            allocate,
+            state: Mutex::new(GovernorState {
+                target: base_size,
+                last_check: Instant::now(),
+                shrinks: 0,
+                grows: 0,
+                smallest: base_size,
+            }),
+        }
+    }
+
+    // returns the buffer, reallocated if the target size changed since it was made
+    pub fn recycle(&self, buffer: PageAlignedByteBuffer) -> PageAlignedByteBuffer {
+        let target = {
+            let mut state = self.state.lock().unwrap();
+            if state.last_check.elapsed() >= CHECK_INTERVAL {
+                state.last_check = Instant::now();
+                let target = decide(
+                    state.target,
+                    self.base_size,
+                    self.unit,
+                    self.num_buffers,
+                    sample_memory(),
+                );
+                if target < state.target {
+                    state.shrinks += 1;
+                    state.smallest = min(state.smallest, target);
+                } else if target > state.target {
+                    state.grows += 1;
+                }
+                state.target = target;
+            }
+            state.target
+        };
+
+        let size = buffer.size() as u64;
+        if size == target {
+            return buffer;
+        }
+        // free first, the point is to give memory back
+        drop(buffer);
+        (self.allocate)(target as usize)
+    }
+
+    // None if buffers never had to change
+    pub fn summary(&self) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        if state.shrinks == 0 && state.grows == 0 {
+            return None;
+        }
+        Some(format!(
+            "Memory pressure: buffers shrunk {}x, grown {}x, smallest {:.2} MiB, now {:.2} MiB",
+            state.shrinks,
+            state.grows,
+            state.smallest as f64 / 1024.0 / 1024.0,
+            state.target as f64 / 1024.0 / 1024.0
+        ))
+    }
+}
+
+// halves the buffer size under pressure, doubles it back towards the initial
+// size when the additional memory for all buffers fits above the high watermark
+fn decide(current: u64, base: u64, unit: u64, num_buffers: u64, sample: MemorySample) -> u64 {
+    let min_size = max(base / MIN_SIZE_DIVISOR / unit * unit, unit);
+    let round = |size: u64| max(size / unit * unit, min_size);
+    let psi = sample.psi.unwrap_or(0.0);
+
+    if sample.available < sample.total * LOW_WATERMARK_PERCENT / 100 || psi > PSI_SHRINK {
+        return round(current / 2);
+    }
+
+    let grown = min(round(current * 2), base);
+    let needed = (grown - current) * num_buffers;
+    if grown > current
+        && psi < PSI_GROW
+        && sample.available > sample.total * HIGH_WATERMARK_PERCENT / 100 + needed
+    {
+        return grown;
+    }
+    current
+}
+
+fn sample_memory() -> MemorySample {
+    let limits = CgroupLimits::detect();
+    let (total, available) = match sys_info::mem_info() {
+        Ok(x) => {
+            let x = limits.apply(x);
+            (x.total * 1024, x.avail * 1024)
+        }
+        Err(_) => (0, 0),
+    };
+    MemorySample {
+        total,
+        available,
+        psi: fs::read_to_string("/proc/pressure/memory")
+            .ok()
+            .and_then(|x| parse_psi(&x)),
+    }
+}
+
+// "some avg10=1.23 avg60=..." line of a psi file
+fn parse_psi(psi: &str) -> Option<f64> {
+    psi.lines()
+        .find(|x| x.starts_with("some"))?
+        .split_whitespace()
+        .find_map(|x| x.strip_prefix("avg10="))?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod pressure_tests {
+    use super::*;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+    const UNIT: u64 = 16 * 262144;
+
+    fn sample(available: u64, psi: Option<f64>) -> MemorySample {
+        MemorySample {
+            total: 64 * GIB,
+            available,
+            psi,
+        }
+    }
+
+    #[test]
+    fn shrinks_under_pressure_and_grows_back() {
+        let base = 4 * GIB;
+        // plenty of memory, stay
+        assert_eq!(decide(base, base, UNIT, 2, sample(32 * GIB, Some(0.0))), base);
+        // low available memory or memory stalls halve the buffers
+        assert_eq!(decide(base, base, UNIT, 2, sample(GIB, None)), 2 * GIB);
+        assert_eq!(decide(base, base, UNIT, 2, sample(32 * GIB, Some(25.0))), 2 * GIB);
+        // never below an eighth
+        assert_eq!(decide(base / 8, base, UNIT, 2, sample(GIB, None)), base / 8);
+        // grow back once there is room for all buffers
+        assert_eq!(decide(GIB, base, UNIT, 2, sample(32 * GIB, Some(0.1))), 2 * GIB);
+        assert_eq!(decide(GIB, base, UNIT, 2, sample(10 * GIB, Some(0.1))), GIB);
+        // sizes stay multiples of the unit
+        let odd = 1000 * UNIT + UNIT / 2;
+        assert_eq!(decide(odd / 2 * 2, 2000 * UNIT, UNIT, 2, sample(GIB, None)) % UNIT, 0);
+    }
+
+    #[test]
+    fn parses_psi() {
+        let psi = "some avg10=12.50 avg60=3.00 avg300=1.00 total=123\nfull avg10=1.00 avg60=0.00 avg300=0.00 total=1\n";
+        assert_eq!(parse_psi(psi), Some(12.5));
+        assert_eq!(parse_psi(""), None);
+    }
+}
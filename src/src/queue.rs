@@ -0,0 +1,311 @@
+use crate::buffer::PageAlignedByteBuffer;
+use crossbeam_channel::Receiver;
+use std::cmp::{max, min};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+// hashing and writing both waited >10% of the interval: they take turns
+// stalling each other, which a deeper queue smooths out
+const STALL_PERCENT: u64 = 10;
+// the hasher waited <1% with spare buffers queued: the depth isn't needed
+const IDLE_PERCENT: u64 = 1;
+
+// Wait times and fill level seen by the consumer of a buffer queue. A hasher
+// waiting for empty buffers means the writers are the bottleneck, a writer
+// waiting for full buffers means hashing is.
+#[derive(Default)]
+pub struct QueueStats {
+    receives: AtomicU64,
+    wait_us: AtomicU64,
+    queued: AtomicU64,
+}
+
+// totals of a QueueStats at one point in time
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct QueueSample {
+    receives: u64,
+    wait_us: u64,
+    queued: u64,
+}
+
+impl QueueSample {
+    fn since(&self, earlier: QueueSample) -> QueueSample {
+        QueueSample {
+            receives: self.receives - earlier.receives,
+            wait_us: self.wait_us - earlier.wait_us,
+            queued: self.queued - earlier.queued,
+        }
+    }
+}
+
+impl QueueStats {
+    fn sample(&self) -> QueueSample {
+        QueueSample {
+            receives: self.receives.load(Ordering::Relaxed),
+            wait_us: self.wait_us.load(Ordering::Relaxed),
+            queued: self.queued.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn wait_time(&self) -> Duration {
+        Duration::from_micros(self.wait_us.load(Ordering::Relaxed))
+    }
+
+    // average number of buffers already waiting when the consumer asked for one
+    pub fn avg_queued(&self) -> f64 {
+        let receives = self.receives.load(Ordering::Relaxed);
+        if receives == 0 {
+            0.0
+        } else {
+            self.queued.load(Ordering::Relaxed) as f64 / receives as f64
+        }
+    }
+}
+
+// receiver that records its QueueStats, iterates like the plain one
+pub struct MeteredReceiver<T> {
+    rx: Receiver<T>,
+    stats: Arc<QueueStats>,
+}
+
+impl<T> MeteredReceiver<T> {
+    pub fn new(rx: Receiver<T>) -> (MeteredReceiver<T>, Arc<QueueStats>) {
+        let stats = Arc::new(QueueStats::default());
+        (
+            MeteredReceiver {
+                rx,
+                stats: stats.clone(),
+            },
+            stats,
+        )
+    }
+}
+
+// clones share the stats
+impl<T> Clone for MeteredReceiver<T> {
+    fn clone(&self) -> MeteredReceiver<T> {
+        MeteredReceiver {
+            rx: self.rx.clone(),
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+impl<T> Iterator for MeteredReceiver<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let queued = self.rx.len() as u64;
+        let start = Instant::now();
+        let item = self.rx.recv().ok();
+        if item.is_some() {
+            self.stats.receives.fetch_add(1, Ordering::Relaxed);
+            self.stats.queued.fetch_add(queued, Ordering::Relaxed);
+            self.stats
+                .wait_us
+                .fetch_add(start.elapsed().as_micros() as u64, Ordering::Relaxed);
+        }
+        item
+    }
+}
+
+// Adapts the number of in-flight buffers per file to the waits of the queues.
+// Buffers are added while hashing and writing keep waiting on each other and
+// freed while they sit in the empty queue unused. Like the MemoryGovernor it
+// acts on buffers coming back from the writers, so nothing hashed is lost.
+pub struct DepthGovernor {
+    min_depth: u64,
+    max_depth: u64,
+    hasher: Arc<QueueStats>,
+    writers: Vec<Arc<QueueStats>>,
+    allocate: Box<dyn Fn(usize) -> PageAlignedByteBuffer + Send + Sync>,
+    state: Mutex<DepthState>,
+}
+
+struct DepthState {
+    depth: u64,
+    // buffers of every file in circulation, they follow depth as they come back
+    buffers: Vec<u64>,
+    last_check: Instant,
+    hasher: QueueSample,
+    writers: Vec<QueueSample>,
+    shrinks: u64,
+    grows: u64,
+    smallest: u64,
+}
+
+impl DepthGovernor {
+    // starts at max_depth, the buffers calculate_mem_to_use sized
+    pub fn new(
+        min_depth: u64,
+        max_depth: u64,
+        hasher: Arc<QueueStats>,
+        writers: Vec<Arc<QueueStats>>,
+        allocate: Box<dyn Fn(usize) -> PageAlignedByteBuffer + Send + Sync>,
+    ) -> DepthGovernor {
+        let state = DepthState {
+            depth: max_depth,
+            buffers: vec![max_depth; writers.len()],
+            last_check: Instant::now(),
+            hasher: hasher.sample(),
+            writers: writers.iter().map(|x| x.sample()).collect(),
+            shrinks: 0,
+            grows: 0,
+            smallest: max_depth,
+        };
+        DepthGovernor {
+            min_depth,
+            max_depth,
+            hasher,
+            writers,
+            allocate,
+            state: Mutex::new(state),
+        }
+    }
+
+    // the buffers to send back to the hasher for this drive: none if it is freed,
+    // more than the one given if the depth grew
+    pub fn recycle(&self, drive: usize, buffer: PageAlignedByteBuffer) -> Vec<PageAlignedByteBuffer> {
+        let added = {
+            let mut state = self.state.lock().unwrap();
+            if state.last_check.elapsed() >= CHECK_INTERVAL {
+                let hasher = self.hasher.sample();
+                let writers: Vec<QueueSample> = self.writers.iter().map(|x| x.sample()).collect();
+                let writer_wait = writers
+                    .iter()
+                    .zip(state.writers.iter())
+                    .map(|(now, then)| now.since(*then).wait_us)
+                    .max()
+                    .unwrap_or(0);
+                let depth = decide(
+                    state.depth,
+                    self.min_depth,
+                    self.max_depth,
+                    self.writers.len() as u64,
+                    state.last_check.elapsed(),
+                    hasher.since(state.hasher),
+                    writer_wait,
+                );
+                if depth < state.depth {
+                    state.shrinks += 1;
+                    state.smallest = min(state.smallest, depth);
+                } else if depth > state.depth {
+                    state.grows += 1;
+                }
+                state.depth = depth;
+                state.last_check = Instant::now();
+                state.hasher = hasher;
+                state.writers = writers;
+            }
+            let depth = state.depth;
+            let buffers = &mut state.buffers[drive];
+            if *buffers > depth {
+                *buffers -= 1;
+                // dropping it gives the memory back
+                return Vec::new();
+            }
+            let added = depth - *buffers;
+            *buffers = depth;
+            added
+        };
+
+        let size = buffer.size();
+        let mut buffers = vec![buffer];
+        for _ in 0..added {
+            buffers.push((self.allocate)(size));
+        }
+        buffers
+    }
+
+    // None if the depth never had to change
+    pub fn summary(&self) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        if state.shrinks == 0 && state.grows == 0 {
+            return None;
+        }
+        Some(format!(
+            "Queue depth: shrunk {}x, grown {}x, smallest {}, now {} of {} buffers per file",
+            state.shrinks, state.grows, state.smallest, state.depth, self.max_depth
+        ))
+    }
+}
+
+// one buffer per file more while hashing and the slowest writer both stalled,
+// one less while the hasher found at least a spare buffer per file waiting
+fn decide(
+    depth: u64,
+    min_depth: u64,
+    max_depth: u64,
+    files: u64,
+    elapsed: Duration,
+    hasher: QueueSample,
+    writer_wait_us: u64,
+) -> u64 {
+    let elapsed_us = elapsed.as_micros() as u64;
+    let stalled = |wait_us: u64| wait_us * 100 > elapsed_us * STALL_PERCENT;
+    if stalled(hasher.wait_us) && stalled(writer_wait_us) {
+        return min(depth + 1, max_depth);
+    }
+    let idle = hasher.wait_us * 100 < elapsed_us * IDLE_PERCENT;
+    if idle && hasher.receives > 0 && hasher.queued >= hasher.receives * files {
+        return max(depth.saturating_sub(1), min_depth);
+    }
+    depth
+}
+
+#[cfg(test)]
+mod queue_tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_secs(10);
+
+    fn hasher(wait_ms: u64, receives: u64, queued: u64) -> QueueSample {
+        QueueSample {
+            receives,
+            wait_us: wait_ms * 1000,
+            queued,
+        }
+    }
+
+    #[test]
+    fn depth_follows_the_waits() {
+        // hashing and writing stall each other, grow up to the maximum
+        assert_eq!(decide(2, 2, 4, 1, INTERVAL, hasher(3000, 5, 0), 2_000_000), 3);
+        assert_eq!(decide(4, 2, 4, 1, INTERVAL, hasher(3000, 5, 0), 2_000_000), 4);
+        // only the writer waits: hashing is the bottleneck, more buffers don't help
+        assert_eq!(decide(3, 2, 4, 1, INTERVAL, hasher(0, 5, 0), 9_000_000), 3);
+        // spare buffers queued for every file, shrink down to the minimum
+        assert_eq!(decide(4, 2, 4, 2, INTERVAL, hasher(10, 5, 10), 9_000_000), 3);
+        assert_eq!(decide(2, 2, 4, 2, INTERVAL, hasher(10, 5, 10), 9_000_000), 2);
+        // not for every file, or nothing received, stay
+        assert_eq!(decide(4, 2, 4, 2, INTERVAL, hasher(10, 5, 5), 9_000_000), 4);
+        assert_eq!(decide(4, 2, 4, 2, INTERVAL, hasher(0, 0, 0), 0), 4);
+    }
+
+    #[test]
+    fn recycled_buffers_follow_the_depth() {
+        let (_, hasher_stats) = MeteredReceiver::<()>::new(crossbeam_channel::bounded(1).1);
+        let (_, writer_stats) = MeteredReceiver::<()>::new(crossbeam_channel::bounded(1).1);
+        let governor = DepthGovernor::new(
+            1,
+            2,
+            hasher_stats,
+            vec![writer_stats],
+            Box::new(PageAlignedByteBuffer::new),
+        );
+        // at the maximum buffers go back as they are
+        let buffers = governor.recycle(0, PageAlignedByteBuffer::new(4096));
+        assert_eq!(buffers.len(), 1);
+
+        governor.state.lock().unwrap().depth = 1;
+        assert!(governor.recycle(0, PageAlignedByteBuffer::new(4096)).is_empty());
+        assert_eq!(governor.recycle(0, PageAlignedByteBuffer::new(4096)).len(), 1);
+
+        governor.state.lock().unwrap().depth = 2;
+        let buffers = governor.recycle(0, PageAlignedByteBuffer::new(4096));
+        assert_eq!(buffers.len(), 2);
+        assert!(buffers.iter().all(|x| x.size() == 4096));
+    }
+}
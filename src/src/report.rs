@@ -0,0 +1,226 @@
+use crate::cgroup::CgroupLimits;
+use crate::queue::QueueStats;
+use crate::scheduler::DeviceStats;
+use crate::writer::WriterStats;
+use serde::Serialize;
+use std::fs;
+use std::io;
+
+// Everything needed to compare runs across machines and versions, written as
+// json. Throughputs are nonces/minute and MiB/s over the whole run.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub version: String,
+    pub benchmark: bool,
+    pub cpu: CpuReport,
+    pub buffers: BufferReport,
+    pub nonces: u64,
+    pub elapsed_ms: u64,
+    pub nonces_per_minute: f64,
+    pub devices: Vec<DeviceReport>,
+    // empty in benchmark mode, nothing is written
+    pub writers: Vec<WriterReport>,
+    pub queues: QueueReport,
+    // cgroup limits the plotter ran under, version 0 without a cgroup
+    pub limits: CgroupLimits,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CpuReport {
+    pub brand: String,
+    pub simd: String,
+    pub threads: u32,
+    pub cores: u32,
+    // one hashing pool per numa node with threads
+    pub threads_per_node: Vec<u64>,
+    // every node's stripe of the buffers is bound to its memory
+    pub buffers_bound: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BufferReport {
+    pub per_file: u64,
+    pub files: u64,
+    pub size: u64,
+    // the pages asked for and what the buffers got, fallbacks differ
+    pub requested: String,
+    pub allocations: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeviceReport {
+    pub name: String,
+    pub cores: usize,
+    pub worksize: usize,
+    pub nonces: u64,
+    pub nonces_per_minute: f64,
+    // time with work in flight, and its share of the run
+    pub busy_ms: u64,
+    pub utilisation: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WriterReport {
+    pub path: String,
+    pub bytes: u64,
+    pub busy_ms: u64,
+    pub mib_per_second: f64,
+}
+
+// the hasher waiting for empty buffers and every writer for full ones
+#[derive(Debug, Serialize)]
+pub struct QueueReport {
+    pub hasher: WaitReport,
+    pub writers: Vec<WaitReport>,
+    // "hashing" or "writing", whoever waits less
+    pub bottleneck: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WaitReport {
+    pub wait_ms: u64,
+    pub avg_queued: f64,
+}
+
+impl From<&QueueStats> for WaitReport {
+    fn from(stats: &QueueStats) -> WaitReport {
+        WaitReport {
+            wait_ms: stats.wait_time().as_millis() as u64,
+            avg_queued: stats.avg_queued(),
+        }
+    }
+}
+
+pub fn queue_report(hasher: WaitReport, writers: Vec<WaitReport>) -> QueueReport {
+    let writer_wait = writers.iter().map(|x| x.wait_ms as f64).sum::<f64>() / writers.len().max(1) as f64;
+    let bottleneck = if hasher.wait_ms as f64 > writer_wait {
+        "writing"
+    } else {
+        "hashing"
+    };
+    QueueReport {
+        hasher,
+        writers,
+        bottleneck: bottleneck.to_string(),
+    }
+}
+
+impl QueueReport {
+    // one line per queue and the bottleneck
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "Hashing waited {:.1}s for empty buffers, {:.2} queued on average.",
+            self.hasher.wait_ms as f64 / 1000.0,
+            self.hasher.avg_queued
+        )];
+        for (drive, writer) in self.writers.iter().enumerate() {
+            lines.push(format!(
+                "Writer {} waited {:.1}s for full buffers, {:.2} queued on average.",
+                drive + 1,
+                writer.wait_ms as f64 / 1000.0,
+                writer.avg_queued
+            ));
+        }
+        lines.push(format!("Bottleneck: {}", self.bottleneck));
+        lines
+    }
+}
+
+pub fn device_reports(devices: &[DeviceStats], elapsed_ms: u64) -> Vec<DeviceReport> {
+    devices
+        .iter()
+        .map(|x| DeviceReport {
+            name: x.name.clone(),
+            cores: x.cores,
+            worksize: x.worksize,
+            nonces: x.nonces,
+            nonces_per_minute: x.nonces as f64 * 1000.0 / (elapsed_ms as f64 + 1.0) * 60.0,
+            busy_ms: x.busy.as_millis() as u64,
+            utilisation: (x.busy.as_millis() as f64 / (elapsed_ms as f64 + 1.0)).min(1.0),
+        })
+        .collect()
+}
+
+// write throughput while writing, waits for buffers don't count
+pub fn writer_report(path: &str, stats: &WriterStats) -> WriterReport {
+    let busy = stats.busy.as_secs_f64();
+    WriterReport {
+        path: path.to_string(),
+        bytes: stats.bytes,
+        busy_ms: stats.busy.as_millis() as u64,
+        mib_per_second: if busy > 0.0 {
+            stats.bytes as f64 / 1024.0 / 1024.0 / busy
+        } else {
+            0.0
+        },
+    }
+}
+
+impl Report {
+    // "-" prints to stdout
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if path == "-" {
+            println!("{}", json);
+            Ok(())
+        } else {
+            fs::write(path, json + "\n")
+        }
+    }
+}
+
+#[cfg(test)]
+mod report_tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn throughput_per_device_and_writer() {
+        let devices = vec![
+            DeviceStats {
+                name: "CPU".to_string(),
+                cores: 8,
+                worksize: 64,
+                nonces: 999,
+                busy: Duration::from_secs(60),
+            },
+            DeviceStats {
+                name: "gfx1030".to_string(),
+                cores: 40,
+                worksize: 10240,
+                nonces: 2997,
+                busy: Duration::from_secs(15),
+            },
+        ];
+        let reports = device_reports(&devices, 59999);
+        assert_eq!(reports[0].nonces_per_minute.round(), 999.0);
+        assert_eq!(reports[1].nonces_per_minute.round(), 2997.0);
+        assert_eq!(reports[0].utilisation, 1.0);
+        assert_eq!((reports[1].utilisation * 100.0).round(), 25.0);
+        assert_eq!(reports[1].busy_ms, 15000);
+
+        let stats = WriterStats {
+            bytes: 300 << 20,
+            busy: Duration::from_secs(2),
+        };
+        let writer = writer_report("/plots", &stats);
+        assert_eq!(writer.mib_per_second, 150.0);
+        assert_eq!(writer.busy_ms, 2000);
+        assert_eq!(writer_report("/plots", &WriterStats::default()).mib_per_second, 0.0);
+    }
+
+    #[test]
+    fn bottleneck_waits_less() {
+        let wait = |wait_ms| WaitReport {
+            wait_ms,
+            avg_queued: 0.5,
+        };
+        let queues = queue_report(wait(9000), vec![wait(100), wait(300)]);
+        assert_eq!(queues.bottleneck, "writing");
+        assert_eq!(queues.lines().len(), 4);
+        assert_eq!(queues.lines()[2], "Writer 2 waited 0.3s for full buffers, 0.50 queued on average.");
+        assert_eq!(queue_report(wait(100), vec![wait(9000)]).bottleneck, "hashing");
+        assert_eq!(queue_report(WaitReport::from(&QueueStats::default()), Vec::new()).bottleneck, "hashing");
+    }
+}
@@ -1,238 +1,629 @@
-use crate::cpu_hasher::{SimdExtension, hash_cpu, CpuTask, SafePointer};
-use crate::buffer::PageAlignedByteBuffer;
-#[cfg(feature = "opencl")]
-use crate::gpu_hasher::{create_gpu_hasher_thread, GpuTask};
-#[cfg(feature = "opencl")]
-use crate::ocl::gpu_init;
-use crate::plotter::{PlotterTask, NONCE_SIZE};
-#[cfg(feature = "opencl")]
-use crossbeam_channel::unbounded;
-use crossbeam_channel::{Receiver, Sender};
-use std::cmp::min;
-use std::sync::mpsc::channel;
-use std::sync::Arc;
-#[cfg(feature = "opencl")]
-use std::thread;
-
-const CPU_TASK_SIZE: u64 = 64;
-
-pub fn create_scheduler_thread(
-    task: Arc<PlotterTask>,
-    thread_pool: rayon::ThreadPool,
-    mut nonces_hashed: u64,
-    mut pb: Option<pbr::ProgressBar<pbr::Pipe>>,
-    rx_empty_buffers: Receiver<PageAlignedByteBuffer>,
-    tx_buffers_to_writer: Sender<PageAlignedByteBuffer>,
-    simd_ext: SimdExtension,
-) -> impl FnOnce() {
-    move || {
-        // synchronisation chanel for all hashing devices (CPU+GPU)
-        // message protocol:    (hash_device_id: u8, message: u8, nonces processed: u64)
-        // hash_device_id:      0=CPU, 1=GPU0, 2=GPU1...
-        // message:             0 = data ready to write
-        //                      1 = device ready to compute next hashing batch
-        // nonces_processed:    nonces hashed / nonces writen to host buffer
-        let (tx, rx) = channel();
-
-        // create gpu threads and channels
-        #[cfg(feature = "opencl")]
-        let gpu_contexts = match &task.gpus {
-            Some(x) => Some(gpu_init(&x, task.zcb)),
-            None => None,
-        };
-
-        #[cfg(feature = "opencl")]
-        let gpus = match gpu_contexts {
-            Some(x) => x,
-            None => Vec::new(),
-        };
-        #[cfg(feature = "opencl")]
-        let mut gpu_threads = Vec::new();
-        #[cfg(feature = "opencl")]
-        let mut gpu_channels = Vec::new();
-
-        #[cfg(feature = "opencl")]
-        for (i, gpu) in gpus.iter().enumerate() {
-            gpu_channels.push(unbounded());
-            gpu_threads.push(thread::spawn({
-                create_gpu_hasher_thread(
-                    (i + 1) as u8,
-                    gpu.clone(),
-                    tx.clone(),
-                    gpu_channels.last().unwrap().1.clone(),
-                )
-            }));
-        }
-
-        for buffer in rx_empty_buffers {
-            let mut_bs = &buffer.get_buffer();
-            let mut bs = mut_bs.lock().unwrap();
-            let buffer_size = (*bs).len() as u64;
-            let nonces_to_hash = min(buffer_size / NONCE_SIZE, task.nonces - nonces_hashed);
-
-            let mut requested = 0u64;
-            let mut processed = 0u64;
-
-            // kickoff first gpu and cpu runs
-            #[cfg(feature = "opencl")]
-            for (i, gpu) in gpus.iter().enumerate() {
-                // schedule next gpu task
-                let gpu = gpu.lock().unwrap();
-                let task_size = min(gpu.worksize as u64, nonces_to_hash - requested);
-                if task_size > 0 {
-                    gpu_channels[i]
-                        .0
-                        .send(Some(GpuTask {
-                            cache: SafePointer {
-                                ptr: bs.as_mut_ptr(),
-                            },
-                            cache_size: buffer_size / NONCE_SIZE,
-                            chunk_offset: requested,
-                            numeric_id: task.numeric_id,
-                            local_startnonce: task.start_nonce + nonces_hashed + requested,
-                            local_nonces: task_size,
-                        }))
-                        .unwrap();
-                }
-                requested += task_size;
-                //println!("Debug: Device: {} started. {} nonces assigned. Total requested: {}\n\n\n",i+1,task_size,requested);
-            }
-
-            for _ in 0..task.cpu_threads {
-                let task_size = min(CPU_TASK_SIZE, nonces_to_hash - requested);
-                if task_size > 0 {
-                    let task = hash_cpu(
-                        tx.clone(),
-                        CpuTask {
-                            cache: SafePointer {
-                                ptr: bs.as_mut_ptr(),
-                            },
-                            cache_size: (buffer_size / NONCE_SIZE) as usize,
-                            chunk_offset: requested as usize,
-                            numeric_id: task.numeric_id,
-                            local_startnonce: task.start_nonce + nonces_hashed + requested,
-                            local_nonces: task_size,
-                        },
-                        simd_ext.clone(),
-                    );
-                    thread_pool.spawn(task);
-                }
-                requested += task_size;
-            }
-
-            // control loop
-            let rx = &rx;
-            for msg in rx {
-                match msg.1 {
-                    // process a request for work: provide a task or signal completion
-                    1 => {
-                        let task_size = match msg.0 {
-                            0 => {
-                                // schedule next cpu task
-                                let task_size = min(CPU_TASK_SIZE, nonces_to_hash - requested);
-                                if task_size > 0 {
-                                    let task = hash_cpu(
-                                        tx.clone(),
-                                        CpuTask {
-                                            cache: SafePointer {
-                                                ptr: bs.as_mut_ptr(),
-                                            },
-                                            cache_size: (buffer_size / NONCE_SIZE) as usize,
-                                            chunk_offset: requested as usize,
-                                            numeric_id: task.numeric_id,
-                                            local_startnonce: task.start_nonce
-                                                + nonces_hashed
-                                                + requested,
-                                            local_nonces: task_size,
-                                        },
-                                        simd_ext.clone(),
-                                    );
-                                    thread_pool.spawn(task);
-                                }
-                                task_size
-                            }
-                            _ => {
-                                // schedule next gpu task
-                                #[cfg(feature = "opencl")]
-                                let gpu = gpus[(msg.0 - 1) as usize].lock().unwrap();
-                                #[cfg(feature = "opencl")]
-                                let task_size =
-                                    min(gpu.worksize as u64, nonces_to_hash - requested);
-
-                                // optimisation: leave some work for cpu in dual mode
-                                #[cfg(feature = "opencl")]
-                                let task_size = if task_size < gpu.worksize as u64
-                                    && task.cpu_threads > 0
-                                    && task_size > CPU_TASK_SIZE
-                                {
-                                    task_size / 2
-                                } else {
-                                    task_size
-                                };
-
-                                #[cfg(not(feature = "opencl"))]
-                                let task_size = 0;
-
-                                #[cfg(feature = "opencl")]
-                                gpu_channels[(msg.0 - 1) as usize]
-                                    .0
-                                    .send(Some(GpuTask {
-                                        cache: SafePointer {
-                                            ptr: bs.as_mut_ptr(),
-                                        },
-                                        cache_size: buffer_size / NONCE_SIZE,
-                                        chunk_offset: requested,
-                                        numeric_id: task.numeric_id,
-                                        local_startnonce: task.start_nonce
-                                            + nonces_hashed
-                                            + requested,
-                                        local_nonces: task_size,
-                                    }))
-                                    .unwrap();
-                                task_size
-                            }
-                        };
-
-                        requested += task_size;
-                        //println!("Debug: Device: {} asked for work. {} nonces assigned. Total requested: {}\n\n\n",msg.0,task_size,requested);
-                    }
-                    // process work completed message
-                    0 => {
-                        processed += msg.2;
-                        match &mut pb {
-                            Some(pb) => {
-                                pb.add(msg.2 * NONCE_SIZE);
-                            }
-                            None => (),
-                        }
-                    }
-                    _ => {}
-                }
-                if processed == nonces_to_hash {
-                    break;
-                }
-            }
-
-            nonces_hashed += nonces_to_hash;
-
-            // queue buffer for writing
-            tx_buffers_to_writer.send(buffer).unwrap();
-
-            // thread end
-            if task.nonces == nonces_hashed {
-                match &mut pb {
-                    Some(pb) => {
-                        pb.finish_print("Hasher done.");
-                    }
-                    None => (),
-                }
-                // shutdown gpu threads
-                #[cfg(feature = "opencl")]
-                for gpu in &gpu_channels {
-                    gpu.0.send(None).unwrap();
-                }
-                break;
-            };
-        }
-    }
-}
+use crate::buffer::{NonceView, PageAlignedByteBuffer};
+use crate::plotter::{PlotterTask, NONCE_SIZE};
+use crate::queue::MeteredReceiver;
+use crate::numa;
+use crossbeam_channel::Sender;
+use std::cmp::min;
+use std::ops::Range;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// smallest chunk the last nonces of a buffer are split into
+const MIN_TASK_SIZE: u64 = 16;
+
+// a hashing device and the nonces it contributed, the cpu comes first
+#[derive(Clone, Debug)]
+pub struct DeviceStats {
+    pub name: String,
+    // cpu threads or gpu compute units
+    pub cores: usize,
+    // nonces per task
+    pub worksize: usize,
+    pub nonces: u64,
+    // time with work in flight
+    pub busy: Duration,
+}
+
+// what hashing devices tell the scheduler, tagged with the device's index
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeviceMessage {
+    // ready for the next task, a gpu may still be transferring the last one
+    Ready(usize),
+    // nonces hashed and written to the buffer
+    Hashed(usize, u64),
+    // computed wrong nonces, no more tasks for it. It still reports what it
+    // has, sent before those so the scheduler knows when the buffer is done.
+    #[cfg_attr(not(feature = "opencl"), allow(dead_code))]
+    Retired(usize),
+    // computed the wrong nonce, the buffer must not be written. It still
+    // reports the tasks it has, sent after this, without hashing them.
+    #[cfg_attr(not(feature = "opencl"), allow(dead_code))]
+    Failed(usize, u64),
+}
+
+// a range of nonces of the buffer being hashed
+pub struct HashTask {
+    pub view: NonceView,
+    pub numeric_id: u64,
+    pub local_startnonce: u64,
+}
+
+// Anything that hashes nonces into the buffers: a cpu thread pool, an opencl
+// context, or a test double. Devices work asynchronously and report back over
+// the channel they get on start.
+pub trait HashDevice: Send {
+    // name, cores and worksize, nonces are counted by the scheduler
+    fn stats(&self) -> DeviceStats;
+
+    fn worksize(&self) -> u64;
+
+    // tasks handed out at once at the start of a buffer
+    fn slots(&self) -> usize {
+        1
+    }
+
+    // the numa stripe of each buffer the device hashes, None takes from the
+    // stripe with the most work left
+    fn stripe(&self) -> Option<usize> {
+        None
+    }
+
+    fn start(&mut self, id: usize, tx: std::sync::mpsc::Sender<DeviceMessage>);
+
+    // the task's view has to be dropped before its nonces are reported hashed,
+    // the buffer goes to the writer right after the last of them
+    fn hash(&mut self, task: HashTask);
+
+    // no work left in this buffer, report whatever is still in flight
+    fn flush(&mut self) {}
+
+    fn shutdown(&mut self) {}
+}
+
+// Hashes for one or more plot files. Empty buffers arrive tagged with the index of
+// the file (drive) they belong to and are handed to that file's writer when full.
+// Every thread pool hashes its own NUMA stripe of each buffer (see numa::stripes).
+pub fn create_scheduler_thread(
+    tasks: Vec<Arc<PlotterTask>>,
+    mut devices: Vec<Box<dyn HashDevice>>,
+    mut nonces_hashed: Vec<u64>,
+    mut pb: Option<pbr::ProgressBar<pbr::Pipe>>,
+    rx_empty_buffers: MeteredReceiver<(usize, PageAlignedByteBuffer)>,
+    tx_buffers_to_writer: Vec<Sender<PageAlignedByteBuffer>>,
+) -> impl FnOnce() -> Result<Vec<DeviceStats>, String> {
+    move || {
+        // synchronisation channel for all hashing devices
+        let (tx, rx) = channel();
+        for (id, device) in devices.iter_mut().enumerate() {
+            device.start(id, tx.clone());
+        }
+        let mut meters = Meters::new(devices.iter().map(|x| x.stats()).collect());
+        // nonces per numa stripe, cpu pools are the devices with a stripe
+        let pool_threads: Vec<u64> = devices
+            .iter()
+            .filter(|x| x.stripe().is_some())
+            .map(|x| x.slots() as u64)
+            .collect();
+
+        let mut failure = None;
+        for (drive, mut buffer) in rx_empty_buffers {
+            let task = &tasks[drive];
+            // file already complete, its remaining buffers aren't needed anymore
+            if nonces_hashed[drive] == task.nonces {
+                continue;
+            }
+            let hashed = nonces_hashed[drive];
+
+            let buffer_nonces = buffer.nonces();
+            let nonces_to_hash = min(buffer_nonces, task.nonces - hashed);
+
+            let mut job = BufferJob {
+                buffer: &mut buffer,
+                numeric_id: task.numeric_id,
+                start_nonce: task.start_nonce + hashed,
+            };
+            // the last buffer of a file may not be full
+            let mut stripes = numa::stripes(buffer_nonces, &pool_threads);
+            for stripe in stripes.iter_mut() {
+                stripe.start = min(stripe.start, nonces_to_hash);
+                stripe.end = min(stripe.end, nonces_to_hash);
+            }
+
+            let result = hash_buffer(&mut devices, &rx, &mut job, &mut stripes, nonces_to_hash, &mut meters, |nonces| {
+                if let Some(pb) = &mut pb {
+                    pb.add(nonces * NONCE_SIZE);
+                }
+            });
+            // the buffer is dropped unwritten, the writers stop with what they have
+            if let Err(e) = result {
+                if let Some(pb) = &mut pb {
+                    pb.finish_print("Hasher failed.");
+                }
+                failure = Some(e);
+                break;
+            }
+
+            nonces_hashed[drive] += nonces_to_hash;
+
+            // queue buffer for writing
+            tx_buffers_to_writer[drive].send(buffer).unwrap();
+
+            // thread end
+            if tasks
+                .iter()
+                .zip(nonces_hashed.iter())
+                .all(|(task, hashed)| task.nonces == *hashed)
+            {
+                if let Some(pb) = &mut pb {
+                    pb.finish_print("Hasher done.");
+                }
+                break;
+            };
+        }
+        for device in devices.iter_mut() {
+            device.shutdown();
+        }
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(meters.stats),
+        }
+    }
+}
+
+// the buffer being hashed, lent out to all its tasks
+struct BufferJob<'a> {
+    buffer: &'a mut PageAlignedByteBuffer,
+    numeric_id: u64,
+    start_nonce: u64,
+}
+
+// What every device hashed and how long it had tasks in flight, which gives its
+// throughput once it finished something.
+struct Meters {
+    stats: Vec<DeviceStats>,
+    in_flight: Vec<u64>,
+    since: Vec<Instant>,
+    retired: Vec<bool>,
+}
+
+impl Meters {
+    fn new(stats: Vec<DeviceStats>) -> Meters {
+        let devices = stats.len();
+        Meters {
+            stats,
+            in_flight: vec![0; devices],
+            since: vec![Instant::now(); devices],
+            retired: vec![false; devices],
+        }
+    }
+
+    // retired devices only get work when no other device is left
+    fn takes_work(&self, id: usize) -> bool {
+        !self.retired[id] || self.retired.iter().all(|x| *x)
+    }
+
+    fn assigned(&mut self, id: usize) {
+        self.tick(id);
+        self.in_flight[id] += 1;
+    }
+
+    fn hashed(&mut self, id: usize, nonces: u64) {
+        self.tick(id);
+        self.in_flight[id] -= 1;
+        self.stats[id].nonces += nonces;
+    }
+
+    fn tick(&mut self, id: usize) {
+        let now = Instant::now();
+        if self.in_flight[id] > 0 {
+            self.stats[id].busy += now - self.since[id];
+        }
+        self.since[id] = now;
+    }
+
+    // nonces/s while busy, None before the first completed task
+    fn rate(&self, id: usize) -> Option<f64> {
+        let busy = self.stats[id].busy.as_secs_f64();
+        if self.stats[id].nonces > 0 && busy > 0.0 {
+            Some(self.stats[id].nonces as f64 / busy)
+        } else {
+            None
+        }
+    }
+}
+
+// Hands out the buffer's stripes until nonces_to_hash nonces are hashed, every
+// completion is passed on to hashed. When a device fails no more work is handed
+// out, and the error returns once every task in flight is back.
+fn hash_buffer(
+    devices: &mut [Box<dyn HashDevice>],
+    rx: &Receiver<DeviceMessage>,
+    job: &mut BufferJob,
+    stripes: &mut [Range<u64>],
+    nonces_to_hash: u64,
+    meters: &mut Meters,
+    mut hashed: impl FnMut(u64),
+) -> Result<(), String> {
+    // kickoff, devices without a stripe first so they get the largest ones
+    let order: Vec<usize> = (0..devices.len())
+        .filter(|&x| devices[x].stripe().is_none())
+        .chain((0..devices.len()).filter(|&x| devices[x].stripe().is_some()))
+        .collect();
+    let ids: Vec<usize> = order.into_iter().filter(|&x| meters.takes_work(x)).collect();
+    for id in ids {
+        for _ in 0..devices[id].slots() {
+            schedule(devices, id, stripes, job, meters);
+        }
+    }
+
+    // control loop
+    let mut processed = 0u64;
+    let mut failure = None;
+    for msg in rx {
+        match msg {
+            DeviceMessage::Ready(id) => {
+                if failure.is_none() && meters.takes_work(id) {
+                    schedule(devices, id, stripes, job, meters)
+                }
+            }
+            DeviceMessage::Hashed(id, nonces) => {
+                processed += nonces;
+                meters.hashed(id, nonces);
+                hashed(nonces);
+            }
+            DeviceMessage::Retired(id) => meters.retired[id] = true,
+            DeviceMessage::Failed(id, nonce) => {
+                failure = Some(format!(
+                    "{} computed a wrong nonce {} of account {}, stopped before it's written. \
+                     Lower its clocks or plot without it.",
+                    meters.stats[id].name, nonce, job.numeric_id
+                ));
+                // gpus hold their last task until flushed
+                for device in devices.iter_mut() {
+                    device.flush();
+                }
+            }
+        }
+        if processed == nonces_to_hash || (failure.is_some() && meters.in_flight.iter().all(|x| *x == 0)) {
+            break;
+        }
+    }
+    match failure {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+// hands a device its next chunk, or lets it finish when its stripe is done
+fn schedule(
+    devices: &mut [Box<dyn HashDevice>],
+    id: usize,
+    stripes: &mut [Range<u64>],
+    job: &mut BufferJob,
+    meters: &mut Meters,
+) {
+    let share = finishing_share(devices, id, stripes, meters);
+    let device = &mut devices[id];
+    let stripe = match device.stripe() {
+        Some(x) => &mut stripes[x],
+        None => largest_stripe(stripes),
+    };
+    let task_size = min(min(device.worksize(), share), stripe.end - stripe.start);
+    if task_size == 0 {
+        device.flush();
+        return;
+    }
+    device.hash(HashTask {
+        view: job.buffer.lend(stripe.start..stripe.start + task_size),
+        numeric_id: job.numeric_id,
+        local_startnonce: job.start_nonce + stripe.start,
+    });
+    meters.assigned(id);
+    stripe.start += task_size;
+}
+
+// A task slot's part of the nonces left, by measured throughput, so all devices
+// that can still help finish the buffer together. Unlimited until every one of
+// them has been measured.
+fn finishing_share(
+    devices: &[Box<dyn HashDevice>],
+    id: usize,
+    stripes: &[Range<u64>],
+    meters: &Meters,
+) -> u64 {
+    let left: u64 = stripes.iter().map(|x| x.end - x.start).sum();
+    let mut total_rate = 0.0;
+    for (i, device) in devices.iter().enumerate() {
+        if !meters.takes_work(i) {
+            continue;
+        }
+        // pools can't take from other stripes
+        if let Some(stripe) = device.stripe() {
+            if stripes[stripe].is_empty() {
+                continue;
+            }
+        }
+        match meters.rate(i) {
+            Some(rate) => total_rate += rate,
+            None => return u64::MAX,
+        }
+    }
+    let rate = match meters.rate(id) {
+        Some(rate) => rate / devices[id].slots() as f64,
+        None => return u64::MAX,
+    };
+    let share = (left as f64 * rate / total_rate).ceil() as u64;
+    share.max(MIN_TASK_SIZE)
+}
+
+// gpus don't care about numa placement, they take from the stripe with the most work left
+fn largest_stripe(stripes: &mut [Range<u64>]) -> &mut Range<u64> {
+    stripes
+        .iter_mut()
+        .max_by_key(|x| x.end - x.start)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod scheduler_tests {
+    use super::*;
+    use crate::cpu_hasher::CPU_TASK_SIZE;
+    use std::sync::mpsc;
+    use std::sync::Mutex;
+    use std::thread;
+
+    // hashes nothing, takes micros per nonce and records the ranges it was given
+    struct FakeDevice {
+        worksize: u64,
+        slots: usize,
+        stripe: Option<usize>,
+        micros: u64,
+        // a gpu reports the previous task when it gets the next one
+        pipelined: bool,
+        // reports its first task as wrong
+        fails: bool,
+        in_flight: Option<NonceView>,
+        sender: Option<(usize, mpsc::Sender<DeviceMessage>)>,
+        ranges: Arc<Mutex<Vec<Range<u64>>>>,
+    }
+
+    impl FakeDevice {
+        fn new(worksize: u64, slots: usize, stripe: Option<usize>, micros: u64) -> FakeDevice {
+            FakeDevice {
+                worksize,
+                slots,
+                stripe,
+                micros,
+                pipelined: stripe.is_none(),
+                fails: false,
+                in_flight: None,
+                sender: None,
+                ranges: Arc::new(Mutex::new(Vec::new())),
+            }
+        }
+    }
+
+    impl HashDevice for FakeDevice {
+        fn stats(&self) -> DeviceStats {
+            DeviceStats {
+                name: "fake".to_string(),
+                cores: self.slots,
+                worksize: self.worksize as usize,
+                nonces: 0,
+                busy: Duration::ZERO,
+            }
+        }
+
+        fn worksize(&self) -> u64 {
+            self.worksize
+        }
+
+        fn slots(&self) -> usize {
+            self.slots
+        }
+
+        fn stripe(&self) -> Option<usize> {
+            self.stripe
+        }
+
+        fn start(&mut self, id: usize, tx: mpsc::Sender<DeviceMessage>) {
+            self.sender = Some((id, tx));
+        }
+
+        fn hash(&mut self, task: HashTask) {
+            let (id, tx) = self.sender.clone().unwrap();
+            self.ranges.lock().unwrap().push(task.view.range());
+            let duration = Duration::from_micros(self.micros * task.view.nonces());
+            let previous = if self.pipelined {
+                self.in_flight.replace(task.view)
+            } else {
+                Some(task.view)
+            };
+            let fails = std::mem::replace(&mut self.fails, false);
+            thread::spawn(move || {
+                thread::sleep(duration);
+                if fails {
+                    tx.send(DeviceMessage::Failed(id, 0)).unwrap();
+                }
+                tx.send(DeviceMessage::Ready(id)).unwrap();
+                if let Some(view) = previous {
+                    let nonces = view.nonces();
+                    drop(view);
+                    tx.send(DeviceMessage::Hashed(id, nonces)).unwrap();
+                }
+            });
+        }
+
+        fn flush(&mut self) {
+            let (id, tx) = self.sender.clone().unwrap();
+            if let Some(view) = self.in_flight.take() {
+                let nonces = view.nonces();
+                drop(view);
+                tx.send(DeviceMessage::Hashed(id, nonces)).unwrap();
+            }
+        }
+    }
+
+    // the scheduler's side of create_scheduler_thread without the plot files
+    struct Bench {
+        devices: Vec<Box<dyn HashDevice>>,
+        ranges: Vec<Arc<Mutex<Vec<Range<u64>>>>>,
+        rx: Receiver<DeviceMessage>,
+        meters: Meters,
+    }
+
+    impl Bench {
+        fn new(devices: Vec<FakeDevice>) -> Bench {
+            let ranges = devices.iter().map(|x| x.ranges.clone()).collect();
+            let mut devices: Vec<Box<dyn HashDevice>> = devices
+                .into_iter()
+                .map(|x| Box::new(x) as Box<dyn HashDevice>)
+                .collect();
+            let (tx, rx) = channel();
+            for (id, device) in devices.iter_mut().enumerate() {
+                device.start(id, tx.clone());
+            }
+            let meters = Meters::new(devices.iter().map(|x| x.stats()).collect());
+            Bench {
+                devices,
+                ranges,
+                rx,
+                meters,
+            }
+        }
+
+        // hashes one buffer, returns the ranges every device got
+        fn buffer(&mut self, stripes: &mut [Range<u64>]) -> Vec<Vec<Range<u64>>> {
+            self.try_buffer(stripes).unwrap()
+        }
+
+        fn try_buffer(&mut self, stripes: &mut [Range<u64>]) -> Result<Vec<Vec<Range<u64>>>, String> {
+            for ranges in &self.ranges {
+                ranges.lock().unwrap().clear();
+            }
+            let nonces = stripes.iter().map(|x| x.end - x.start).sum();
+            let size = stripes.last().unwrap().end * NONCE_SIZE;
+            let mut buffer = PageAlignedByteBuffer::new(size as usize);
+            let mut job = BufferJob {
+                buffer: &mut buffer,
+                numeric_id: 0,
+                start_nonce: 0,
+            };
+            let mut total = 0;
+            let result = hash_buffer(&mut self.devices, &self.rx, &mut job, stripes, nonces, &mut self.meters, |n| {
+                total += n
+            });
+            // every view is back
+            buffer.as_slice();
+            result?;
+            assert_eq!(total, nonces);
+            Ok(self.ranges.iter().map(|x| x.lock().unwrap().clone()).collect())
+        }
+    }
+
+    fn nonces(ranges: &[Range<u64>]) -> u64 {
+        ranges.iter().map(|x| x.end - x.start).sum()
+    }
+
+    // every nonce exactly once
+    fn assert_covers(ranges: &[Vec<Range<u64>>], nonces: u64) {
+        let mut all: Vec<Range<u64>> = ranges.iter().flatten().cloned().collect();
+        all.sort_by_key(|x| x.start);
+        let mut next = 0;
+        for range in all {
+            assert_eq!(range.start, next, "gap or overlap at {}", next);
+            next = range.end;
+        }
+        assert_eq!(next, nonces);
+    }
+
+    #[test]
+    fn cpu_and_fast_gpu_hash_every_nonce_once() {
+        let cpu = FakeDevice::new(CPU_TASK_SIZE, 4, Some(0), 1600);
+        let gpu = FakeDevice::new(128, 1, None, 40);
+        let mut bench = Bench::new(vec![cpu, gpu]);
+        let ranges = bench.buffer(&mut [Range { start: 0, end: 1024 }]);
+        assert_covers(&ranges, 1024);
+        let stats = &bench.meters.stats;
+        assert_eq!(stats[0].nonces, nonces(&ranges[0]));
+        assert_eq!(stats[0].nonces + stats[1].nonces, 1024);
+        // the gpu is 40x faster and does most of the work
+        assert!(stats[1].nonces > stats[0].nonces, "{:?}", stats);
+        assert!(stats.iter().all(|x| x.busy > Duration::ZERO));
+    }
+
+    #[test]
+    fn pools_keep_to_their_stripes() {
+        let pool_a = FakeDevice::new(CPU_TASK_SIZE, 2, Some(0), 10);
+        let pool_b = FakeDevice::new(CPU_TASK_SIZE, 2, Some(1), 10);
+        let mut bench = Bench::new(vec![pool_a, pool_b]);
+        // last buffer of a file, only partly used
+        let mut stripes = vec![0..300, 512..700];
+        let ranges = bench.buffer(&mut stripes);
+        assert!(ranges[0].iter().all(|x| x.end <= 300));
+        assert!(ranges[1].iter().all(|x| x.start >= 512));
+        assert_eq!((nonces(&ranges[0]), nonces(&ranges[1])), (300, 188));
+        assert_eq!(stripes, vec![300..300, 700..700]);
+    }
+
+    #[test]
+    fn retired_devices_get_no_work() {
+        let cpu = FakeDevice::new(CPU_TASK_SIZE, 4, Some(0), 10);
+        let gpu = FakeDevice::new(128, 1, None, 1);
+        let mut bench = Bench::new(vec![cpu, gpu]);
+        bench.meters.retired[1] = true;
+        let ranges = bench.buffer(&mut [Range { start: 0, end: 512 }]);
+        assert_covers(&ranges, 512);
+        assert!(ranges[1].is_empty(), "{:?}", ranges[1]);
+        // nobody else left, the retired device has to do it
+        bench.meters.retired[0] = true;
+        let ranges = bench.buffer(&mut [Range { start: 0, end: 512 }]);
+        assert_covers(&ranges, 512);
+    }
+
+    #[test]
+    fn failed_device_stops_the_buffer() {
+        let cpu = FakeDevice::new(CPU_TASK_SIZE, 4, Some(0), 10);
+        let mut gpu = FakeDevice::new(128, 1, None, 1);
+        gpu.fails = true;
+        let mut bench = Bench::new(vec![cpu, gpu]);
+        let error = bench.try_buffer(&mut [Range { start: 0, end: 4096 }]).unwrap_err();
+        assert!(error.contains("wrong nonce 0"), "{}", error);
+        // nothing is handed out after the failure
+        let handed_out: u64 = bench.ranges.iter().map(|x| nonces(&x.lock().unwrap())).sum();
+        assert!(handed_out < 4096, "{}", handed_out);
+        assert!(bench.meters.in_flight.iter().all(|x| *x == 0));
+    }
+
+    #[test]
+    fn measured_devices_finish_together() {
+        // a 2.5k nonces/s cpu with 4 threads next to a 12.5k nonces/s gpu
+        let devices: Vec<Box<dyn HashDevice>> = vec![
+            Box::new(FakeDevice::new(8, 4, Some(0), 0)),
+            Box::new(FakeDevice::new(1024, 1, None, 0)),
+        ];
+        let unmeasured = Meters::new(devices.iter().map(|x| x.stats()).collect());
+        let mut meters = Meters::new(devices.iter().map(|x| x.stats()).collect());
+        for &(id, nonces) in [(0, 2500), (1, 12500)].iter() {
+            meters.stats[id].nonces = nonces;
+            meters.stats[id].busy = Duration::from_secs(1);
+        }
+        let stripes = [Range { start: 0, end: 1800 }];
+        assert_eq!(finishing_share(&devices, 1, &stripes, &unmeasured), u64::MAX);
+
+        // the gpu's chunk shrinks below its worksize to what it hashes while
+        // each cpu thread does its part, both take 120ms
+        let gpu = finishing_share(&devices, 1, &stripes, &meters);
+        let cpu = finishing_share(&devices, 0, &stripes, &meters);
+        assert_eq!((cpu, gpu), (75, 1500));
+        assert_eq!(4 * cpu + gpu, 1800);
+
+        // the last nonces aren't split any further
+        let tail = [Range { start: 0, end: 20 }];
+        assert_eq!(finishing_share(&devices, 0, &tail, &meters), MIN_TASK_SIZE);
+        // a pool whose stripe is done doesn't count, the gpu takes the rest
+        let other = [Range { start: 0, end: 0 }, Range { start: 0, end: 300 }];
+        assert_eq!(finishing_share(&devices, 1, &other, &meters), 300);
+    }
+}
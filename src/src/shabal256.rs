@@ -1,263 +1,400 @@
-use std::slice::from_raw_parts;
-
-const A_INIT: [u32; 12] = [
-    0x52F84552, 0xE54B7999, 0x2D8EE3EC, 0xB9645191, 0xE0078B86, 0xBB7C44C9, 0xD2B5C1CA, 0xB0D2EB8C,
-    0x14CE5A45, 0x22AF50DC, 0xEFFDBC6B, 0xEB21B74A,
-];
-
-const B_INIT: [u32; 16] = [
-    0xB555C6EE, 0x3E710596, 0xA72A652F, 0x9301515F, 0xDA28C1FA, 0x696FD868, 0x9CB6BF72, 0x0AFE4002,
-    0xA6E03615, 0x5138C1D4, 0xBE216306, 0xB38B8890, 0x3EA8B96B, 0x3299ACE4, 0x30924DD4, 0x55CB34A5,
-];
-
-const C_INIT: [u32; 16] = [
-    0xB405F031, 0xC4233EBA, 0xB3733979, 0xC0DD9D55, 0xC51C28AE, 0xA327B8E1, 0x56C56167, 0xED614433,
-    0x88B59D60, 0x60E2CEBA, 0x758B4B8B, 0x83E82A7F, 0xBC968828, 0xE6E00BF7, 0xBA839E55, 0x9B491C60,
-];
-
-pub fn shabal256_fast(data: &[u8], term: &[u32; 16]) -> [u8; 32] {
-    let mut a = A_INIT;
-    let mut b = B_INIT;
-    let mut c = C_INIT;
-    let mut w_high = 0u32;
-    let mut w_low = 1u32;
-    let mut num = data.len() >> 6;
-    let mut ptr = 0;
-    let data_ptr = data.as_ptr() as *const u32;
-    let data = unsafe { from_raw_parts(data_ptr, data.len() / 4) };
-
-    while num > 0 {
-        input_block_add(&mut b, &data[ptr..]);
-        xor_w(&mut a, w_low, w_high);
-        apply_p(&mut a, &mut b, &c, &data[ptr..]);
-        input_block_sub(&mut c, &data[ptr..]);
-        swap_bc(&mut b, &mut c);
-        incr_w(&mut w_low, &mut w_high);
-        ptr = ptr.wrapping_add(16);
-        num = num.wrapping_sub(1);
-    }
-    input_block_add(&mut b, term);
-    xor_w(&mut a, w_low, w_high);
-    apply_p(&mut a, &mut b, &c, term);
-    for _ in 0..3 {
-        swap_bc(&mut b, &mut c);
-        xor_w(&mut a, w_low, w_high);
-        apply_p(&mut a, &mut b, &c, term);
-    }
-    unsafe { *(b[8..16].as_ptr() as *const [u8; 32]) }
-}
-
-#[inline(always)]
-fn input_block_add(b: &mut [u32; 16], data: &[u32]) {
-    for (element, data) in b.iter_mut().zip(data.iter()) {
-        *element = element.wrapping_add(*data);
-    }
-}
-
-#[inline(always)]
-fn input_block_sub(c: &mut [u32; 16], data: &[u32]) {
-    for (element, data) in c.iter_mut().zip(data.iter()) {
-        *element = element.wrapping_sub(*data);
-    }
-}
-
-#[inline(always)]
-fn xor_w(a: &mut [u32; 12], w_low: u32, w_high: u32) {
-    a[0] ^= w_low;
-    a[1] ^= w_high;
-}
-
-#[inline(always)]
-fn apply_p(a: &mut [u32; 12], b: &mut [u32; 16], c: &[u32; 16], data: &[u32]) {
-    for element in b.iter_mut() {
-        *element = element.wrapping_shl(17) | element.wrapping_shr(15);
-    }
-    perm(a, b, c, data);
-    a[0] = a[0]
-        .wrapping_add(c[11])
-        .wrapping_add(c[15])
-        .wrapping_add(c[3]);
-    a[1] = a[1]
-        .wrapping_add(c[12])
-        .wrapping_add(c[0])
-        .wrapping_add(c[4]);
-    a[2] = a[2]
-        .wrapping_add(c[13])
-        .wrapping_add(c[1])
-        .wrapping_add(c[5]);
-    a[3] = a[3]
-        .wrapping_add(c[14])
-        .wrapping_add(c[2])
-        .wrapping_add(c[6]);
-    a[4] = a[4]
-        .wrapping_add(c[15])
-        .wrapping_add(c[3])
-        .wrapping_add(c[7]);
-    a[5] = a[5]
-        .wrapping_add(c[0])
-        .wrapping_add(c[4])
-        .wrapping_add(c[8]);
-    a[6] = a[6]
-        .wrapping_add(c[1])
-        .wrapping_add(c[5])
-        .wrapping_add(c[9]);
-    a[7] = a[7]
-        .wrapping_add(c[2])
-        .wrapping_add(c[6])
-        .wrapping_add(c[10]);
-    a[8] = a[8]
-        .wrapping_add(c[3])
-        .wrapping_add(c[7])
-        .wrapping_add(c[11]);
-    a[9] = a[9]
-        .wrapping_add(c[4])
-        .wrapping_add(c[8])
-        .wrapping_add(c[12]);
-    a[10] = a[10]
-        .wrapping_add(c[5])
-        .wrapping_add(c[9])
-        .wrapping_add(c[13]);
-    a[11] = a[11]
-        .wrapping_add(c[6])
-        .wrapping_add(c[10])
-        .wrapping_add(c[14]);
-}
-
-#[inline(always)]
-fn perm_elt(
-    a: &mut [u32; 12],
-    b: &mut [u32; 16],
-    xa0: usize,
-    xa1: usize,
-    xb0: usize,
-    xb1: usize,
-    xb2: usize,
-    xb3: usize,
-    xc: u32,
-    xm: u32,
-) {
-    unsafe {
-        *a.get_unchecked_mut(xa0) = (a.get_unchecked(xa0)
-            ^ ((a.get_unchecked(xa1).wrapping_shl(15u32)
-                | a.get_unchecked(xa1).wrapping_shr(17u32))
-            .wrapping_mul(5u32))
-            ^ xc)
-            .wrapping_mul(3u32)
-            ^ b.get_unchecked(xb1)
-            ^ (b.get_unchecked(xb2) & !b.get_unchecked(xb3))
-            ^ xm;
-        *b.get_unchecked_mut(xb0) = !((b.get_unchecked(xb0).wrapping_shl(1)
-            | b.get_unchecked(xb0).wrapping_shr(31))
-            ^ a.get_unchecked(xa0));
-    }
-}
-
-#[inline(always)]
-fn perm(a: &mut [u32; 12], b: &mut [u32; 16], c: &[u32; 16], data: &[u32]) {
-    unsafe {
-        perm_elt(a, b, 0, 11, 0, 13, 9, 6, c[8], *data.get_unchecked(0));
-        perm_elt(a, b, 1, 0, 1, 14, 10, 7, c[7], *data.get_unchecked(1));
-        perm_elt(a, b, 2, 1, 2, 15, 11, 8, c[6], *data.get_unchecked(2));
-        perm_elt(a, b, 3, 2, 3, 0, 12, 9, c[5], *data.get_unchecked(3));
-        perm_elt(a, b, 4, 3, 4, 1, 13, 10, c[4], *data.get_unchecked(4));
-        perm_elt(a, b, 5, 4, 5, 2, 14, 11, c[3], *data.get_unchecked(5));
-        perm_elt(a, b, 6, 5, 6, 3, 15, 12, c[2], *data.get_unchecked(6));
-        perm_elt(a, b, 7, 6, 7, 4, 0, 13, c[1], *data.get_unchecked(7));
-        perm_elt(a, b, 8, 7, 8, 5, 1, 14, c[0], *data.get_unchecked(8));
-        perm_elt(a, b, 9, 8, 9, 6, 2, 15, c[15], *data.get_unchecked(9));
-        perm_elt(a, b, 10, 9, 10, 7, 3, 0, c[14], *data.get_unchecked(10));
-        perm_elt(a, b, 11, 10, 11, 8, 4, 1, c[13], *data.get_unchecked(11));
-        perm_elt(a, b, 0, 11, 12, 9, 5, 2, c[12], *data.get_unchecked(12));
-        perm_elt(a, b, 1, 0, 13, 10, 6, 3, c[11], *data.get_unchecked(13));
-        perm_elt(a, b, 2, 1, 14, 11, 7, 4, c[10], *data.get_unchecked(14));
-        perm_elt(a, b, 3, 2, 15, 12, 8, 5, c[9], *data.get_unchecked(15));
-        perm_elt(a, b, 4, 3, 0, 13, 9, 6, c[8], *data.get_unchecked(0));
-        perm_elt(a, b, 5, 4, 1, 14, 10, 7, c[7], *data.get_unchecked(1));
-        perm_elt(a, b, 6, 5, 2, 15, 11, 8, c[6], *data.get_unchecked(2));
-        perm_elt(a, b, 7, 6, 3, 0, 12, 9, c[5], *data.get_unchecked(3));
-        perm_elt(a, b, 8, 7, 4, 1, 13, 10, c[4], *data.get_unchecked(4));
-        perm_elt(a, b, 9, 8, 5, 2, 14, 11, c[3], *data.get_unchecked(5));
-        perm_elt(a, b, 10, 9, 6, 3, 15, 12, c[2], *data.get_unchecked(6));
-        perm_elt(a, b, 11, 10, 7, 4, 0, 13, c[1], *data.get_unchecked(7));
-        perm_elt(a, b, 0, 11, 8, 5, 1, 14, c[0], *data.get_unchecked(8));
-        perm_elt(a, b, 1, 0, 9, 6, 2, 15, c[15], *data.get_unchecked(9));
-        perm_elt(a, b, 2, 1, 10, 7, 3, 0, c[14], *data.get_unchecked(10));
-        perm_elt(a, b, 3, 2, 11, 8, 4, 1, c[13], *data.get_unchecked(11));
-        perm_elt(a, b, 4, 3, 12, 9, 5, 2, c[12], *data.get_unchecked(12));
-        perm_elt(a, b, 5, 4, 13, 10, 6, 3, c[11], *data.get_unchecked(13));
-        perm_elt(a, b, 6, 5, 14, 11, 7, 4, c[10], *data.get_unchecked(14));
-        perm_elt(a, b, 7, 6, 15, 12, 8, 5, c[9], *data.get_unchecked(15));
-        perm_elt(a, b, 8, 7, 0, 13, 9, 6, c[8], *data.get_unchecked(0));
-        perm_elt(a, b, 9, 8, 1, 14, 10, 7, c[7], *data.get_unchecked(1));
-        perm_elt(a, b, 10, 9, 2, 15, 11, 8, c[6], *data.get_unchecked(2));
-        perm_elt(a, b, 11, 10, 3, 0, 12, 9, c[5], *data.get_unchecked(3));
-        perm_elt(a, b, 0, 11, 4, 1, 13, 10, c[4], *data.get_unchecked(4));
-        perm_elt(a, b, 1, 0, 5, 2, 14, 11, c[3], *data.get_unchecked(5));
-        perm_elt(a, b, 2, 1, 6, 3, 15, 12, c[2], *data.get_unchecked(6));
-        perm_elt(a, b, 3, 2, 7, 4, 0, 13, c[1], *data.get_unchecked(7));
-        perm_elt(a, b, 4, 3, 8, 5, 1, 14, c[0], *data.get_unchecked(8));
-        perm_elt(a, b, 5, 4, 9, 6, 2, 15, c[15], *data.get_unchecked(9));
-        perm_elt(a, b, 6, 5, 10, 7, 3, 0, c[14], *data.get_unchecked(10));
-        perm_elt(a, b, 7, 6, 11, 8, 4, 1, c[13], *data.get_unchecked(11));
-        perm_elt(a, b, 8, 7, 12, 9, 5, 2, c[12], *data.get_unchecked(12));
-        perm_elt(a, b, 9, 8, 13, 10, 6, 3, c[11], *data.get_unchecked(13));
-        perm_elt(a, b, 10, 9, 14, 11, 7, 4, c[10], *data.get_unchecked(14));
-        perm_elt(a, b, 11, 10, 15, 12, 8, 5, c[9], *data.get_unchecked(15));
-    }
-}
-
-#[inline(always)]
-fn swap_bc(b: &mut [u32; 16], c: &mut [u32; 16]) {
-    std::mem::swap(b, c);
-}
-
-#[inline(always)]
-fn incr_w(w_low: &mut u32, w_high: &mut u32) {
-    *w_low = w_low.wrapping_add(1);
-    if *w_low == 0 {
-        *w_high = w_high.wrapping_add(1);
-    }
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-    const TEST_A_RESULT: [u8; 32] = [
-        0xDA, 0x8F, 0x08, 0xC0, 0x2A, 0x67, 0xBA, 0x9A, 0x56, 0xBD, 0xD0, 0x79, 0x8E, 0x48, 0xAE,
-        0x07, 0x14, 0x21, 0x5E, 0x09, 0x3B, 0x5B, 0x85, 0x06, 0x49, 0xA3, 0x77, 0x18, 0x99, 0x3F,
-        0x54, 0xA2,
-    ];
-    const TEST_B_RESULT: [u8; 32] = [
-        0xB4, 0x9F, 0x34, 0xBF, 0x51, 0x86, 0x4C, 0x30, 0x53, 0x3C, 0xC4, 0x6C, 0xC2, 0x54, 0x2B,
-        0xDE, 0xC2, 0xF9, 0x6F, 0xD0, 0x6F, 0x5C, 0x53, 0x9A, 0xFF, 0x6E, 0xAD, 0x58, 0x83, 0xF7,
-        0x32, 0x7A,
-    ];
-    const TEST_B_M1: [u32; 16] = [
-        0x64636261, 0x68676665, 0x6C6B6A69, 0x706F6E6D, 0x74737271, 0x78777675, 0x302D7A79,
-        0x34333231, 0x38373635, 0x42412D39, 0x46454443, 0x4A494847, 0x4E4D4C4B, 0x5251504F,
-        0x56555453, 0x5A595857,
-    ];
-    const TEST_B_M2: [u32; 16] = [
-        0x3231302D, 0x36353433, 0x2D393837, 0x64636261, 0x68676665, 0x6C6B6A69, 0x706F6E6D,
-        0x74737271, 0x78777675, 0x00807A79, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
-        0x00000000, 0x00000000,
-    ];
-
-    #[test]
-    fn shabal256() {
-        // test message A
-        let test_data = [0u8; 64];
-        let mut test_term = [0u32; 16];
-        test_term[0] = 0x80;
-        let hash_a = shabal256_fast(&test_data, &test_term);
-        assert_eq!(hash_a, TEST_A_RESULT);
-        // test message B
-        let hash_b = unsafe {
-            shabal256_fast(
-                &std::mem::transmute::<[u32; 16], [u8; 64]>(TEST_B_M1),
-                &TEST_B_M2,
-            )
-        };
-        assert_eq!(hash_b, TEST_B_RESULT);
-    }
-}
+use crate::lanes::Lanes;
+
+const A_INIT: [u32; 12] = [
+    0x52F84552, 0xE54B7999, 0x2D8EE3EC, 0xB9645191, 0xE0078B86, 0xBB7C44C9, 0xD2B5C1CA, 0xB0D2EB8C,
+    0x14CE5A45, 0x22AF50DC, 0xEFFDBC6B, 0xEB21B74A,
+];
+
+const B_INIT: [u32; 16] = [
+    0xB555C6EE, 0x3E710596, 0xA72A652F, 0x9301515F, 0xDA28C1FA, 0x696FD868, 0x9CB6BF72, 0x0AFE4002,
+    0xA6E03615, 0x5138C1D4, 0xBE216306, 0xB38B8890, 0x3EA8B96B, 0x3299ACE4, 0x30924DD4, 0x55CB34A5,
+];
+
+const C_INIT: [u32; 16] = [
+    0xB405F031, 0xC4233EBA, 0xB3733979, 0xC0DD9D55, 0xC51C28AE, 0xA327B8E1, 0x56C56167, 0xED614433,
+    0x88B59D60, 0x60E2CEBA, 0x758B4B8B, 0x83E82A7F, 0xBC968828, 0xE6E00BF7, 0xBA839E55, 0x9B491C60,
+];
+
+pub fn shabal256_fast(data: &[u8], term: &[u32; 16]) -> [u8; 32] {
+    let mut a = A_INIT;
+    let mut b = B_INIT;
+    let mut c = C_INIT;
+    let mut w_high = 0u32;
+    let mut w_low = 1u32;
+
+    // message words are little endian, blocks needn't be aligned
+    for block in data.chunks_exact(64) {
+        let mut m = [0u32; 16];
+        for (word, bytes) in m.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        input_block_add(&mut b, &m);
+        xor_w(&mut a, w_low, w_high);
+        apply_p(&mut a, &mut b, &c, &m);
+        input_block_sub(&mut c, &m);
+        swap_bc(&mut b, &mut c);
+        incr_w(&mut w_low, &mut w_high);
+    }
+    input_block_add(&mut b, term);
+    xor_w(&mut a, w_low, w_high);
+    apply_p(&mut a, &mut b, &c, term);
+    for _ in 0..3 {
+        swap_bc(&mut b, &mut c);
+        xor_w(&mut a, w_low, w_high);
+        apply_p(&mut a, &mut b, &c, term);
+    }
+    let mut hash = [0u8; 32];
+    for (bytes, word) in hash.chunks_exact_mut(4).zip(b[8..16].iter()) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    hash
+}
+
+// Shabal-256 of N messages at once, interleaved word by word: word i of lane l is
+// data[i * N + l], the layout the mshabal C implementations hash from. Only whole
+// 64 byte blocks are hashed, then each lane's termination, and the hash comes
+// back interleaved the same way. Loops only, no closures, so everything inlines
+// into the target_feature function the lanes are used from.
+#[inline(always)]
+pub fn shabal256_lanes<V: Lanes<N>, const N: usize>(data: &[u32], term: &[V; 16]) -> [V; 8] {
+    let mut a = [V::splat(0); 12];
+    let mut b = [V::splat(0); 16];
+    let mut c = [V::splat(0); 16];
+    for (x, init) in a.iter_mut().zip(A_INIT.iter()) {
+        *x = V::splat(*init);
+    }
+    for (x, init) in b.iter_mut().zip(B_INIT.iter()) {
+        *x = V::splat(*init);
+    }
+    for (x, init) in c.iter_mut().zip(C_INIT.iter()) {
+        *x = V::splat(*init);
+    }
+    let mut w_low = 1u32;
+    let mut w_high = 0u32;
+
+    let mut m = [V::splat(0); 16];
+    for block in data.chunks_exact(16 * N) {
+        for (i, word) in m.iter_mut().enumerate() {
+            *word = V::load(&block[i * N..]);
+        }
+        lanes_add(&mut b, &m);
+        lanes_xor_w(&mut a, w_low, w_high);
+        lanes_apply_p(&mut a, &mut b, &c, &m);
+        lanes_sub(&mut c, &m);
+        std::mem::swap(&mut b, &mut c);
+        incr_w(&mut w_low, &mut w_high);
+    }
+
+    lanes_add(&mut b, term);
+    lanes_xor_w(&mut a, w_low, w_high);
+    lanes_apply_p(&mut a, &mut b, &c, term);
+    for _ in 0..3 {
+        std::mem::swap(&mut b, &mut c);
+        lanes_xor_w(&mut a, w_low, w_high);
+        lanes_apply_p(&mut a, &mut b, &c, term);
+    }
+
+    let mut hash = [V::splat(0); 8];
+    hash.copy_from_slice(&b[8..16]);
+    hash
+}
+
+#[inline(always)]
+fn lanes_add<V: Lanes<N>, const N: usize>(b: &mut [V; 16], m: &[V; 16]) {
+    for (b, m) in b.iter_mut().zip(m.iter()) {
+        *b = b.add(*m);
+    }
+}
+
+#[inline(always)]
+fn lanes_sub<V: Lanes<N>, const N: usize>(c: &mut [V; 16], m: &[V; 16]) {
+    for (c, m) in c.iter_mut().zip(m.iter()) {
+        *c = c.sub(*m);
+    }
+}
+
+#[inline(always)]
+fn lanes_xor_w<V: Lanes<N>, const N: usize>(a: &mut [V; 12], w_low: u32, w_high: u32) {
+    a[0] = a[0].xor(V::splat(w_low));
+    a[1] = a[1].xor(V::splat(w_high));
+}
+
+// same schedule as apply_p and perm: step i updates a[i % 12] and b[i % 16] with
+// c[(8 - i) mod 16] and message word i % 16
+#[inline(always)]
+fn lanes_apply_p<V: Lanes<N>, const N: usize>(
+    a: &mut [V; 12],
+    b: &mut [V; 16],
+    c: &[V; 16],
+    m: &[V; 16],
+) {
+    for b in b.iter_mut() {
+        *b = b.rotl::<17, 15>();
+    }
+    for i in 0..48 {
+        let (xa0, xa1) = (i % 12, (i + 11) % 12);
+        let (xb0, xb1, xb2, xb3) = (i % 16, (i + 13) % 16, (i + 9) % 16, (i + 6) % 16);
+        let xc = (24 - i % 16) % 16;
+        let a0 = a[xa0]
+            .xor(a[xa1].rotl::<15, 17>().mul5())
+            .xor(c[xc])
+            .mul3()
+            .xor(b[xb1])
+            .xor(b[xb3].andnot(b[xb2]))
+            .xor(m[i % 16]);
+        a[xa0] = a0;
+        b[xb0] = b[xb0].rotl::<1, 31>().xor(a0).not();
+    }
+    for (i, a) in a.iter_mut().enumerate() {
+        *a = a
+            .add(c[(i + 11) % 16])
+            .add(c[(i + 15) % 16])
+            .add(c[(i + 3) % 16]);
+    }
+}
+
+#[inline(always)]
+fn input_block_add(b: &mut [u32; 16], data: &[u32]) {
+    for (element, data) in b.iter_mut().zip(data.iter()) {
+        *element = element.wrapping_add(*data);
+    }
+}
+
+#[inline(always)]
+fn input_block_sub(c: &mut [u32; 16], data: &[u32]) {
+    for (element, data) in c.iter_mut().zip(data.iter()) {
+        *element = element.wrapping_sub(*data);
+    }
+}
+
+#[inline(always)]
+fn xor_w(a: &mut [u32; 12], w_low: u32, w_high: u32) {
+    a[0] ^= w_low;
+    a[1] ^= w_high;
+}
+
+#[inline(always)]
+fn apply_p(a: &mut [u32; 12], b: &mut [u32; 16], c: &[u32; 16], data: &[u32]) {
+    for element in b.iter_mut() {
+        *element = element.wrapping_shl(17) | element.wrapping_shr(15);
+    }
+    perm(a, b, c, data);
+    a[0] = a[0]
+        .wrapping_add(c[11])
+        .wrapping_add(c[15])
+        .wrapping_add(c[3]);
+    a[1] = a[1]
+        .wrapping_add(c[12])
+        .wrapping_add(c[0])
+        .wrapping_add(c[4]);
+    a[2] = a[2]
+        .wrapping_add(c[13])
+        .wrapping_add(c[1])
+        .wrapping_add(c[5]);
+    a[3] = a[3]
+        .wrapping_add(c[14])
+        .wrapping_add(c[2])
+        .wrapping_add(c[6]);
+    a[4] = a[4]
+        .wrapping_add(c[15])
+        .wrapping_add(c[3])
+        .wrapping_add(c[7]);
+    a[5] = a[5]
+        .wrapping_add(c[0])
+        .wrapping_add(c[4])
+        .wrapping_add(c[8]);
+    a[6] = a[6]
+        .wrapping_add(c[1])
+        .wrapping_add(c[5])
+        .wrapping_add(c[9]);
+    a[7] = a[7]
+        .wrapping_add(c[2])
+        .wrapping_add(c[6])
+        .wrapping_add(c[10]);
+    a[8] = a[8]
+        .wrapping_add(c[3])
+        .wrapping_add(c[7])
+        .wrapping_add(c[11]);
+    a[9] = a[9]
+        .wrapping_add(c[4])
+        .wrapping_add(c[8])
+        .wrapping_add(c[12]);
+    a[10] = a[10]
+        .wrapping_add(c[5])
+        .wrapping_add(c[9])
+        .wrapping_add(c[13]);
+    a[11] = a[11]
+        .wrapping_add(c[6])
+        .wrapping_add(c[10])
+        .wrapping_add(c[14]);
+}
+
+#[inline(always)]
+fn perm_elt(
+    a: &mut [u32; 12],
+    b: &mut [u32; 16],
+    xa0: usize,
+    xa1: usize,
+    xb0: usize,
+    xb1: usize,
+    xb2: usize,
+    xb3: usize,
+    xc: u32,
+    xm: u32,
+) {
+    unsafe {
+        *a.get_unchecked_mut(xa0) = (a.get_unchecked(xa0)
+            ^ ((a.get_unchecked(xa1).wrapping_shl(15u32)
+                | a.get_unchecked(xa1).wrapping_shr(17u32))
+            .wrapping_mul(5u32))
+            ^ xc)
+            .wrapping_mul(3u32)
+            ^ b.get_unchecked(xb1)
+            ^ (b.get_unchecked(xb2) & !b.get_unchecked(xb3))
+            ^ xm;
+        *b.get_unchecked_mut(xb0) = !((b.get_unchecked(xb0).wrapping_shl(1)
+            | b.get_unchecked(xb0).wrapping_shr(31))
+            ^ a.get_unchecked(xa0));
+    }
+}
+
+#[inline(always)]
+fn perm(a: &mut [u32; 12], b: &mut [u32; 16], c: &[u32; 16], data: &[u32]) {
+    unsafe {
+        perm_elt(a, b, 0, 11, 0, 13, 9, 6, c[8], *data.get_unchecked(0));
+        perm_elt(a, b, 1, 0, 1, 14, 10, 7, c[7], *data.get_unchecked(1));
+        perm_elt(a, b, 2, 1, 2, 15, 11, 8, c[6], *data.get_unchecked(2));
+        perm_elt(a, b, 3, 2, 3, 0, 12, 9, c[5], *data.get_unchecked(3));
+        perm_elt(a, b, 4, 3, 4, 1, 13, 10, c[4], *data.get_unchecked(4));
+        perm_elt(a, b, 5, 4, 5, 2, 14, 11, c[3], *data.get_unchecked(5));
+        perm_elt(a, b, 6, 5, 6, 3, 15, 12, c[2], *data.get_unchecked(6));
+        perm_elt(a, b, 7, 6, 7, 4, 0, 13, c[1], *data.get_unchecked(7));
+        perm_elt(a, b, 8, 7, 8, 5, 1, 14, c[0], *data.get_unchecked(8));
+        perm_elt(a, b, 9, 8, 9, 6, 2, 15, c[15], *data.get_unchecked(9));
+        perm_elt(a, b, 10, 9, 10, 7, 3, 0, c[14], *data.get_unchecked(10));
+        perm_elt(a, b, 11, 10, 11, 8, 4, 1, c[13], *data.get_unchecked(11));
+        perm_elt(a, b, 0, 11, 12, 9, 5, 2, c[12], *data.get_unchecked(12));
+        perm_elt(a, b, 1, 0, 13, 10, 6, 3, c[11], *data.get_unchecked(13));
+        perm_elt(a, b, 2, 1, 14, 11, 7, 4, c[10], *data.get_unchecked(14));
+        perm_elt(a, b, 3, 2, 15, 12, 8, 5, c[9], *data.get_unchecked(15));
+        perm_elt(a, b, 4, 3, 0, 13, 9, 6, c[8], *data.get_unchecked(0));
+        perm_elt(a, b, 5, 4, 1, 14, 10, 7, c[7], *data.get_unchecked(1));
+        perm_elt(a, b, 6, 5, 2, 15, 11, 8, c[6], *data.get_unchecked(2));
+        perm_elt(a, b, 7, 6, 3, 0, 12, 9, c[5], *data.get_unchecked(3));
+        perm_elt(a, b, 8, 7, 4, 1, 13, 10, c[4], *data.get_unchecked(4));
+        perm_elt(a, b, 9, 8, 5, 2, 14, 11, c[3], *data.get_unchecked(5));
+        perm_elt(a, b, 10, 9, 6, 3, 15, 12, c[2], *data.get_unchecked(6));
+        perm_elt(a, b, 11, 10, 7, 4, 0, 13, c[1], *data.get_unchecked(7));
+        perm_elt(a, b, 0, 11, 8, 5, 1, 14, c[0], *data.get_unchecked(8));
+        perm_elt(a, b, 1, 0, 9, 6, 2, 15, c[15], *data.get_unchecked(9));
+        perm_elt(a, b, 2, 1, 10, 7, 3, 0, c[14], *data.get_unchecked(10));
+        perm_elt(a, b, 3, 2, 11, 8, 4, 1, c[13], *data.get_unchecked(11));
+        perm_elt(a, b, 4, 3, 12, 9, 5, 2, c[12], *data.get_unchecked(12));
+        perm_elt(a, b, 5, 4, 13, 10, 6, 3, c[11], *data.get_unchecked(13));
+        perm_elt(a, b, 6, 5, 14, 11, 7, 4, c[10], *data.get_unchecked(14));
+        perm_elt(a, b, 7, 6, 15, 12, 8, 5, c[9], *data.get_unchecked(15));
+        perm_elt(a, b, 8, 7, 0, 13, 9, 6, c[8], *data.get_unchecked(0));
+        perm_elt(a, b, 9, 8, 1, 14, 10, 7, c[7], *data.get_unchecked(1));
+        perm_elt(a, b, 10, 9, 2, 15, 11, 8, c[6], *data.get_unchecked(2));
+        perm_elt(a, b, 11, 10, 3, 0, 12, 9, c[5], *data.get_unchecked(3));
+        perm_elt(a, b, 0, 11, 4, 1, 13, 10, c[4], *data.get_unchecked(4));
+        perm_elt(a, b, 1, 0, 5, 2, 14, 11, c[3], *data.get_unchecked(5));
+        perm_elt(a, b, 2, 1, 6, 3, 15, 12, c[2], *data.get_unchecked(6));
+        perm_elt(a, b, 3, 2, 7, 4, 0, 13, c[1], *data.get_unchecked(7));
+        perm_elt(a, b, 4, 3, 8, 5, 1, 14, c[0], *data.get_unchecked(8));
+        perm_elt(a, b, 5, 4, 9, 6, 2, 15, c[15], *data.get_unchecked(9));
+        perm_elt(a, b, 6, 5, 10, 7, 3, 0, c[14], *data.get_unchecked(10));
+        perm_elt(a, b, 7, 6, 11, 8, 4, 1, c[13], *data.get_unchecked(11));
+        perm_elt(a, b, 8, 7, 12, 9, 5, 2, c[12], *data.get_unchecked(12));
+        perm_elt(a, b, 9, 8, 13, 10, 6, 3, c[11], *data.get_unchecked(13));
+        perm_elt(a, b, 10, 9, 14, 11, 7, 4, c[10], *data.get_unchecked(14));
+        perm_elt(a, b, 11, 10, 15, 12, 8, 5, c[9], *data.get_unchecked(15));
+    }
+}
+
+#[inline(always)]
+fn swap_bc(b: &mut [u32; 16], c: &mut [u32; 16]) {
+    std::mem::swap(b, c);
+}
+
+#[inline(always)]
+fn incr_w(w_low: &mut u32, w_high: &mut u32) {
+    *w_low = w_low.wrapping_add(1);
+    if *w_low == 0 {
+        *w_high = w_high.wrapping_add(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    const TEST_A_RESULT: [u8; 32] = [
+        0xDA, 0x8F, 0x08, 0xC0, 0x2A, 0x67, 0xBA, 0x9A, 0x56, 0xBD, 0xD0, 0x79, 0x8E, 0x48, 0xAE,
+        0x07, 0x14, 0x21, 0x5E, 0x09, 0x3B, 0x5B, 0x85, 0x06, 0x49, 0xA3, 0x77, 0x18, 0x99, 0x3F,
+        0x54, 0xA2,
+    ];
+    const TEST_B_RESULT: [u8; 32] = [
+        0xB4, 0x9F, 0x34, 0xBF, 0x51, 0x86, 0x4C, 0x30, 0x53, 0x3C, 0xC4, 0x6C, 0xC2, 0x54, 0x2B,
+        0xDE, 0xC2, 0xF9, 0x6F, 0xD0, 0x6F, 0x5C, 0x53, 0x9A, 0xFF, 0x6E, 0xAD, 0x58, 0x83, 0xF7,
+        0x32, 0x7A,
+    ];
+    const TEST_B_M1: [u32; 16] = [
+        0x64636261, 0x68676665, 0x6C6B6A69, 0x706F6E6D, 0x74737271, 0x78777675, 0x302D7A79,
+        0x34333231, 0x38373635, 0x42412D39, 0x46454443, 0x4A494847, 0x4E4D4C4B, 0x5251504F,
+        0x56555453, 0x5A595857,
+    ];
+    const TEST_B_M2: [u32; 16] = [
+        0x3231302D, 0x36353433, 0x2D393837, 0x64636261, 0x68676665, 0x6C6B6A69, 0x706F6E6D,
+        0x74737271, 0x78777675, 0x00807A79, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000,
+    ];
+
+    #[test]
+    fn shabal256() {
+        // test message A
+        let test_data = [0u8; 64];
+        let mut test_term = [0u32; 16];
+        test_term[0] = 0x80;
+        let hash_a = shabal256_fast(&test_data, &test_term);
+        assert_eq!(hash_a, TEST_A_RESULT);
+        // test message B
+        let hash_b = unsafe {
+            shabal256_fast(
+                &std::mem::transmute::<[u32; 16], [u8; 64]>(TEST_B_M1),
+                &TEST_B_M2,
+            )
+        };
+        assert_eq!(hash_b, TEST_B_RESULT);
+    }
+
+    #[test]
+    fn lanes_match_scalar() {
+        let message: Vec<u8> = (0..4096u32).map(|x| (x * 7 + x / 256) as u8).collect();
+        // different content and termination per lane
+        let data: Vec<&[u8]> = (0..4).map(|lane| &message[lane * 4..lane * 4 + 128]).collect();
+        let mut terms = [[0u32; 16]; 4];
+        for (lane, term) in terms.iter_mut().enumerate() {
+            term[0] = 0x80;
+            term[3] = lane as u32;
+        }
+
+        let mut interleaved = vec![0u32; 32 * 4];
+        let mut term = [[0u32; 4]; 16];
+        for lane in 0..4 {
+            for (i, bytes) in data[lane].chunks_exact(4).enumerate() {
+                interleaved[i * 4 + lane] = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+            for i in 0..16 {
+                term[i][lane] = terms[lane][i];
+            }
+        }
+        let hash = shabal256_lanes::<[u32; 4], 4>(&interleaved, &term);
+        for lane in 0..4 {
+            let mut bytes = [0u8; 32];
+            for (i, word) in hash.iter().enumerate() {
+                bytes[i * 4..i * 4 + 4].copy_from_slice(&word[lane].to_le_bytes());
+            }
+            assert_eq!(bytes, shabal256_fast(data[lane], &terms[lane]));
+        }
+    }
+}
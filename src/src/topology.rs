@@ -0,0 +1,185 @@
+use crate::numa::parse_cpu_list;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+// where hashing threads go
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum PinningPolicy {
+    // leave scheduling to the os
+    None,
+    // one thread per physical core before any core gets a second one
+    #[default]
+    PhysicalFirst,
+    // fill all smt siblings of a core before moving to the next core
+    SmtSiblings,
+    // exactly these cpus, in this order, e.g. to keep cores free for a miner
+    Explicit(Vec<usize>),
+}
+
+// "none", "physical", "smt" or a cpu list like "2-7,10"
+impl FromStr for PinningPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PinningPolicy, String> {
+        match s.trim().to_lowercase().as_str() {
+            "none" => Ok(PinningPolicy::None),
+            "physical" | "" => Ok(PinningPolicy::PhysicalFirst),
+            "smt" => Ok(PinningPolicy::SmtSiblings),
+            list => {
+                let cpus = parse_cpu_list(list)?;
+                if cpus.is_empty() {
+                    return Err(format!("unknown pinning policy: {}", s));
+                }
+                Ok(PinningPolicy::Explicit(cpus))
+            }
+        }
+    }
+}
+
+impl fmt::Display for PinningPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PinningPolicy::None => write!(f, "none"),
+            PinningPolicy::PhysicalFirst => write!(f, "physical cores first"),
+            PinningPolicy::SmtSiblings => write!(f, "smt siblings"),
+            PinningPolicy::Explicit(cpus) => write!(
+                f,
+                "cpus {}",
+                cpus.iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+    }
+}
+
+// position of a logical cpu in the package / shared cache / core hierarchy
+#[derive(Clone, Debug, PartialEq)]
+pub struct CpuTopology {
+    pub cpu: usize,
+    pub package: usize,
+    // lowest cpu sharing the last level cache, cpus with equal values share it
+    pub cache_group: usize,
+    pub core: usize,
+}
+
+// Reads /sys/devices/system/cpu. Cpus without topology information are treated
+// as cores of their own.
+pub fn cpu_topology() -> Vec<CpuTopology> {
+    let mut topology = Vec::new();
+    let entries = match fs::read_dir("/sys/devices/system/cpu") {
+        Ok(x) => x,
+        Err(_) => return topology,
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let cpu: usize = match name.strip_prefix("cpu").and_then(|x| x.parse().ok()) {
+            Some(x) => x,
+            None => continue,
+        };
+        let read = |file: &str| fs::read_to_string(entry.path().join(file)).ok();
+        let read_number = |file: &str| read(file).and_then(|x| x.trim().parse().ok());
+
+        // the cache with the highest index is the last level one
+        let mut cache_group = cpu;
+        for index in (0..8).rev() {
+            if let Some(list) = read(&format!("cache/index{}/shared_cpu_list", index)) {
+                cache_group = parse_cpu_list(&list)
+                    .ok()
+                    .and_then(|x| x.into_iter().min())
+                    .unwrap_or(cpu);
+                break;
+            }
+        }
+
+        topology.push(CpuTopology {
+            cpu,
+            package: read_number("topology/physical_package_id").unwrap_or(0),
+            cache_group,
+            core: read_number("topology/core_id").unwrap_or(cpu),
+        });
+    }
+    topology.sort_by_key(|x| x.cpu);
+    topology
+}
+
+// Orders the given cpus the way threads should be pinned to them. Cores are
+// visited package by package and cache group by cache group, so consecutive
+// threads share caches. Explicit lists keep their order and drop cpus not given.
+pub fn order_cpus(cpus: &[usize], policy: &PinningPolicy, topology: &[CpuTopology]) -> Vec<usize> {
+    let mut cores: BTreeMap<(usize, usize, usize), Vec<usize>> = BTreeMap::new();
+    for &cpu in cpus {
+        let key = match topology.iter().find(|x| x.cpu == cpu) {
+            Some(x) => (x.package, x.cache_group, x.core),
+            None => (0, cpu, cpu),
+        };
+        cores.entry(key).or_default().push(cpu);
+    }
+    for siblings in cores.values_mut() {
+        siblings.sort_unstable();
+    }
+
+    match policy {
+        PinningPolicy::None => cpus.to_vec(),
+        PinningPolicy::PhysicalFirst => {
+            let smt = cores.values().map(|x| x.len()).max().unwrap_or(0);
+            (0..smt)
+                .flat_map(|sibling| cores.values().filter_map(move |x| x.get(sibling).copied()))
+                .collect()
+        }
+        PinningPolicy::SmtSiblings => cores.values().flatten().copied().collect(),
+        PinningPolicy::Explicit(list) => list.iter().copied().filter(|x| cpus.contains(x)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod topology_tests {
+    use super::*;
+
+    // 2 packages, 2 cores each, 2 smt siblings per core, linux style numbering
+    fn dual_socket() -> Vec<CpuTopology> {
+        (0..8)
+            .map(|cpu| CpuTopology {
+                cpu,
+                package: (cpu % 4) / 2,
+                cache_group: (cpu % 4) / 2 * 2,
+                core: cpu % 2,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn policies_order_cpus() {
+        let topology = dual_socket();
+        let cpus: Vec<usize> = (0..8).collect();
+        assert_eq!(
+            order_cpus(&cpus, &PinningPolicy::PhysicalFirst, &topology),
+            vec![0, 1, 2, 3, 4, 5, 6, 7]
+        );
+        assert_eq!(
+            order_cpus(&cpus, &PinningPolicy::SmtSiblings, &topology),
+            vec![0, 4, 1, 5, 2, 6, 3, 7]
+        );
+        assert_eq!(
+            order_cpus(&cpus, &"7,2-3,9".parse().unwrap(), &topology),
+            vec![7, 2, 3]
+        );
+        // restricted to a numa node
+        assert_eq!(
+            order_cpus(&[0, 1, 4, 5], &PinningPolicy::SmtSiblings, &topology),
+            vec![0, 4, 1, 5]
+        );
+    }
+
+    #[test]
+    fn parse_policies() {
+        assert_eq!("none".parse(), Ok(PinningPolicy::None));
+        assert_eq!("".parse(), Ok(PinningPolicy::PhysicalFirst));
+        assert_eq!("smt".parse(), Ok(PinningPolicy::SmtSiblings));
+        assert_eq!("0-2".parse(), Ok(PinningPolicy::Explicit(vec![0, 1, 2])));
+        assert!("fastest".parse::<PinningPolicy>().is_err());
+    }
+}
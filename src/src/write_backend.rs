@@ -0,0 +1,400 @@
+use crate::plotter::{NONCE_SIZE, SCOOP_SIZE};
+use crate::utils::write_all_at;
+use cfg_if::cfg_if;
+use std::cmp::{max, min};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread;
+
+const TASK_SIZE: u64 = 16384;
+const DEFAULT_PWRITE_THREADS: usize = 4;
+const DEFAULT_QUEUE_DEPTH: u32 = 64;
+
+// how a writer thread gets the scoops of a buffer onto disk
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WriteBackendKind {
+    // one seek + write_all per scoop, the classic path
+    #[default]
+    Sync,
+    // positioned writes, scoop ranges split across n threads
+    Pwrite(usize),
+    // io_uring with up to n writes in flight, linux only
+    IoUring(u32),
+}
+
+// "sync", "pwrite[:threads]" or "io_uring[:queue depth]"
+impl FromStr for WriteBackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<WriteBackendKind, String> {
+        let mut parts = s.trim().splitn(2, ':');
+        let name = parts.next().unwrap_or("").to_lowercase();
+        let arg = parts.next();
+        let parse_arg = |default: u64| match arg {
+            Some(x) => x
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .filter(|&x| x > 0)
+                .ok_or_else(|| format!("invalid write backend parameter: {}", x)),
+            None => Ok(default),
+        };
+        match name.as_str() {
+            "sync" if arg.is_none() => Ok(WriteBackendKind::Sync),
+            "pwrite" => Ok(WriteBackendKind::Pwrite(
+                parse_arg(DEFAULT_PWRITE_THREADS as u64)? as usize,
+            )),
+            "io_uring" | "iouring" => Ok(WriteBackendKind::IoUring(
+                parse_arg(u64::from(DEFAULT_QUEUE_DEPTH))? as u32,
+            )),
+            _ => Err(format!("unknown write backend: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for WriteBackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WriteBackendKind::Sync => write!(f, "sync"),
+            WriteBackendKind::Pwrite(threads) => write!(f, "pwrite:{}", threads),
+            WriteBackendKind::IoUring(depth) => write!(f, "io_uring:{}", depth),
+        }
+    }
+}
+
+impl WriteBackendKind {
+    // backends that can't be set up on this system fall back to sync writes
+    pub fn create(self) -> Box<dyn WriteBackend> {
+        match self {
+            WriteBackendKind::Sync => Box::new(SyncBackend),
+            WriteBackendKind::Pwrite(threads) => Box::new(PwriteBackend {
+                threads: max(threads, 1),
+            }),
+            WriteBackendKind::IoUring(depth) => match create_io_uring(max(depth, 1)) {
+                Ok(x) => x,
+                Err(e) => {
+                    println!("Warning: io_uring unavailable ({}), falling back to sync writes", e);
+                    Box::new(SyncBackend)
+                }
+            },
+        }
+    }
+}
+
+// Writes the first `nonces` nonces of every scoop of a scoop-major buffer to
+// the plot file, starting at nonce offset `nonces_written` of a plot with
+// `plot_nonces` nonces. `progress` receives the number of bytes written.
+pub trait WriteBackend {
+    fn write_scoops(
+        &mut self,
+        file: &File,
+        bs: &[u8],
+        plot_nonces: u64,
+        nonces_written: u64,
+        nonces: u64,
+        progress: &mut dyn FnMut(u64),
+    ) -> io::Result<()>;
+}
+
+// buffer and file offset of a scoop
+fn scoop_addr(scoop: u64, buffer_size: u64, plot_nonces: u64, nonces_written: u64) -> (usize, u64) {
+    let local_addr = scoop * buffer_size / NONCE_SIZE * SCOOP_SIZE;
+    let seek_addr = (scoop * plot_nonces + nonces_written) * SCOOP_SIZE;
+    (local_addr as usize, seek_addr)
+}
+
+struct SyncBackend;
+
+impl WriteBackend for SyncBackend {
+    fn write_scoops(
+        &mut self,
+        mut file: &File,
+        bs: &[u8],
+        plot_nonces: u64,
+        nonces_written: u64,
+        nonces: u64,
+        progress: &mut dyn FnMut(u64),
+    ) -> io::Result<()> {
+        for scoop in 0..4096 {
+            let (mut local_addr, seek_addr) =
+                scoop_addr(scoop, bs.len() as u64, plot_nonces, nonces_written);
+
+            file.seek(SeekFrom::Start(seek_addr))?;
+
+            for _ in 0..nonces / TASK_SIZE {
+                file.write_all(&bs[local_addr..local_addr + (TASK_SIZE * SCOOP_SIZE) as usize])?;
+                local_addr += (TASK_SIZE * SCOOP_SIZE) as usize;
+            }
+
+            // write remainder
+            if !nonces.is_multiple_of(TASK_SIZE) {
+                file.write_all(
+                    &bs[local_addr..local_addr + (nonces % TASK_SIZE * SCOOP_SIZE) as usize],
+                )?;
+            }
+
+            if (scoop + 1) % 128 == 0 {
+                progress(nonces * SCOOP_SIZE * 128);
+            }
+        }
+        Ok(())
+    }
+}
+
+// every thread writes a contiguous range of scoops with positioned writes, so
+// no thread shares a file cursor and the device sees several requests at once
+struct PwriteBackend {
+    threads: usize,
+}
+
+impl WriteBackend for PwriteBackend {
+    fn write_scoops(
+        &mut self,
+        file: &File,
+        bs: &[u8],
+        plot_nonces: u64,
+        nonces_written: u64,
+        nonces: u64,
+        progress: &mut dyn FnMut(u64),
+    ) -> io::Result<()> {
+        let scoops_per_thread = 4096u64.div_ceil(self.threads as u64);
+        let len = (nonces * SCOOP_SIZE) as usize;
+        thread::scope(|s| {
+            let (tx_progress, rx_progress) = mpsc::channel();
+            let workers: Vec<_> = (0..4096)
+                .step_by(scoops_per_thread as usize)
+                .map(|first| {
+                    let tx_progress = tx_progress.clone();
+                    s.spawn(move || -> io::Result<()> {
+                        for scoop in first..min(first + scoops_per_thread, 4096) {
+                            let (local_addr, seek_addr) =
+                                scoop_addr(scoop, bs.len() as u64, plot_nonces, nonces_written);
+                            write_all_at(file, &bs[local_addr..local_addr + len], seek_addr)?;
+                            if (scoop + 1) % 128 == 0 {
+                                let _ = tx_progress.send(len as u64 * 128);
+                            }
+                        }
+                        Ok(())
+                    })
+                })
+                .collect();
+            drop(tx_progress);
+
+            for bytes in rx_progress {
+                progress(bytes);
+            }
+            workers.into_iter().try_for_each(|x| x.join().unwrap())
+        })
+    }
+}
+
+cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use io_uring::{opcode, types, IoUring};
+        use std::os::unix::io::AsRawFd;
+
+        fn create_io_uring(depth: u32) -> io::Result<Box<dyn WriteBackend>> {
+            Ok(Box::new(IoUringBackend {
+                ring: IoUring::new(depth)?,
+                depth,
+            }))
+        }
+
+        // one write per scoop, kept `depth` deep in the submission queue
+        struct IoUringBackend {
+            ring: IoUring,
+            depth: u32,
+        }
+
+        impl WriteBackend for IoUringBackend {
+            fn write_scoops(
+                &mut self,
+                file: &File,
+                bs: &[u8],
+                plot_nonces: u64,
+                nonces_written: u64,
+                nonces: u64,
+                progress: &mut dyn FnMut(u64),
+            ) -> io::Result<()> {
+                let fd = types::Fd(file.as_raw_fd());
+                let len = nonces * SCOOP_SIZE;
+                let mut next = 0u64;
+                let mut in_flight = 0u32;
+                let mut done = 0u64;
+                let mut error = None;
+
+                // the kernel reads straight from `bs`, so even after an error all
+                // submitted writes have to complete before we return
+                while done < next || (next < 4096 && error.is_none()) {
+                    while next < 4096 && in_flight < self.depth && error.is_none() {
+                        let (local_addr, seek_addr) =
+                            scoop_addr(next, bs.len() as u64, plot_nonces, nonces_written);
+                        let entry = opcode::Write::new(fd, bs[local_addr..].as_ptr(), len as u32)
+                            .offset(seek_addr)
+                            .build()
+                            .user_data(next);
+                        if unsafe { self.ring.submission().push(&entry) }.is_err() {
+                            break;
+                        }
+                        next += 1;
+                        in_flight += 1;
+                    }
+
+                    // after an error nothing new is queued, the writes already in the
+                    // queue are still submitted and waited for
+                    match self.ring.submit_and_wait(1) {
+                        Ok(_) => (),
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        // the completion queue is full, reap it below
+                        Err(e) if e.raw_os_error() == Some(libc::EBUSY) => (),
+                        Err(e) if error.is_none() => {
+                            error = Some(e);
+                        }
+                        // the ring can't be waited on anymore
+                        Err(e) => return Err(e),
+                    }
+                    let completed: Vec<_> = self
+                        .ring
+                        .completion()
+                        .map(|x| (x.user_data(), x.result()))
+                        .collect();
+                    for (scoop, result) in completed {
+                        in_flight -= 1;
+                        done += 1;
+                        if result < 0 {
+                            error.get_or_insert(io::Error::from_raw_os_error(-result));
+                            continue;
+                        }
+                        // short writes are rare, finish them synchronously
+                        if (result as u64) < len && error.is_none() {
+                            let (local_addr, seek_addr) =
+                                scoop_addr(scoop, bs.len() as u64, plot_nonces, nonces_written);
+                            let written = result as usize;
+                            if let Err(e) = write_all_at(
+                                file,
+                                &bs[local_addr + written..local_addr + len as usize],
+                                seek_addr + written as u64,
+                            ) {
+                                error.get_or_insert(e);
+                            }
+                        }
+                        if done.is_multiple_of(128) {
+                            progress(len * 128);
+                        }
+                    }
+                }
+
+                match error {
+                    Some(e) => Err(e),
+                    None => Ok(()),
+                }
+            }
+        }
+    } else {
+        fn create_io_uring(_depth: u32) -> io::Result<Box<dyn WriteBackend>> {
+            Err(io::Error::new(io::ErrorKind::Other, "io_uring needs linux"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod write_backend_tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Instant;
+
+    fn test_buffer(nonces: u64) -> Vec<u8> {
+        (0..nonces * NONCE_SIZE).map(|i| (i * 7 + i / 4096) as u8).collect()
+    }
+
+    fn backends() -> Vec<WriteBackendKind> {
+        vec![
+            WriteBackendKind::Sync,
+            WriteBackendKind::Pwrite(3),
+            WriteBackendKind::IoUring(8),
+        ]
+    }
+
+    #[test]
+    fn parse_backends() {
+        assert_eq!("sync".parse(), Ok(WriteBackendKind::Sync));
+        assert_eq!("pwrite".parse(), Ok(WriteBackendKind::Pwrite(DEFAULT_PWRITE_THREADS)));
+        assert_eq!("pwrite:8".parse(), Ok(WriteBackendKind::Pwrite(8)));
+        assert_eq!("io_uring:128".parse(), Ok(WriteBackendKind::IoUring(128)));
+        assert!("io_uring:0".parse::<WriteBackendKind>().is_err());
+        assert!("aio".parse::<WriteBackendKind>().is_err());
+        for backend in backends() {
+            assert_eq!(backend.to_string().parse(), Ok(backend));
+        }
+    }
+
+    #[test]
+    fn backends_write_identical_plots() {
+        let dir = temp_dir("backend-identical");
+        // buffers of 40 nonces, the second one only partially filled
+        let bs = test_buffer(40);
+        let mut files = Vec::new();
+        for backend in backends() {
+            let path = dir.join(backend.to_string().replace(':', "_"));
+            let file = File::create(&path).unwrap();
+            let mut writer = backend.create();
+            let mut written = 0;
+            writer.write_scoops(&file, &bs, 64, 0, 40, &mut |x| written += x).unwrap();
+            writer.write_scoops(&file, &bs, 64, 40, 24, &mut |x| written += x).unwrap();
+            assert_eq!(written, 64 * NONCE_SIZE);
+            files.push(fs::read(&path).unwrap());
+        }
+        assert_eq!(files[0].len() as u64, 64 * NONCE_SIZE);
+        assert_eq!(&files[0][..40 * SCOOP_SIZE as usize], &bs[..40 * SCOOP_SIZE as usize]);
+        for file in &files[1..] {
+            assert!(file == &files[0]);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // cargo test --release bench_write_backends -- --ignored --nocapture
+    // WRITE_BENCH_DIR selects the target drive, the system temp dir otherwise
+    #[test]
+    #[ignore]
+    fn bench_write_backends() {
+        let dir = match std::env::var("WRITE_BENCH_DIR") {
+            Ok(x) => PathBuf::from(x),
+            Err(_) => temp_dir("backend-bench"),
+        };
+        let buffer_nonces = 2048;
+        let buffers = 4;
+        let bs = test_buffer(buffer_nonces);
+        let candidates = vec![
+            WriteBackendKind::Sync,
+            WriteBackendKind::Pwrite(2),
+            WriteBackendKind::Pwrite(8),
+            WriteBackendKind::IoUring(32),
+            WriteBackendKind::IoUring(128),
+        ];
+        for backend in candidates {
+            let path = dir.join("bench_write_backend");
+            let file = File::create(&path).unwrap();
+            let mut writer = backend.create();
+            let start = Instant::now();
+            for i in 0..buffers {
+                writer
+                    .write_scoops(&file, &bs, buffer_nonces * buffers, i * buffer_nonces, buffer_nonces, &mut |_| ())
+                    .unwrap();
+            }
+            file.sync_all().unwrap();
+            let secs = start.elapsed().as_secs_f64();
+            println!(
+                "{:>14}: {:.2} MiB/s",
+                backend.to_string(),
+                (buffer_nonces * buffers * NONCE_SIZE) as f64 / 1024.0 / 1024.0 / secs
+            );
+            drop(file);
+            fs::remove_file(&path).unwrap();
+        }
+    }
+}
@@ -1,143 +1,320 @@
-use crate::plotter::{PlotterTask, NONCE_SIZE, SCOOP_SIZE};
-use crate::buffer::PageAlignedByteBuffer;
-use crate::utils::{open, open_r, open_using_direct_io};
-use crossbeam_channel::{Receiver, Sender};
-use std::cmp::min;
-use std::io::{Read, Seek, SeekFrom, Write, Error, ErrorKind};
-use std::path::Path;
-use std::sync::Arc;
-
-const TASK_SIZE: u64 = 16384;
-
-pub fn create_writer_thread(
-    task: Arc<PlotterTask>,
-    mut nonces_written: u64,
-    mut pb: Option<pbr::ProgressBar<pbr::Pipe>>,
-    rx_buffers_to_writer: Receiver<PageAlignedByteBuffer>,
-    tx_empty_buffers: Sender<PageAlignedByteBuffer>,
-) -> impl FnOnce() {
-    move || {
-        for buffer in rx_buffers_to_writer {
-            let mut_bs = &buffer.get_buffer();
-            let bs = mut_bs.lock().unwrap();
-            let buffer_size = (*bs).len() as u64;
-            let nonces_to_write = min(buffer_size / NONCE_SIZE, task.nonces - nonces_written);
-
-            let filename = Path::new(&task.output_path).join(format!(
-                "{}_{}_{}",
-                task.numeric_id, task.start_nonce, task.nonces
-            ));
-            if !task.benchmark {
-                let file = if task.direct_io {
-                    open_using_direct_io(&filename)
-                } else {
-                    open(&filename)
-                };
-
-                let mut file = file.unwrap();
-
-                for scoop in 0..4096 {
-                    let mut seek_addr = scoop * task.nonces as u64 * SCOOP_SIZE;
-                    seek_addr += nonces_written as u64 * SCOOP_SIZE;
-
-                    file.seek(SeekFrom::Start(seek_addr)).unwrap();
-
-                    let mut local_addr = scoop * buffer_size / NONCE_SIZE * SCOOP_SIZE;
-                    for _ in 0..nonces_to_write / TASK_SIZE {
-                        file.write_all(
-                            &bs[local_addr as usize
-                                ..(local_addr + TASK_SIZE * SCOOP_SIZE) as usize],
-                        )
-                        .unwrap();
-
-                        local_addr += TASK_SIZE * SCOOP_SIZE;
-                    }
-
-                    // write remainder
-                    if nonces_to_write % TASK_SIZE > 0 {
-                        file.write_all(
-                            &bs[local_addr as usize
-                                ..(local_addr + (nonces_to_write % TASK_SIZE) * SCOOP_SIZE)
-                                    as usize],
-                        )
-                        .unwrap();
-                    }
-
-                    if (scoop + 1) % 128 == 0 {
-                        match &mut pb {
-                            Some(pb) => {
-                                pb.add(nonces_to_write * SCOOP_SIZE * 128);
-                            }
-                            None => (),
-                        }
-                    }
-                }
-            }
-            nonces_written += nonces_to_write;
-
-            // thread end
-            if task.nonces == nonces_written {
-                match &mut pb {
-                    Some(pb) => {
-                        pb.finish_print("Writer done.");
-                    }
-                    None => (),
-                }
-                tx_empty_buffers.send(buffer).unwrap();
-                break;
-            }
-
-            if !task.benchmark {
-                if write_resume_info(&filename, nonces_written).is_err() {
-                    println!("Error: couldn't write resume info");
-                }
-            }
-            tx_empty_buffers.send(buffer).unwrap();
-        }
-    }
-}
-
-pub fn read_resume_info(file: &Path) -> Result<u64, Error> {
-    let mut file = open_r(&file)?;
-    file.seek(SeekFrom::End(-8))?;
-      
-
-    let mut progress = [0u8; 4];
-    let mut double_monkey = [0u8; 4];
-
-    file.read_exact(&mut progress[0..4])?;
-    file.read_exact(&mut double_monkey[0..4])?;
-
-    if double_monkey == [0xAF, 0xFE, 0xAF, 0xFE] {
-        Ok(u64::from(as_u32_le(progress)))
-    } else {
-        Err(Error::new(ErrorKind::Other, "End marker not found"))
-    }
-}
-
-pub fn write_resume_info(file: &Path, nonces_written: u64) -> Result<(), Error> {
-    let mut file = open(&file)?;
-    file.seek(SeekFrom::End(-8))?;
-
-    let progress = as_u8_le(nonces_written as u32);
-    let double_monkey = [0xAF, 0xFE, 0xAF, 0xFE];
-
-    file.write_all(&progress[0..4])?;
-    file.write_all(&double_monkey[0..4])?;
-    Ok(())    
-}
-
-fn as_u32_le(array: [u8; 4]) -> u32 {
-    u32::from(array[0])
-        + (u32::from(array[1]) << 8)
-        + (u32::from(array[2]) << 16)
-        + (u32::from(array[3]) << 24)
-}
-
-fn as_u8_le(x: u32) -> [u8; 4] {
-    let b1: u8 = (x & 0xff) as u8;
-    let b2: u8 = ((x >> 8) & 0xff) as u8;
-    let b3: u8 = ((x >> 16) & 0xff) as u8;
-    let b4: u8 = ((x >> 24) & 0xff) as u8;
-    [b1, b2, b3, b4]
-}
+use crate::plotter::{PlotterTask, NONCE_SIZE, SCOOP_SIZE};
+use crate::buffer::PageAlignedByteBuffer;
+use crate::pressure::MemoryGovernor;
+use crate::queue::{DepthGovernor, MeteredReceiver};
+use crate::utils::{open, open_r, open_using_direct_io};
+use crossbeam_channel::Sender;
+use fs2::FileExt;
+use std::cmp::min;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// what a writer put on its drive and how long the writes took
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WriterStats {
+    pub bytes: u64,
+    pub busy: Duration,
+}
+
+// One writer per plot file, emptied buffers go back to the scheduler tagged with its drive index.
+// A write error stops the writer from writing, it still returns buffers so hashing can finish,
+// and the plot file keeps its resume point.
+#[allow(clippy::too_many_arguments)]
+pub fn create_writer_thread(
+    task: Arc<PlotterTask>,
+    drive: usize,
+    mut nonces_written: u64,
+    mut pb: Option<pbr::ProgressBar<pbr::Pipe>>,
+    rx_buffers_to_writer: MeteredReceiver<PageAlignedByteBuffer>,
+    tx_empty_buffers: Sender<(usize, PageAlignedByteBuffer)>,
+    governor: Option<Arc<MemoryGovernor>>,
+    depth: Option<Arc<DepthGovernor>>,
+) -> impl FnOnce() -> Result<WriterStats, String> {
+    move || {
+        let mut stats = WriterStats::default();
+        let filename = Path::new(&task.output_path).join(format!(
+            "{}_{}_{}",
+            task.numeric_id, task.start_nonce, task.nonces
+        ));
+
+        // two-phase mode: buffers go scoop-major to a temp file first and are
+        // transposed into the plot file once hashing is done
+        let mut error = None;
+        let mut transpose_file = match &task.transpose_path {
+            Some(dir) if !task.benchmark => {
+                match TransposeFile::create(Path::new(dir), &filename, nonces_written, task.nonces) {
+                    Ok(x) => Some(x),
+                    Err(e) => {
+                        error = Some(format!("couldn't create the transpose file for {}: {}", filename.display(), e));
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        let mut backend = task.write_backend.create();
+
+        for mut buffer in rx_buffers_to_writer {
+            let bs = buffer.as_mut_slice();
+            let buffer_size = bs.len() as u64;
+            let nonces_to_write = min(buffer_size / NONCE_SIZE, task.nonces - nonces_written);
+
+            if !task.benchmark && error.is_none() {
+                let started = Instant::now();
+                let written = if let Some(transpose_file) = &mut transpose_file {
+                    transpose_file
+                        .append(bs, nonces_to_write, &mut pb)
+                        .map_err(|e| format!("couldn't write the transpose file for {}: {}", filename.display(), e))
+                } else {
+                    let file = if task.direct_io {
+                        open_using_direct_io(&filename)
+                    } else {
+                        open(&filename)
+                    };
+                    file.and_then(|file| {
+                        backend.write_scoops(
+                            &file,
+                            bs,
+                            task.nonces,
+                            nonces_written,
+                            nonces_to_write,
+                            &mut |bytes| {
+                                if let Some(pb) = &mut pb {
+                                    pb.add(bytes);
+                                }
+                            },
+                        )
+                    })
+                    .map_err(|e| format!("couldn't write {}: {}", filename.display(), e))
+                };
+                stats.busy += started.elapsed();
+                match written {
+                    Ok(()) => stats.bytes += nonces_to_write * NONCE_SIZE,
+                    Err(e) => error = Some(e),
+                }
+            }
+            if error.is_some() {
+                tx_empty_buffers.send((drive, buffer)).unwrap();
+                continue;
+            }
+            nonces_written += nonces_to_write;
+
+            // thread end
+            if task.nonces == nonces_written {
+                // second phase, the last buffer serves as staging area
+                if let Some(transpose_file) = transpose_file.take() {
+                    if let Some(pb) = &mut pb {
+                        pb.set(0);
+                        pb.message("Transposing: ");
+                    }
+                    let started = Instant::now();
+                    let file = if task.direct_io {
+                        open_using_direct_io(&filename)
+                    } else {
+                        open(&filename)
+                    };
+                    let transposed = file.and_then(|mut file| {
+                        transpose_file.transpose_into(&mut file, task.nonces, bs, &mut pb)
+                    });
+                    if let Err(e) = transposed {
+                        error = Some(format!("couldn't transpose into {}: {}", filename.display(), e));
+                    }
+                    stats.busy += started.elapsed();
+                }
+                match &mut pb {
+                    Some(pb) if error.is_some() => {
+                        pb.finish_print("Writer stopped.");
+                    }
+                    Some(pb) => {
+                        pb.finish_print("Writer done.");
+                    }
+                    None => (),
+                }
+                tx_empty_buffers.send((drive, buffer)).unwrap();
+                break;
+            }
+
+            // in two-phase mode the plot file only advances once the transposition is done
+            if !task.benchmark && transpose_file.is_none() {
+                if write_resume_info(&filename, nonces_written).is_err() {
+                    println!("Error: couldn't write resume info");
+                }
+            }
+            // between buffers is the only safe point to resize, add or free them
+            let buffer = match &governor {
+                Some(governor) => governor.recycle(buffer),
+                None => buffer,
+            };
+            let buffers = match &depth {
+                Some(depth) => depth.recycle(drive, buffer),
+                None => vec![buffer],
+            };
+            for buffer in buffers {
+                tx_empty_buffers.send((drive, buffer)).unwrap();
+            }
+        }
+        // the hasher stopped before the file was complete
+        if nonces_written < task.nonces {
+            if let Some(pb) = &mut pb {
+                pb.finish_print("Writer stopped.");
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(stats),
+        }
+    }
+}
+
+// Temp file of the two-phase mode. Every hashed buffer is appended as one chunk
+// holding its nonces scoop by scoop, so both phases only ever write sequentially.
+// An interrupted run restarts the temp file from the plot file's resume point.
+struct TransposeFile {
+    path: PathBuf,
+    file: File,
+    start_nonce: u64,
+    chunks: Vec<u64>,
+}
+
+impl TransposeFile {
+    fn create(dir: &Path, plot: &Path, start_nonce: u64, nonces: u64) -> Result<TransposeFile, Error> {
+        let path = dir.join(format!(
+            "{}.transpose",
+            plot.file_name().unwrap().to_string_lossy()
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        file.allocate((nonces - start_nonce) * NONCE_SIZE)?;
+        Ok(TransposeFile {
+            path,
+            file,
+            start_nonce,
+            chunks: Vec::new(),
+        })
+    }
+
+    // buffers are scoop-major with a stride of buffer_size / NONCE_SIZE, only
+    // the first `nonces` of every scoop are valid
+    fn append(
+        &mut self,
+        bs: &[u8],
+        nonces: u64,
+        pb: &mut Option<pbr::ProgressBar<pbr::Pipe>>,
+    ) -> Result<(), Error> {
+        let stride = bs.len() as u64 / NONCE_SIZE * SCOOP_SIZE;
+        for scoop in 0..4096 {
+            let local_addr = scoop * stride;
+            self.file
+                .write_all(&bs[local_addr as usize..(local_addr + nonces * SCOOP_SIZE) as usize])?;
+            if (scoop + 1) % 128 == 0 {
+                if let Some(pb) = pb {
+                    pb.add(nonces * SCOOP_SIZE * 128);
+                }
+            }
+        }
+        self.chunks.push(nonces);
+        Ok(())
+    }
+
+    // gathers every scoop from all chunks in the staging buffer and streams
+    // it to its region of the plot file, one seek per scoop
+    fn transpose_into(
+        mut self,
+        plot: &mut File,
+        plot_nonces: u64,
+        staging: &mut [u8],
+        pb: &mut Option<pbr::ProgressBar<pbr::Pipe>>,
+    ) -> Result<(), Error> {
+        let staging_nonces = staging.len() as u64 / SCOOP_SIZE;
+        let nonces: u64 = self.chunks.iter().sum();
+        for scoop in 0..4096 {
+            plot.seek(SeekFrom::Start(
+                (scoop * plot_nonces + self.start_nonce) * SCOOP_SIZE,
+            ))?;
+            let mut chunk_addr = 0;
+            let mut staged = 0;
+            for &chunk in &self.chunks {
+                let mut done = 0;
+                while done < chunk {
+                    let n = min(chunk - done, staging_nonces - staged);
+                    self.file.seek(SeekFrom::Start(
+                        chunk_addr + (scoop * chunk + done) * SCOOP_SIZE,
+                    ))?;
+                    self.file.read_exact(
+                        &mut staging[(staged * SCOOP_SIZE) as usize
+                            ..((staged + n) * SCOOP_SIZE) as usize],
+                    )?;
+                    staged += n;
+                    done += n;
+                    if staged == staging_nonces {
+                        plot.write_all(staging)?;
+                        staged = 0;
+                    }
+                }
+                chunk_addr += chunk * NONCE_SIZE;
+            }
+            if staged > 0 {
+                plot.write_all(&staging[..(staged * SCOOP_SIZE) as usize])?;
+            }
+            if let Some(pb) = pb {
+                pb.add(nonces * SCOOP_SIZE);
+            }
+        }
+        plot.sync_all()?;
+        drop(self.file);
+        fs::remove_file(&self.path)
+    }
+}
+
+pub fn read_resume_info(file: &Path) -> Result<u64, Error> {
+    let mut file = open_r(&file)?;
+    file.seek(SeekFrom::End(-8))?;
+      
+
+    let mut progress = [0u8; 4];
+    let mut double_monkey = [0u8; 4];
+
+    file.read_exact(&mut progress[0..4])?;
+    file.read_exact(&mut double_monkey[0..4])?;
+
+    if double_monkey == [0xAF, 0xFE, 0xAF, 0xFE] {
+        Ok(u64::from(as_u32_le(progress)))
+    } else {
+        Err(Error::new(ErrorKind::Other, "End marker not found"))
+    }
+}
+
+pub fn write_resume_info(file: &Path, nonces_written: u64) -> Result<(), Error> {
+    let mut file = open(&file)?;
+    file.seek(SeekFrom::End(-8))?;
+
+    let progress = as_u8_le(nonces_written as u32);
+    let double_monkey = [0xAF, 0xFE, 0xAF, 0xFE];
+
+    file.write_all(&progress[0..4])?;
+    file.write_all(&double_monkey[0..4])?;
+    Ok(())    
+}
+
+fn as_u32_le(array: [u8; 4]) -> u32 {
+    u32::from(array[0])
+        + (u32::from(array[1]) << 8)
+        + (u32::from(array[2]) << 16)
+        + (u32::from(array[3]) << 24)
+}
+
+fn as_u8_le(x: u32) -> [u8; 4] {
+    let b1: u8 = (x & 0xff) as u8;
+    let b2: u8 = ((x >> 8) & 0xff) as u8;
+    let b3: u8 = ((x >> 16) & 0xff) as u8;
+    let b4: u8 = ((x >> 24) & 0xff) as u8;
+    [b1, b2, b3, b4]
+}
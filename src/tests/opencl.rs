@@ -0,0 +1,34 @@
+// Runs the benchmark on the first OpenCL device with every nonce checked
+// against the CPU, through the binary as the GUI and CLI use it. Needs an
+// OpenCL runtime, PoCL will do.
+#![cfg(feature = "opencl")]
+
+use std::fs;
+use std::process::{self, Command};
+
+const NONCES: u64 = 1024;
+
+#[test]
+fn gpu_benchmark_matches_cpu() {
+    let report = std::env::temp_dir().join(format!("signum-plotter-opencl-{}.json", process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_signum-plotter"))
+        .args(&["benchmark", "--gpu", "0", "--threads", "none", "--gpu-check", "1:abort"])
+        .args(&["--nonces", &NONCES.to_string(), "--report"])
+        .arg(&report)
+        .output()
+        .expect("can't run signum-plotter");
+    assert!(
+        output.status.success(),
+        "benchmark failed: {}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let json = fs::read_to_string(&report).expect("no report written");
+    fs::remove_file(&report).unwrap();
+    let report: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(report["nonces"], NONCES);
+    // the gpu alone hashed, and every nonce matched
+    let devices = report["devices"].as_array().unwrap();
+    assert_eq!(devices.len(), 1, "{}", json);
+    assert_eq!(devices[0]["nonces"], NONCES);
+}